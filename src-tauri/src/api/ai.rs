@@ -1,5 +1,4 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::Mutex;
+use std::{collections::HashMap, path::PathBuf};
 
 use linfa::traits::{Fit, Predict};
 use linfa::DatasetBase;
@@ -11,7 +10,7 @@ use rand_xoshiro::Xoshiro256Plus;
 use serde::Serialize;
 use tauri::Manager;
 
-use crate::logic::show_packets::FormatedPacket;
+use crate::logic::session::{now, SessionId, SessionManager};
 
 #[derive(Serialize)]
 pub struct Detect {
@@ -21,21 +20,23 @@ pub struct Detect {
     avg_dst_ip_freq: f64,
     avg_packet_rate: f64,
     avg_packet_length: f64,
+    /// Evidence file of the trigger fired for this detection, for pre-trigger captures.
+    evidence: Option<PathBuf>,
 }
 
 #[tauri::command]
-pub fn dos_detect(app_handle: tauri::AppHandle) -> Vec<Detect> {
+pub fn dos_detect(app_handle: tauri::AppHandle, session_id: SessionId) -> Vec<Detect> {
     let mut potential_dos = Vec::new();
-    if let Some(packets) = app_handle.try_state::<Mutex<VecDeque<FormatedPacket>>>() {
+    if let Some(session) = app_handle.state::<SessionManager>().get(session_id) {
         let rng = Xoshiro256Plus::seed_from_u64(42);
-        let packets = packets.lock().unwrap();
+        let packets = session.store();
         let mut src_ip_counts = HashMap::new();
         let mut dst_ip_counts = HashMap::new();
         let mut packet_timestamps: Vec<f64> = Vec::new();
         if packets.len() == 0 {
             return vec![];
         }
-        for packet in &*packets {
+        for packet in packets.iter() {
             *src_ip_counts.entry(packet.src_ip).or_insert(0) += 1;
             *dst_ip_counts.entry(packet.dst_ip).or_insert(0) += 1;
             packet_timestamps.push(packet.timestamp);
@@ -58,7 +59,7 @@ pub fn dos_detect(app_handle: tauri::AppHandle) -> Vec<Detect> {
 
         // Create feature matrix
         let mut features = Vec::new();
-        for packet in &*packets {
+        for packet in packets.iter() {
             let src_ip_freq = *src_ip_counts.get(&packet.src_ip).unwrap_or(&0) as f64;
             let dst_ip_freq = *dst_ip_counts.get(&packet.dst_ip).unwrap_or(&0) as f64;
             let protocol_code = match packet.protocol.as_str() {
@@ -147,6 +148,7 @@ pub fn dos_detect(app_handle: tauri::AppHandle) -> Vec<Detect> {
                     avg_dst_ip_freq,
                     avg_packet_rate,
                     avg_packet_length,
+                    evidence: None,
                 };
                 potential_dos.push(dos);
                 log::info!(
@@ -155,6 +157,20 @@ pub fn dos_detect(app_handle: tauri::AppHandle) -> Vec<Detect> {
             );
             }
         }
+
+        if !potential_dos.is_empty() {
+            if let Some(buffer) = session.trigger().as_mut() {
+                let label = format!("dos_detect: {} suspicious clusters", potential_dos.len());
+                match buffer.fire(label, now()) {
+                    Ok(trigger) => {
+                        for dos in potential_dos.iter_mut() {
+                            dos.evidence = Some(trigger.path.clone());
+                        }
+                    }
+                    Err(e) => log::error!("failed to write evidence file: {}", e),
+                }
+            }
+        }
     }
     potential_dos
 }
@@ -0,0 +1,67 @@
+use crate::{
+    api::sessions::session,
+    errors::AppResult,
+    logic::{
+        craft::{self, FrameEdit, FrameSpec},
+        frame::RawFrame,
+        get_interface,
+        session::SessionId,
+        show_packets::{dissect, FormatedPacket},
+    },
+};
+
+/// Builds a frame from `spec` and returns it dissected, without sending it.
+#[tauri::command]
+pub fn build_packet(interface: String, spec: FrameSpec) -> AppResult<FormatedPacket> {
+    let interface = get_interface(interface)?;
+    let data = spec.build(&interface)?;
+    Ok(preview(&interface.name, &data))
+}
+
+/// Builds a frame from `spec` and sends it on `interface`, `count` times in a row (once by
+/// default, at most [`craft::MAX_SEND_COUNT`]). Returns what was sent, dissected.
+#[tauri::command]
+pub fn send_packet(
+    interface: String,
+    spec: FrameSpec,
+    count: Option<u32>,
+) -> AppResult<FormatedPacket> {
+    log::info!("send packet on {}", interface);
+    let interface = get_interface(interface)?;
+    let data = spec.build(&interface)?;
+    craft::inject(&interface, &data, count.unwrap_or(1))?;
+    Ok(preview(&interface.name, &data))
+}
+
+/// Sends a packet of the session again, to reproduce what it caused. It goes out on the
+/// interface it was captured on unless another is given, with `edit` applied if set.
+#[tauri::command]
+pub fn resend_packet(
+    app_handle: tauri::AppHandle,
+    session_id: SessionId,
+    number: u32,
+    interface: Option<String>,
+    edit: Option<FrameEdit>,
+) -> AppResult<FormatedPacket> {
+    log::info!("resend packet {} of session {}", number, session_id);
+    let frame = session(&app_handle, session_id)?
+        .store()
+        .get(number)
+        .map(|packet| packet.frame.clone())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Packet {} is no longer held by session {}",
+                number,
+                session_id
+            )
+        })?;
+    let interface = get_interface(interface.unwrap_or_else(|| frame.interface.clone()))?;
+    let data = craft::rebuild(&frame, &interface, edit.as_ref())?;
+    craft::inject(&interface, &data, 1)?;
+    Ok(preview(&interface.name, &data))
+}
+
+fn preview(interface: &str, data: &[u8]) -> FormatedPacket {
+    let mut count = 0;
+    dissect(RawFrame::captured_now(interface, data, None), &mut count).0
+}
@@ -0,0 +1,52 @@
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    time::Duration,
+};
+
+use crate::{
+    api::sessions::session,
+    errors::AppResult,
+    logic::{
+        discovery::{self, DiscoveredHost, DEFAULT_TIMEOUT},
+        get_interface,
+        session::SessionId,
+    },
+};
+
+/// Finds the live hosts on the subnets of `interface`. When a session is given, IPv6 addresses
+/// seen in it are solicited too, and each host is linked to the packets it sent there.
+#[tauri::command]
+pub async fn discover_hosts(
+    app_handle: tauri::AppHandle,
+    interface: String,
+    session_id: Option<SessionId>,
+    timeout_ms: Option<u64>,
+) -> AppResult<Vec<DiscoveredHost>> {
+    log::info!("discover hosts on {}", interface);
+    let interface = get_interface(interface)?;
+    let session = session_id.map(|id| session(&app_handle, id)).transpose()?;
+    let candidates: Vec<Ipv6Addr> = session
+        .as_ref()
+        .map(|session| {
+            session
+                .store()
+                .iter()
+                .filter_map(|packet| match packet.src_ip {
+                    Some(IpAddr::V6(ip)) => Some(ip),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let timeout = timeout_ms.map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+    // probing takes seconds, which would hold up a worker of the async runtime
+    let mut hosts = tauri::async_runtime::spawn_blocking(move || {
+        discovery::discover(&interface, &candidates, timeout)
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
+    if let Some(session) = session {
+        discovery::link_packets(&mut hosts, session.store().iter());
+    }
+    Ok(hosts)
+}
@@ -0,0 +1,25 @@
+use crate::{
+    errors::AppResult,
+    logic::dissectors::{registry, registry_mut, PortMapping, Transport},
+};
+
+/// Every protocol with a dissector, for choosing what to decode a port as.
+#[tauri::command]
+pub fn get_dissectors() -> Vec<String> {
+    registry().names().into_iter().map(str::to_string).collect()
+}
+
+/// Which ports are dissected as which protocol.
+#[tauri::command]
+pub fn get_port_mappings() -> Vec<PortMapping> {
+    registry().port_mappings()
+}
+
+/// Decodes traffic on `port` as `protocol` from now on, e.g. 8443 as TLS, or stops decoding it
+/// by port when `protocol` isn't given. Stored packets keep their dissection until they're
+/// reparsed.
+#[tauri::command]
+pub fn map_port(transport: Transport, port: u16, protocol: Option<String>) -> AppResult<()> {
+    log::info!("map {} port {} to {:?}", transport, port, protocol);
+    registry_mut().map_port(transport, port, protocol.as_deref())
+}
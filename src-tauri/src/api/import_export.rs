@@ -1,68 +1,227 @@
-use std::{collections::VecDeque, fs, sync::Mutex};
+use std::{collections::VecDeque, fs, path::Path, sync::atomic::AtomicBool, thread};
 
 use tauri::Manager;
-use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use tauri_plugin_dialog::DialogExt;
 
-use crate::{errors::AppResult, logic::show_packets::FormatedPacket};
+use crate::{
+    api::{packets::capture_into_store, sessions::session},
+    errors::{AppError, AppResult},
+    logic::{
+        link,
+        pcap::{read_capture, write_pcap, write_pcapng, CaptureFormat, CaptureRecord},
+        reassembly::Reassembler,
+        session::{SessionId, SessionManager},
+        show_packets::FormatedPacket,
+        source::{run_capture, FileSource, ReplaySpeed},
+        stats::CaptureCounters,
+        stop::StopConditions,
+    },
+};
 
 #[tauri::command]
-pub async fn export_packets(app_handle: tauri::AppHandle) -> AppResult<()> {
+pub async fn export_packets(app_handle: tauri::AppHandle, session_id: SessionId) -> AppResult<()> {
+    let session = session(&app_handle, session_id)?;
     let file_path = app_handle
         .dialog()
         .file()
-        .set_file_name("packets.json")
-        .add_filter("My Filter", &["json"])
+        .set_file_name("packets.pcapng")
+        .add_filter("pcapng", &["pcapng"])
+        .add_filter("pcap", &["pcap"])
+        .add_filter("JSON", &["json"])
         .blocking_save_file();
     match file_path {
         Some(path) => {
-            let packets = app_handle
-                .try_state::<Mutex<VecDeque<FormatedPacket>>>()
-                .ok_or_else(|| anyhow::anyhow!("Packets state not found"))?;
+            let path = path
+                .as_path()
+                .ok_or_else(|| anyhow::anyhow!("Unsupported file path"))?;
+            let packets = session
+                .store()
+                .iter()
+                .cloned()
+                .collect::<VecDeque<FormatedPacket>>();
 
-            let packets = packets
-                .lock()
-                .map_err(|e| anyhow::anyhow!("Mutex lock failed: {}", e))?
-                .clone();
-
-            let serialized_data =
-                serde_json::to_string_pretty(&packets).map_err(anyhow::Error::from)?;
-            fs::write(path.as_path().unwrap(), serialized_data)
+            let serialized_data = match CaptureFormat::from_path(path) {
+                CaptureFormat::Json => {
+                    serde_json::to_vec_pretty(&packets).map_err(anyhow::Error::from)?
+                }
+                format => {
+                    let records = to_capture_records(&packets)?;
+                    let mut buf = Vec::new();
+                    if format == CaptureFormat::Pcap {
+                        write_pcap(&mut buf, &records).map_err(anyhow::Error::from)?;
+                    } else {
+                        write_pcapng(&mut buf, &records).map_err(anyhow::Error::from)?;
+                    }
+                    buf
+                }
+            };
+            fs::write(path, serialized_data)
                 .map_err(|_| anyhow::anyhow!("Cant write data to file"))?;
             Ok(())
         }
         None => {
-            println!("No file selected");
+            log::info!("No file selected");
             Err(anyhow::anyhow!("No file selected").into())
         }
     }
 }
 
+/// Loads a capture file into a new, already finished session.
 #[tauri::command]
-pub async fn import_packets(app_handle: tauri::AppHandle) -> AppResult<()> {
+pub async fn import_packets(app_handle: tauri::AppHandle) -> AppResult<SessionId> {
     let file_path = app_handle
         .dialog()
         .file()
-        .add_filter("My Filter", &["json"])
+        .add_filter("Captures", &["pcapng", "pcap", "cap", "json"])
         .blocking_pick_file();
     match file_path {
         Some(path) => {
-            let packets = app_handle
-                .try_state::<Mutex<VecDeque<FormatedPacket>>>()
-                .ok_or_else(|| anyhow::anyhow!("Packets state not found"))?;
-
-            let imported_data =
-                fs::read_to_string(path.as_path().unwrap()).map_err(anyhow::Error::from)?;
+            let path = path
+                .as_path()
+                .ok_or_else(|| anyhow::anyhow!("Unsupported file path"))?;
+            let imported_data = fs::read(path).map_err(anyhow::Error::from)?;
 
-            let data: VecDeque<FormatedPacket> =
-                serde_json::from_str(&imported_data).map_err(anyhow::Error::from)?;
+            let sessions = app_handle.state::<SessionManager>();
+            let session = match CaptureFormat::detect(&imported_data) {
+                CaptureFormat::Json => {
+                    let data: VecDeque<FormatedPacket> =
+                        serde_json::from_slice(&imported_data).map_err(anyhow::Error::from)?;
+                    // older exports carry no frame bytes, so keep their rendered fields as they are
+                    let mut reassembler = Reassembler::default();
+                    let data: VecDeque<FormatedPacket> = data
+                        .into_iter()
+                        .map(|packet| {
+                            let mut packet = if packet.frame.data.is_empty() {
+                                packet
+                            } else {
+                                packet.redissect()
+                            };
+                            reassembler.add(&mut packet);
+                            packet
+                        })
+                        .collect();
+                    let session = sessions.create(file_name(path), None);
+                    session.store().replace(data);
+                    session
+                }
+                _ => {
+                    let records = read_capture(&imported_data)?;
+                    check_link_types(path, &records)?;
+                    // created first so the import is counted in the session's statistics
+                    let session = sessions.create(file_name(path), None);
+                    match from_capture_records(records, session.counters()) {
+                        Ok(data) => session.store().replace(data),
+                        Err(e) => {
+                            sessions.remove(session.id);
+                            return Err(e);
+                        }
+                    }
+                    session
+                }
+            };
+            session.mark_stopped();
+            Ok(session.id)
+        }
+        None => Err(anyhow::anyhow!("No file selected").into()),
+    }
+}
 
-            let mut packets = packets
-                .lock()
-                .map_err(|e| anyhow::anyhow!("Mutex lock failed: {}", e))?;
+/// Plays a capture file back into a new session as if it were a live interface.
+#[tauri::command]
+pub async fn replay_packets(
+    app_handle: tauri::AppHandle,
+    original_timing: bool,
+) -> AppResult<SessionId> {
+    let file_path = app_handle
+        .dialog()
+        .file()
+        .add_filter("Captures", &["pcapng", "pcap", "cap"])
+        .blocking_pick_file();
+    match file_path {
+        Some(path) => {
+            let path = path
+                .as_path()
+                .ok_or_else(|| anyhow::anyhow!("Unsupported file path"))?;
+            let imported_data = fs::read(path).map_err(anyhow::Error::from)?;
+            let records = read_capture(&imported_data)?;
+            check_link_types(path, &records)?;
+            let speed = if original_timing {
+                ReplaySpeed::Original
+            } else {
+                ReplaySpeed::AsFastAsPossible
+            };
 
-            *packets = data;
-            Ok(())
+            let session = app_handle
+                .state::<SessionManager>()
+                .create(file_name(path), None);
+            let id = session.id;
+            thread::spawn(move || {
+                let mut source = FileSource::new(records, speed);
+                capture_into_store(
+                    &app_handle,
+                    &session,
+                    &mut source,
+                    StopConditions::default(),
+                    None,
+                );
+            });
+            Ok(id)
         }
         None => Err(anyhow::anyhow!("No file selected").into()),
     }
 }
+
+/// What a session loaded from `path` is called in the session list.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Fails rather than leave out packets imported without their frame bytes, e.g. from an old
+/// JSON export, which only JSON can hold.
+fn to_capture_records(packets: &VecDeque<FormatedPacket>) -> AppResult<Vec<CaptureRecord>> {
+    let frameless = packets
+        .iter()
+        .filter(|packet| packet.frame.data.is_empty())
+        .count();
+    if frameless > 0 {
+        return Err(anyhow::anyhow!(
+            "{} packets have no frame bytes to write to a capture file. Export them as JSON instead",
+            frameless
+        )
+        .into());
+    }
+    Ok(packets
+        .iter()
+        .map(|packet| packet.frame.to_record())
+        .collect())
+}
+
+/// Fails when a capture holds frames but none of them can be dissected. Files mixing link
+/// types are still loaded, and the unsupported frames skipped.
+fn check_link_types(path: &Path, records: &[CaptureRecord]) -> AppResult<()> {
+    match records.first() {
+        Some(record) if !records.iter().any(|r| link::is_supported(r.link_type)) => {
+            Err(AppError::UnsupportedLinkType(format!(
+                "{} (link type {})",
+                file_name(path),
+                record.link_type
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn from_capture_records(
+    records: Vec<CaptureRecord>,
+    counters: &CaptureCounters,
+) -> AppResult<VecDeque<FormatedPacket>> {
+    let mut packets = VecDeque::with_capacity(records.len());
+    let mut source = FileSource::new(records, ReplaySpeed::AsFastAsPossible);
+    run_capture(&mut source, &AtomicBool::new(true), counters, |batch| {
+        packets.extend(batch)
+    })
+    .map_err(anyhow::Error::from)?;
+    Ok(packets)
+}
@@ -1,3 +1,8 @@
 pub mod ai;
+pub mod craft;
+pub mod discovery;
+pub mod dissectors;
 pub mod import_export;
 pub mod packets;
+pub mod sessions;
+pub mod triggers;
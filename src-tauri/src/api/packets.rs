@@ -1,137 +1,408 @@
+use crate::api::sessions::session;
+use crate::errors::{AppError, AppResult};
 use crate::logic::{
-    create_chanel, get_interface,
-    show_packets::{
-        get_payload_data, process_arp_packet, process_ipv4_packet, process_ipv6_packet,
-        DetailedInfo, FormatedPacket,
-    },
-};
-use pnet::packet::{
-    ethernet::{EtherTypes, EthernetPacket},
-    Packet,
+    bpf::{self, BpfProgram},
+    get_interface,
+    interfaces::{InterfaceInfo, InterfaceMonitor},
+    reassembly::Reassembler,
+    ring::{self, RingBufferOptions, RingWriter},
+    session::{now, Session, SessionId, SessionInfo, SessionManager},
+    show_packets::{FormatedPacket, PacketRow},
+    source::{run_capture, LiveSource, PacketSource},
+    stats::CaptureStats,
+    stop::{StopConditions, StopReason, StopTracker},
+    store::{DisplayFilter, PacketPage, StoreLimits, StoreUsage},
+    trigger::{TriggerBuffer, TriggerOptions},
 };
 use serde::{Deserialize, Serialize};
 use std::{
     collections::VecDeque,
+    io,
     net::IpAddr,
     sync::{
         atomic::{AtomicBool, Ordering},
         Mutex,
     },
-    thread::{self, sleep},
+    thread,
     time::Duration,
 };
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 #[derive(Serialize, Deserialize)]
 struct FilterState {
     ip: IpAddr,
     protocol: String,
 }
+/// Every interface with its addresses, flags and traffic counters. The rates are worked out
+/// from the previous call, so polling this shows which interface is busy.
 #[tauri::command]
-pub fn get_interfaces() -> Vec<String> {
-    pnet::datalink::interfaces()
-        .iter()
-        .map(|int| int.name.clone())
-        .collect()
+pub fn get_interfaces(monitor: tauri::State<InterfaceMonitor>) -> Vec<InterfaceInfo> {
+    monitor.list()
 }
 
+/// Largest frame the kernel hands over when no snaplen is given.
+const DEFAULT_SNAPLEN: usize = 262_144;
+
+/// Starts capturing on `interface` in a new session, next to any that are already running.
 #[tauri::command]
-pub fn start_watch(app_handle: tauri::AppHandle, interface: String) {
+pub fn start_watch(
+    app_handle: tauri::AppHandle,
+    interface: String,
+    snaplen: Option<usize>,
+    filter: Option<String>,
+    stop: Option<StopConditions>,
+    ring: Option<RingBufferOptions>,
+    trigger: Option<TriggerOptions>,
+) -> AppResult<SessionId> {
     log::info!("start watch");
-    if let Some(running) = app_handle.try_state::<AtomicBool>() {
-        running.store(true, Ordering::SeqCst);
+    let interface = get_interface(interface)?;
+    if interface.index != 0 && !interface.is_up() {
+        return Err(AppError::InterfaceDown(interface.name));
     }
-    if let Some(packets) = app_handle.try_state::<Mutex<VecDeque<FormatedPacket>>>() {
-        packets.lock().unwrap().clear();
+    // compile before creating the session, so a typo in the filter leaves nothing behind
+    let program: Option<BpfProgram> = match filter.as_deref().map(str::trim) {
+        Some(expr) if !expr.is_empty() => {
+            if cfg!(not(target_os = "linux")) {
+                return Err(anyhow::anyhow!("Capture filters are only supported on Linux").into());
+            }
+            log::info!("capture filter: {}", expr);
+            Some(bpf::compile(
+                expr,
+                snaplen.unwrap_or(DEFAULT_SNAPLEN) as u32,
+                LiveSource::framing(&interface),
+            )?)
+        }
+        _ => None,
+    };
+    if let Some(stop) = &stop {
+        stop.max_duration()
+            .map_err(|e| anyhow::anyhow!("Invalid capture duration: {}", e))?;
     }
+    // opened here rather than on the capture thread, so permission errors reach the UI
+    let mut source = LiveSource::new(&interface, snaplen, program.as_ref())
+        .map_err(|e| AppError::capture(&interface.name, e))?;
+    let ring = ring
+        .map(|options| RingWriter::new(options, &interface.name))
+        .transpose()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidInput => anyhow::anyhow!("{}", e),
+            _ => anyhow::anyhow!("Cant use the capture directory: {}", e),
+        })?;
 
-    let interface = get_interface(interface);
-    log::info!("listen on: {}", interface.name);
+    let filter = filter
+        .map(|expr| expr.trim().to_string())
+        .filter(|expr| !expr.is_empty());
+    let session = app_handle
+        .state::<SessionManager>()
+        .create(interface.name.clone(), filter);
+    log::info!("session {}: listen on {}", session.id, interface.name);
+    if ring.is_some() {
+        // the files hold the capture, so the store only needs a recent window
+        let mut store = session.store();
+        let limits = ring::window_limits(store.limits().clone());
+        // no spill file, so this can't fail
+        let _ = store.set_limits(limits);
+    }
+    if let Some(options) = trigger {
+        match TriggerBuffer::new(options, &format!("session{}", session.id)) {
+            Ok(buffer) => session.set_trigger(buffer),
+            Err(e) => {
+                app_handle.state::<SessionManager>().remove(session.id);
+                return Err(anyhow::anyhow!("Cant use the evidence directory: {}", e).into());
+            }
+        }
+    }
 
+    let id = session.id;
     thread::spawn(move || {
-        let mut count_fp = 0;
-        let (_, mut rx) = create_chanel(interface.clone());
-
-        let handle = app_handle.state::<AtomicBool>();
-        while handle.load(Ordering::SeqCst) {
-            if let Ok(packet) = rx.next() {
-                if let Some(ethernet_packet) = EthernetPacket::new(packet) {
-                    let formated_packet = match ethernet_packet.get_ethertype() {
-                        EtherTypes::Arp => {
-                            process_arp_packet(&ethernet_packet, &mut count_fp, "ARP".to_string())
-                        }
-                        EtherTypes::Rarp => {
-                            process_arp_packet(&ethernet_packet, &mut count_fp, "RARP".to_string())
-                        }
-                        EtherTypes::Ipv4 => process_ipv4_packet(&ethernet_packet, &mut count_fp),
-                        EtherTypes::Ipv6 => process_ipv6_packet(&ethernet_packet, &mut count_fp),
-                        _ => None,
-                    };
-                    if let Some(mut fp) = formated_packet {
-                        let src_mac = ethernet_packet.get_source().to_string();
-                        let dst_mac = ethernet_packet.get_destination().to_string();
-                        let frame_type = ethernet_packet.get_ethertype().to_string();
-                        let payload_length = ethernet_packet.payload().len();
-                        let packet_length = ethernet_packet.packet().len();
-                        let payload_data = get_payload_data(ethernet_packet.payload());
-
-                        let det_inf = DetailedInfo {
-                            src_mac,
-                            dst_mac,
-                            frame_type,
-                            packet_length,
-                            payload_length,
-                            interface: interface.name.clone(),
-                            payload_data,
-                        };
-                        fp.detailed_info = Some(det_inf);
-
-                        if let Some(packets) =
-                            app_handle.try_state::<Mutex<VecDeque<FormatedPacket>>>()
-                        {
-                            packets.lock().unwrap().push_back(fp);
-                        }
-                    }
+        capture_into_store(
+            &app_handle,
+            &session,
+            &mut source,
+            stop.unwrap_or_default(),
+            ring,
+        );
+    });
+    Ok(id)
+}
+
+/// Event carrying newly dissected packets to the UI.
+pub const PACKETS_EVENT: &str = "packets";
+/// Event sent when a capture stops, carrying its [`SessionInfo`] with the error if it failed.
+pub const STATUS_EVENT: &str = "capture-status";
+/// Packets are coalesced and pushed to the UI at most this often.
+const EMIT_INTERVAL: Duration = Duration::from_millis(100);
+/// Most packets a single event carries. When more arrive in one interval only the newest are
+/// sent; the rest are still in the store.
+const MAX_EVENT_PACKETS: usize = 2_000;
+
+#[derive(Serialize, Clone)]
+pub struct PacketsEvent {
+    pub session_id: SessionId,
+    /// Only what the table shows; `get_packet` has the rest.
+    pub packets: Vec<PacketRow>,
+    /// Packets stored since the previous event that were left out to keep the event small.
+    pub skipped: usize,
+    pub usage: StoreUsage,
+    /// The capture's counters as of this event, so the UI doesn't have to poll them.
+    pub stats: CaptureStats,
+}
+
+/// Packets waiting for the next event.
+#[derive(Default)]
+struct PendingEvent {
+    packets: VecDeque<PacketRow>,
+    skipped: usize,
+}
+
+impl PendingEvent {
+    fn push(&mut self, batch: &[FormatedPacket]) {
+        let keep = batch.len().min(MAX_EVENT_PACKETS);
+        self.skipped += batch.len() - keep;
+        self.packets
+            .extend(batch[batch.len() - keep..].iter().map(PacketRow::from));
+        let excess = self.packets.len().saturating_sub(MAX_EVENT_PACKETS);
+        self.packets.drain(..excess);
+        self.skipped += excess;
+    }
+}
+
+/// Runs `source` through the dissection loop into the session's store until it's exhausted,
+/// `stop_watch` is called or one of the `stop` conditions is met. New packets are pushed to the
+/// UI as `PACKETS_EVENT`s, and a `STATUS_EVENT` is sent once it's over. With a `ring`, every
+/// stored packet is also written to disk.
+pub(crate) fn capture_into_store(
+    app_handle: &tauri::AppHandle,
+    session: &Session,
+    source: &mut dyn PacketSource,
+    stop: StopConditions,
+    ring: Option<RingWriter>,
+) {
+    let ring = Mutex::new(ring);
+    let capturing = AtomicBool::new(true);
+    let pending = Mutex::new(PendingEvent::default());
+    let tracker = Mutex::new(StopTracker::new(stop));
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut stats = CaptureStats::default();
+            loop {
+                // checked before emitting, so whatever the last batch added still goes out
+                let finished = !capturing.load(Ordering::SeqCst);
+                if let Some(reason) = tracker.lock().unwrap().expired() {
+                    log::info!("session {}: stopping, {:?}", session.id, reason);
+                    session.stop(reason);
                 }
+                with_trigger(session, |buffer| buffer.finish_before(now()));
+                with_ring(session, &ring, RingWriter::rotate_if_due);
+                emit_packets(app_handle, session, &pending, &mut stats);
+                if finished {
+                    break;
+                }
+                thread::sleep(EMIT_INTERVAL);
             }
-            sleep(Duration::from_millis(100));
+        });
+
+        let result = run_capture(
+            source,
+            session.running(),
+            session.counters(),
+            |mut batch| {
+                if let Some(reason) = tracker.lock().unwrap().admit(&mut batch) {
+                    log::info!("session {}: stopping, {:?}", session.id, reason);
+                    session.stop(reason);
+                }
+                with_ring(session, &ring, |writer| {
+                    batch
+                        .iter()
+                        .filter(|packet| !packet.frame.data.is_empty())
+                        .try_for_each(|packet| writer.write(&packet.frame))
+                        .and_then(|()| writer.flush())
+                });
+                with_trigger(session, |buffer| buffer.push(&batch));
+                session.store().stamp(&mut batch);
+                pending.lock().unwrap().push(&batch);
+                session.store().extend(batch);
+            },
+        );
+        capturing.store(false, Ordering::SeqCst);
+        if let Err(e) = result {
+            session.fail(AppError::capture(&session.interface, e).to_string());
         }
-        println!("loop stopped");
     });
+    with_trigger(session, TriggerBuffer::finish);
+    session.mark_stopped();
+    log::info!("session {}: loop stopped", session.id);
+    emit_status(app_handle, session.info());
 }
 
-#[tauri::command]
-pub fn stop_watch(app_handle: tauri::AppHandle) {
-    log::info!("stoped watch");
-    if let Some(running) = app_handle.try_state::<AtomicBool>() {
-        running.store(false, Ordering::SeqCst);
+/// Runs `f` on the session's pre-trigger buffer, if it was started with one. A failed write
+/// ends the capture, as evidence that can't be saved is worse than no capture.
+fn with_trigger(session: &Session, f: impl FnOnce(&mut TriggerBuffer) -> io::Result<()>) {
+    let result = match session.trigger().as_mut() {
+        Some(buffer) => f(buffer),
+        None => return,
+    };
+    if let Err(e) = result {
+        log::error!("session {}: failed to write evidence: {}", session.id, e);
+        session.fail(format!("Failed to write evidence file: {}", e));
+    }
+}
+
+/// Runs `f` on the capture's ring writer, if it has one. A failed write ends the capture, and
+/// nothing more is written.
+fn with_ring(
+    session: &Session,
+    ring: &Mutex<Option<RingWriter>>,
+    f: impl FnOnce(&mut RingWriter) -> io::Result<()>,
+) {
+    let mut ring = ring.lock().unwrap();
+    let Some(writer) = ring.as_mut() else {
+        return;
+    };
+    if let Err(e) = f(writer) {
+        log::error!(
+            "session {}: failed to write capture file: {}",
+            session.id,
+            e
+        );
+        session.fail(format!("Failed to write capture file: {}", e));
+        *ring = None;
+    }
+}
+
+fn emit_status(app_handle: &tauri::AppHandle, info: SessionInfo) {
+    if let Err(e) = app_handle.emit(STATUS_EVENT, info) {
+        log::error!("failed to emit capture status: {}", e);
+    }
+}
+
+/// Sends what's pending, or just the counters when only they changed since the `last_stats`
+/// sent, e.g. when the kernel drops frames or the rates fall off on a quiet link.
+fn emit_packets(
+    app_handle: &tauri::AppHandle,
+    session: &Session,
+    pending: &Mutex<PendingEvent>,
+    last_stats: &mut CaptureStats,
+) {
+    let PendingEvent { packets, skipped } = std::mem::take(&mut *pending.lock().unwrap());
+    let stats = session.counters().snapshot();
+    if packets.is_empty() && skipped == 0 && stats == *last_stats {
+        return;
+    }
+    *last_stats = stats.clone();
+    let event = PacketsEvent {
+        session_id: session.id,
+        packets: packets.into(),
+        skipped,
+        usage: session.store().usage(),
+        stats,
+    };
+    if let Err(e) = app_handle.emit(PACKETS_EVENT, event) {
+        log::error!("failed to emit packets: {}", e);
     }
 }
 
 #[tauri::command]
+pub fn stop_watch(app_handle: tauri::AppHandle, session_id: SessionId) -> AppResult<()> {
+    log::info!("stoped watch of session {}", session_id);
+    session(&app_handle, session_id)?.stop(StopReason::User);
+    Ok(())
+}
+
+/// Page size when the caller doesn't ask for one.
+const DEFAULT_PAGE_LIMIT: usize = 1_000;
+/// Largest page handed out in one call, so a single request can't stall the UI.
+const MAX_PAGE_LIMIT: usize = 10_000;
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn get_packets(
     app_handle: tauri::AppHandle,
+    session_id: SessionId,
     protocol: String,
     ip: String,
-) -> VecDeque<FormatedPacket> {
-    log::info!("filtered protocol: {}, ip: {}", protocol, ip);
-    if let Some(packets) = app_handle.try_state::<Mutex<VecDeque<FormatedPacket>>>() {
-        let locked_packets = match packets.lock() {
-            Ok(guard) => guard,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-        let packets = locked_packets
-            .iter() // Use iter() instead of cloning
-            .filter(|packet| {
-                (protocol == "all" || protocol == packet.protocol)
-                    && (ip.is_empty()
-                        || packet.src_ip.to_string().starts_with(&ip)
-                        || packet.dst_ip.to_string().starts_with(&ip))
-            })
-            .cloned()
-            .collect::<VecDeque<FormatedPacket>>();
-        packets
-    } else {
-        VecDeque::new()
-    }
+    vlan: Option<u16>,
+    after_number: Option<u32>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> AppResult<PacketPage> {
+    log::info!(
+        "filtered protocol: {}, ip: {}, vlan: {:?}",
+        protocol,
+        ip,
+        vlan
+    );
+    let session = session(&app_handle, session_id)?;
+    let filter = DisplayFilter::new(protocol, ip, vlan);
+    let page = session.store().page(
+        |packet| filter.matches(packet),
+        after_number,
+        offset.unwrap_or(0),
+        limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT),
+    );
+    Ok(page)
+}
+
+/// The whole of one stored packet, for the row selected in the table.
+#[tauri::command]
+pub fn get_packet(
+    app_handle: tauri::AppHandle,
+    session_id: SessionId,
+    number: u32,
+) -> AppResult<FormatedPacket> {
+    session(&app_handle, session_id)?
+        .store()
+        .get(number)
+        .cloned()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Packet {} is no longer held by session {}",
+                number,
+                session_id
+            )
+            .into()
+        })
+}
+
+/// Dissects every stored frame again, e.g. after a dissector was fixed or extended.
+#[tauri::command]
+pub fn reparse_packets(app_handle: tauri::AppHandle, session_id: SessionId) -> AppResult<()> {
+    let mut reassembler = Reassembler::default();
+    session(&app_handle, session_id)?.store().update(|packet| {
+        // packets imported from older exports have no frame to dissect
+        if packet.frame.data.is_empty() {
+            return None;
+        }
+        let mut packet = packet.redissect();
+        reassembler.add(&mut packet);
+        Some(packet)
+    });
+    Ok(())
+}
+
+/// How much the packet store holds and how much it had to evict, so the UI can warn before
+/// packets are lost.
+#[tauri::command]
+pub fn get_store_usage(
+    app_handle: tauri::AppHandle,
+    session_id: SessionId,
+) -> AppResult<StoreUsage> {
+    Ok(session(&app_handle, session_id)?.store().usage())
+}
+
+/// Changes the session's limits; sessions started afterwards get them too, minus the spill file.
+#[tauri::command]
+pub fn set_store_limits(
+    app_handle: tauri::AppHandle,
+    session_id: SessionId,
+    limits: StoreLimits,
+) -> AppResult<()> {
+    log::info!("store limits of session {}: {:?}", session_id, limits);
+    let session = session(&app_handle, session_id)?;
+    app_handle
+        .state::<SessionManager>()
+        .set_limits(&session, limits)
+        .map_err(|e| anyhow::anyhow!("Cant open spill file: {}", e))?;
+    Ok(())
 }
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use tauri::Manager;
+
+use crate::{
+    errors::AppResult,
+    logic::{
+        session::{Session, SessionId, SessionInfo, SessionManager},
+        stats::CaptureStats,
+    },
+};
+
+/// Looks up the session a command was called for.
+pub(crate) fn session(app_handle: &tauri::AppHandle, id: SessionId) -> AppResult<Arc<Session>> {
+    app_handle
+        .state::<SessionManager>()
+        .get(id)
+        .ok_or_else(|| anyhow::anyhow!("Session {} not found", id).into())
+}
+
+#[tauri::command]
+pub fn get_sessions(app_handle: tauri::AppHandle) -> Vec<SessionInfo> {
+    app_handle.state::<SessionManager>().list()
+}
+
+/// Stops a session if it's still capturing and drops its packets.
+#[tauri::command]
+pub fn close_session(app_handle: tauri::AppHandle, session_id: SessionId) -> AppResult<()> {
+    log::info!("close session {}", session_id);
+    app_handle
+        .state::<SessionManager>()
+        .remove(session_id)
+        .ok_or_else(|| anyhow::anyhow!("Session {} not found", session_id))?;
+    Ok(())
+}
+
+/// Counts of what the session's capture received, dissected and skipped, to judge whether
+/// the packet list can be trusted to be complete.
+#[tauri::command]
+pub fn get_capture_stats(
+    app_handle: tauri::AppHandle,
+    session_id: SessionId,
+) -> AppResult<CaptureStats> {
+    Ok(session(&app_handle, session_id)?.counters().snapshot())
+}
@@ -0,0 +1,42 @@
+use crate::{
+    api::sessions::session,
+    errors::AppResult,
+    logic::{
+        session::{now, SessionId},
+        trigger::TriggerInfo,
+    },
+};
+
+/// Saves the session's pre-trigger window, and what follows it, to a new evidence file.
+#[tauri::command]
+pub fn fire_trigger(
+    app_handle: tauri::AppHandle,
+    session_id: SessionId,
+    label: String,
+) -> AppResult<TriggerInfo> {
+    log::info!("trigger on session {}: {}", session_id, label);
+    let session = session(&app_handle, session_id)?;
+    let mut trigger = session.trigger();
+    let buffer = trigger.as_mut().ok_or_else(|| {
+        anyhow::anyhow!("Session {} wasn't started in pre-trigger mode", session_id)
+    })?;
+    let info = buffer
+        .fire(label, now())
+        .map_err(|e| anyhow::anyhow!("Cant write evidence file: {}", e))?;
+    Ok(info)
+}
+
+/// Every trigger fired on the session, with where its evidence was written.
+#[tauri::command]
+pub fn get_triggers(
+    app_handle: tauri::AppHandle,
+    session_id: SessionId,
+) -> AppResult<Vec<TriggerInfo>> {
+    let session = session(&app_handle, session_id)?;
+    let triggers = session
+        .trigger()
+        .as_ref()
+        .map(|buffer| buffer.triggers())
+        .unwrap_or_default();
+    Ok(triggers)
+}
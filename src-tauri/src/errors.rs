@@ -1,11 +1,36 @@
+use std::io;
+
 pub type AppResult<T> = std::result::Result<T, AppError>;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AppError {
+    #[error("Permission denied: capturing and sending need root or the CAP_NET_RAW capability")]
+    PermissionDenied,
+    #[error("Interface {0} not found")]
+    InterfaceNotFound(String),
+    #[error("Interface {0} is down")]
+    InterfaceDown(String),
+    #[error("{0} uses a link type that can't be captured")]
+    UnsupportedLinkType(String),
     #[error("Any error: {0:?}")]
     Anyhow(#[from] anyhow::Error),
 }
 
+impl AppError {
+    /// Classifies an error from opening or reading a capture on `interface`.
+    pub fn capture(interface: &str, e: io::Error) -> Self {
+        match e.raw_os_error() {
+            Some(libc::EPERM | libc::EACCES) => AppError::PermissionDenied,
+            Some(libc::ENETDOWN) => AppError::InterfaceDown(interface.to_string()),
+            Some(libc::ENODEV | libc::ENXIO) => AppError::InterfaceNotFound(interface.to_string()),
+            _ if e.kind() == io::ErrorKind::Unsupported => {
+                AppError::UnsupportedLinkType(interface.to_string())
+            }
+            _ => anyhow::anyhow!("Capture on {} failed: {}", interface, e).into(),
+        }
+    }
+}
+
 impl serde::Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
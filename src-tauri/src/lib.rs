@@ -1,24 +1,27 @@
-use std::{
-    collections::VecDeque,
-    sync::{atomic::AtomicBool, Mutex},
-};
-
-use logic::show_packets::FormatedPacket;
+use logic::{interfaces::InterfaceMonitor, session::SessionManager};
 
 mod api;
 mod errors;
 mod logic;
 
 use api::ai::dos_detect;
-use api::import_export::{export_packets, import_packets};
-use api::packets::{get_interfaces, get_packets, start_watch, stop_watch};
+use api::craft::{build_packet, resend_packet, send_packet};
+use api::discovery::discover_hosts;
+use api::dissectors::{get_dissectors, get_port_mappings, map_port};
+use api::import_export::{export_packets, import_packets, replay_packets};
+use api::packets::{
+    get_interfaces, get_packet, get_packets, get_store_usage, reparse_packets, set_store_limits,
+    start_watch, stop_watch,
+};
+use api::sessions::{close_session, get_capture_stats, get_sessions};
+use api::triggers::{fire_trigger, get_triggers};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
-        .manage(Mutex::new(VecDeque::<FormatedPacket>::new()))
-        .manage(AtomicBool::new(true))
+        .manage(SessionManager::default())
+        .manage(InterfaceMonitor::default())
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             //packets
@@ -26,9 +29,31 @@ pub fn run() {
             stop_watch,
             get_interfaces,
             get_packets,
+            get_packet,
+            reparse_packets,
+            get_store_usage,
+            set_store_limits,
+            //sessions
+            get_sessions,
+            close_session,
+            get_capture_stats,
+            //triggers
+            fire_trigger,
+            get_triggers,
+            //craft
+            build_packet,
+            send_packet,
+            resend_packet,
+            //discovery
+            discover_hosts,
+            //dissectors
+            get_dissectors,
+            get_port_mappings,
+            map_port,
             //import_export
             import_packets,
             export_packets,
+            replay_packets,
             //ai
             dos_detect
         ])
@@ -0,0 +1,1153 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use anyhow::{anyhow, bail};
+use pnet::datalink::MacAddr;
+
+/// One classic BPF instruction, laid out like the kernel's `struct sock_filter`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BpfInsn {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// A compiled classic BPF program, ready to be attached to a packet socket.
+pub type BpfProgram = Vec<BpfInsn>;
+
+const BPF_LD: u16 = 0x00;
+const BPF_LDX: u16 = 0x01;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+
+const BPF_W: u16 = 0x00;
+const BPF_H: u16 = 0x08;
+const BPF_B: u16 = 0x10;
+
+const BPF_ABS: u16 = 0x20;
+const BPF_IND: u16 = 0x40;
+const BPF_LEN: u16 = 0x80;
+const BPF_MSH: u16 = 0xa0;
+
+const BPF_AND: u16 = 0x50;
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_JSET: u16 = 0x40;
+const BPF_K: u16 = 0x00;
+
+const BPF_MAXINSNS: usize = 4096;
+
+/// Offsets into an Ethernet frame.
+const ETHERTYPE: u32 = 12;
+const NET: u32 = 14;
+/// Ancillary load of the frame's EtherType, for sockets that don't see the link-layer header.
+const SKF_AD_PROTOCOL: u32 = 0xffff_f000;
+/// Ancillary loads of the VLAN tag the NIC or kernel took off the frame, and whether there was one.
+const SKF_AD_VLAN_TAG: u32 = 0xffff_f02c;
+const SKF_AD_VLAN_TAG_PRESENT: u32 = 0xffff_f030;
+
+const ETHERTYPE_IPV4: u32 = 0x0800;
+const ETHERTYPE_ARP: u32 = 0x0806;
+const ETHERTYPE_RARP: u32 = 0x8035;
+const ETHERTYPE_IPV6: u32 = 0x86dd;
+const ETHERTYPE_VLAN: u32 = 0x8100;
+const ETHERTYPE_QINQ: u32 = 0x88a8;
+/// The VLAN ID bits of a tag's control information.
+const VLAN_ID_MASK: u32 = 0x0fff;
+
+const PROTO_ICMP: u32 = 1;
+const PROTO_TCP: u32 = 6;
+const PROTO_UDP: u32 = 17;
+const PROTO_ICMPV6: u32 = 58;
+const PROTO_SCTP: u32 = 132;
+
+/// What the filter sees at the start of each frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Framing {
+    Ethernet,
+    /// The network-layer header, as on cooked sockets. The EtherType comes from the kernel.
+    NetworkLayer,
+}
+
+/// Compiles a tcpdump-style capture filter, e.g. `tcp port 443 and not host 10.0.0.1`.
+/// Matching frames are accepted up to `snaplen` bytes.
+pub fn compile(expr: &str, snaplen: u32, framing: Framing) -> anyhow::Result<BpfProgram> {
+    let mut parser = Parser {
+        tokens: tokenize(expr),
+        pos: 0,
+        last: None,
+        framing,
+    };
+    let root = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        bail!("Unexpected '{}' in capture filter", token);
+    }
+
+    if framing == Framing::NetworkLayer && reads_link_addresses(&root) {
+        bail!("Ethernet address filters only work when capturing on an Ethernet interface");
+    }
+
+    let mut codegen = Codegen {
+        framing,
+        code: Vec::new(),
+        labels: Vec::new(),
+    };
+    let accept = codegen.label();
+    let reject = codegen.label();
+    codegen.node(&root, accept, reject);
+    codegen.place(accept);
+    codegen.emit(BPF_RET | BPF_K, snaplen);
+    codegen.place(reject);
+    codegen.emit(BPF_RET | BPF_K, 0);
+
+    let program = codegen.assemble()?;
+    if program.len() > BPF_MAXINSNS {
+        bail!(
+            "Capture filter is too complex ({} instructions)",
+            program.len()
+        );
+    }
+    Ok(program)
+}
+
+/// Attaches `program` to a socket, so the kernel drops non-matching frames before they're
+/// copied into userspace.
+#[cfg(target_os = "linux")]
+pub fn attach(fd: std::os::fd::RawFd, program: &BpfProgram) -> std::io::Result<()> {
+    let fprog = libc::sock_fprog {
+        len: program.len() as u16,
+        filter: program.as_ptr() as *mut libc::sock_filter,
+    };
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ATTACH_FILTER,
+            &fprog as *const libc::sock_fprog as *const libc::c_void,
+            std::mem::size_of::<libc::sock_fprog>() as libc::socklen_t,
+        )
+    };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Load {
+    Abs(u16, u32),
+    /// Relative to the IPv4 header end, whose length is read from the IHL field.
+    IpPayload(u16, u32),
+    Len,
+    /// A word the kernel knows about the frame rather than one read from it.
+    Ancillary(u32),
+}
+
+#[derive(Clone, Copy)]
+enum Jump {
+    Eq,
+    Gt,
+    Ge,
+    Set,
+}
+
+enum Node {
+    Test {
+        load: Load,
+        mask: Option<u32>,
+        jump: Jump,
+        k: u32,
+    },
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+fn test(load: Load, jump: Jump, k: u32) -> Node {
+    Node::Test {
+        load,
+        mask: None,
+        jump,
+        k,
+    }
+}
+
+fn eq(load: Load, k: u32) -> Node {
+    test(load, Jump::Eq, k)
+}
+
+fn masked_eq(load: Load, mask: u32, k: u32) -> Node {
+    Node::Test {
+        load,
+        mask: (mask != u32::MAX).then_some(mask),
+        jump: Jump::Eq,
+        k: k & mask,
+    }
+}
+
+fn and(a: Node, b: Node) -> Node {
+    Node::And(Box::new(a), Box::new(b))
+}
+
+fn or(a: Node, b: Node) -> Node {
+    Node::Or(Box::new(a), Box::new(b))
+}
+
+fn not(a: Node) -> Node {
+    Node::Not(Box::new(a))
+}
+
+fn any(nodes: impl IntoIterator<Item = Node>) -> Option<Node> {
+    nodes.into_iter().reduce(or)
+}
+
+fn all(nodes: impl IntoIterator<Item = Node>) -> Option<Node> {
+    nodes.into_iter().reduce(and)
+}
+
+fn ether_type(ethertype: u32) -> Node {
+    eq(Load::Abs(BPF_H, ETHERTYPE), ethertype)
+}
+
+fn ipv4_proto(proto: u32) -> Node {
+    and(
+        ether_type(ETHERTYPE_IPV4),
+        eq(Load::Abs(BPF_B, NET + 9), proto),
+    )
+}
+
+fn ipv6_proto(proto: u32) -> Node {
+    and(
+        ether_type(ETHERTYPE_IPV6),
+        eq(Load::Abs(BPF_B, NET + 6), proto),
+    )
+}
+
+/// Frames carrying a VLAN tag, with ID `id` if given. Tags are usually taken off by the NIC
+/// and handed over beside the frame; otherwise an Ethernet frame still has its outer tag.
+/// Unlike tcpdump, primitives after `vlan` read the frame as if it were untagged, which is
+/// how the socket sees it in the usual case.
+fn vlan(id: Option<u32>, framing: Framing) -> anyhow::Result<Node> {
+    if id.is_some_and(|id| id > VLAN_ID_MASK) {
+        bail!("Invalid VLAN ID '{}'", id.unwrap_or_default());
+    }
+    let stripped = test(Load::Ancillary(SKF_AD_VLAN_TAG_PRESENT), Jump::Gt, 0);
+    let stripped = match id {
+        Some(id) => and(
+            stripped,
+            masked_eq(Load::Ancillary(SKF_AD_VLAN_TAG), VLAN_ID_MASK, id),
+        ),
+        None => stripped,
+    };
+    if framing == Framing::NetworkLayer {
+        return Ok(stripped);
+    }
+    let tagged = or(ether_type(ETHERTYPE_VLAN), ether_type(ETHERTYPE_QINQ));
+    let tagged = match id {
+        Some(id) => and(tagged, masked_eq(Load::Abs(BPF_H, NET), VLAN_ID_MASK, id)),
+        None => tagged,
+    };
+    Ok(or(stripped, tagged))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Dir {
+    Src,
+    Dst,
+    SrcOrDst,
+    SrcAndDst,
+}
+
+impl Dir {
+    /// Builds the match for one or both address fields; `side` gets `true` for the source.
+    fn build(self, mut side: impl FnMut(bool) -> Node) -> Node {
+        match self {
+            Dir::Src => side(true),
+            Dir::Dst => side(false),
+            Dir::SrcOrDst => or(side(true), side(false)),
+            Dir::SrcAndDst => and(side(true), side(false)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Host,
+    Net,
+    Port,
+    PortRange,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Proto {
+    Ether,
+    Ip,
+    Ip6,
+    Arp,
+    Rarp,
+    Tcp,
+    Udp,
+    Sctp,
+    Icmp,
+    Icmp6,
+}
+
+impl Proto {
+    fn parse(token: &str) -> Option<Self> {
+        Some(match token {
+            "ether" => Proto::Ether,
+            "ip" => Proto::Ip,
+            "ip6" => Proto::Ip6,
+            "arp" => Proto::Arp,
+            "rarp" => Proto::Rarp,
+            "tcp" => Proto::Tcp,
+            "udp" => Proto::Udp,
+            "sctp" => Proto::Sctp,
+            "icmp" => Proto::Icmp,
+            "icmp6" => Proto::Icmp6,
+            _ => return None,
+        })
+    }
+
+    fn matches(self) -> anyhow::Result<Node> {
+        Ok(match self {
+            Proto::Ip => ether_type(ETHERTYPE_IPV4),
+            Proto::Ip6 => ether_type(ETHERTYPE_IPV6),
+            Proto::Arp => ether_type(ETHERTYPE_ARP),
+            Proto::Rarp => ether_type(ETHERTYPE_RARP),
+            Proto::Tcp => or(ipv4_proto(PROTO_TCP), ipv6_proto(PROTO_TCP)),
+            Proto::Udp => or(ipv4_proto(PROTO_UDP), ipv6_proto(PROTO_UDP)),
+            Proto::Sctp => or(ipv4_proto(PROTO_SCTP), ipv6_proto(PROTO_SCTP)),
+            Proto::Icmp => ipv4_proto(PROTO_ICMP),
+            Proto::Icmp6 => ipv6_proto(PROTO_ICMPV6),
+            Proto::Ether => bail!("'ether' needs a 'host' or 'proto' qualifier"),
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Qualifiers {
+    proto: Option<Proto>,
+    dir: Dir,
+    kind: Option<Kind>,
+}
+
+fn tokenize(expr: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        let single = match c {
+            '(' | ')' => true,
+            '!' if chars.peek() != Some(&'=') => true,
+            '&' | '|' if chars.peek() == Some(&c) => {
+                chars.next();
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                tokens.push(if c == '&' { "and" } else { "or" }.to_string());
+                continue;
+            }
+            _ => false,
+        };
+        if single || c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            if single {
+                tokens.push(if c == '!' {
+                    "not".to_string()
+                } else {
+                    c.to_string()
+                });
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+    /// tcpdump lets `host a or b` reuse the qualifiers of the previous primitive.
+    last: Option<Qualifiers>,
+    framing: Framing,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&str> {
+        self.tokens.get(self.pos + offset).map(String::as_str)
+    }
+
+    fn next(&mut self) -> anyhow::Result<String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("Capture filter ends unexpectedly"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> anyhow::Result<Node> {
+        let mut node = self.parse_and()?;
+        while self.eat("or") {
+            node = or(node, self.parse_and()?);
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> anyhow::Result<Node> {
+        let mut node = self.parse_not()?;
+        while self.eat("and") {
+            node = and(node, self.parse_not()?);
+        }
+        Ok(node)
+    }
+
+    fn parse_not(&mut self) -> anyhow::Result<Node> {
+        if self.eat("not") {
+            return Ok(not(self.parse_not()?));
+        }
+        if self.eat("(") {
+            let node = self.parse_or()?;
+            if !self.eat(")") {
+                bail!("Missing ')' in capture filter");
+            }
+            return Ok(node);
+        }
+        self.parse_primitive()
+    }
+
+    fn parse_primitive(&mut self) -> anyhow::Result<Node> {
+        let token = self.next()?;
+        match token.as_str() {
+            "less" => return Ok(not(test(Load::Len, Jump::Gt, self.number()?))),
+            "greater" => return Ok(test(Load::Len, Jump::Ge, self.number()?)),
+            "vlan" => {
+                let id = match self.peek() {
+                    Some(token) if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                        Some(self.number()?)
+                    }
+                    _ => None,
+                };
+                return vlan(id, self.framing);
+            }
+            _ => {}
+        }
+
+        let mut qualifiers = Qualifiers {
+            proto: None,
+            dir: Dir::SrcOrDst,
+            kind: None,
+        };
+        let mut token = Some(token);
+
+        if let Some(proto) = token.as_deref().and_then(Proto::parse) {
+            if self.eat("proto") {
+                let number = self.number()?;
+                return match proto {
+                    Proto::Ether => Ok(eq(Load::Abs(BPF_H, ETHERTYPE), number)),
+                    Proto::Ip => Ok(ipv4_proto(number)),
+                    Proto::Ip6 => Ok(ipv6_proto(number)),
+                    _ => bail!("'{:?} proto' is not supported", proto),
+                };
+            }
+            let qualifies = matches!(
+                self.peek(),
+                Some("src" | "dst" | "host" | "net" | "port" | "portrange")
+            );
+            if !qualifies {
+                return proto.matches();
+            }
+            qualifiers.proto = Some(proto);
+            token = None;
+        }
+
+        let token = match token {
+            Some(token) => token,
+            None => self.next()?,
+        };
+        let mut token = Some(token);
+        if let Some(first) = token.as_deref().filter(|t| *t == "src" || *t == "dst") {
+            let first_src = first == "src";
+            qualifiers.dir = if first_src { Dir::Src } else { Dir::Dst };
+            let other = if first_src { "dst" } else { "src" };
+            if matches!(self.peek(), Some("or" | "and")) && self.peek_at(1) == Some(other) {
+                qualifiers.dir = if self.next()? == "or" {
+                    Dir::SrcOrDst
+                } else {
+                    Dir::SrcAndDst
+                };
+                self.pos += 1;
+            }
+            token = None;
+        }
+
+        let token = match token {
+            Some(token) => token,
+            None => self.next()?,
+        };
+        let kind = match token.as_str() {
+            "host" => Some(Kind::Host),
+            "net" => Some(Kind::Net),
+            "port" => Some(Kind::Port),
+            "portrange" => Some(Kind::PortRange),
+            _ => None,
+        };
+        let id = match kind {
+            Some(_) => self.next()?,
+            None => token,
+        };
+        qualifiers.kind = kind;
+
+        let bare = qualifiers.proto.is_none() && qualifiers.dir == Dir::SrcOrDst && kind.is_none();
+        if bare {
+            if let Some(last) = self.last {
+                qualifiers = last;
+            }
+        }
+        self.last = Some(qualifiers);
+
+        let mask = if self.eat("mask") {
+            Some(self.next()?)
+        } else {
+            None
+        };
+        build(qualifiers, &id, mask.as_deref())
+    }
+
+    fn number(&mut self) -> anyhow::Result<u32> {
+        let token = self.next()?;
+        parse_number(&token)
+    }
+}
+
+fn parse_number(token: &str) -> anyhow::Result<u32> {
+    let parsed = match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => token.parse(),
+    };
+    parsed.map_err(|_| anyhow!("Expected a number, found '{}'", token))
+}
+
+fn build(q: Qualifiers, id: &str, mask: Option<&str>) -> anyhow::Result<Node> {
+    let kind = q.kind.unwrap_or(if id.contains('/') || mask.is_some() {
+        Kind::Net
+    } else {
+        Kind::Host
+    });
+
+    match kind {
+        Kind::Host if q.proto == Some(Proto::Ether) => {
+            let mac: MacAddr = id
+                .parse()
+                .map_err(|_| anyhow!("Invalid MAC address '{}'", id))?;
+            let b = mac.octets();
+            let high = u16::from_be_bytes([b[0], b[1]]) as u32;
+            let low = u32::from_be_bytes([b[2], b[3], b[4], b[5]]);
+            Ok(q.dir.build(|src| {
+                let offset = if src { 6 } else { 0 };
+                and(
+                    eq(Load::Abs(BPF_W, offset + 2), low),
+                    eq(Load::Abs(BPF_H, offset), high),
+                )
+            }))
+        }
+        Kind::Host | Kind::Net => {
+            let (addr, prefix) = parse_net(id, mask, kind == Kind::Host)?;
+            match addr {
+                IpAddr::V4(addr) => ipv4_net(q, addr, prefix),
+                IpAddr::V6(addr) => ipv6_net(q, addr, prefix),
+            }
+        }
+        Kind::Port | Kind::PortRange => {
+            let (low, high) = match id.split_once('-') {
+                Some((low, high)) if kind == Kind::PortRange => {
+                    (parse_number(low)?, parse_number(high)?)
+                }
+                _ => {
+                    let port = parse_number(id)?;
+                    (port, port)
+                }
+            };
+            if low > u16::MAX as u32 || high > u16::MAX as u32 || low > high {
+                bail!("Invalid port '{}'", id);
+            }
+            let protos: &[u32] = match q.proto {
+                None => &[PROTO_TCP, PROTO_UDP, PROTO_SCTP],
+                Some(Proto::Tcp) => &[PROTO_TCP],
+                Some(Proto::Udp) => &[PROTO_UDP],
+                Some(Proto::Sctp) => &[PROTO_SCTP],
+                Some(proto) => bail!("'{:?}' has no ports", proto),
+            };
+            let in_range = |load: Load| {
+                if low == high {
+                    eq(load, low)
+                } else {
+                    and(test(load, Jump::Ge, low), not(test(load, Jump::Gt, high)))
+                }
+            };
+
+            let proto_at = |offset: u32| {
+                any(protos.iter().map(|p| eq(Load::Abs(BPF_B, offset), *p)))
+                    .expect("protocol list is never empty")
+            };
+
+            let v4 = and(
+                and(ether_type(ETHERTYPE_IPV4), proto_at(NET + 9)),
+                and(
+                    // only the first fragment carries the transport header
+                    not(test(Load::Abs(BPF_H, NET + 6), Jump::Set, 0x1fff)),
+                    q.dir
+                        .build(|src| in_range(Load::IpPayload(BPF_H, if src { 0 } else { 2 }))),
+                ),
+            );
+            let v6 = and(
+                and(ether_type(ETHERTYPE_IPV6), proto_at(NET + 6)),
+                q.dir
+                    .build(|src| in_range(Load::Abs(BPF_H, NET + if src { 40 } else { 42 }))),
+            );
+            Ok(or(v4, v6))
+        }
+    }
+}
+
+fn parse_net(id: &str, mask: Option<&str>, host: bool) -> anyhow::Result<(IpAddr, u32)> {
+    let (addr, prefix) = match id.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(parse_number(prefix)?)),
+        None => (id, None),
+    };
+    let addr: IpAddr = addr.parse().map_err(|_| {
+        anyhow!(
+            "Invalid address '{}' (host names aren't supported in capture filters)",
+            id
+        )
+    })?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match (prefix, mask) {
+        (Some(prefix), None) => prefix,
+        (None, Some(mask)) => {
+            let mask: Ipv4Addr = mask
+                .parse()
+                .map_err(|_| anyhow!("Invalid netmask '{}'", mask))?;
+            let mask = u32::from(mask);
+            if mask.leading_ones() + mask.trailing_zeros() != 32 {
+                bail!("Netmask '{}' is not contiguous", Ipv4Addr::from(mask));
+            }
+            mask.leading_ones()
+        }
+        (None, None) => max,
+        (Some(_), Some(_)) => bail!("Use either a prefix length or a mask, not both"),
+    };
+    if prefix > max || (host && prefix != max) {
+        bail!("Invalid prefix length in '{}'", id);
+    }
+    Ok((addr, prefix))
+}
+
+fn prefix_mask(prefix: u32) -> u32 {
+    if prefix == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix.min(32))
+    }
+}
+
+fn ipv4_net(q: Qualifiers, addr: Ipv4Addr, prefix: u32) -> anyhow::Result<Node> {
+    let addr = u32::from(addr);
+    let mask = prefix_mask(prefix);
+    let matches = |src_offset: u32, dst_offset: u32| {
+        q.dir.build(|src| {
+            let offset = if src { src_offset } else { dst_offset };
+            masked_eq(Load::Abs(BPF_W, offset), mask, addr)
+        })
+    };
+
+    let ip = and(ether_type(ETHERTYPE_IPV4), matches(NET + 12, NET + 16));
+    let arp = |ethertype| and(ether_type(ethertype), matches(NET + 14, NET + 24));
+    Ok(match q.proto {
+        None => or(ip, or(arp(ETHERTYPE_ARP), arp(ETHERTYPE_RARP))),
+        Some(Proto::Ip) => ip,
+        Some(Proto::Arp) => arp(ETHERTYPE_ARP),
+        Some(Proto::Rarp) => arp(ETHERTYPE_RARP),
+        Some(proto) => bail!(
+            "'{:?} host' is not supported, use '{:?} and host'",
+            proto,
+            proto
+        ),
+    })
+}
+
+fn ipv6_net(q: Qualifiers, addr: Ipv6Addr, prefix: u32) -> anyhow::Result<Node> {
+    if !matches!(q.proto, None | Some(Proto::Ip6)) {
+        bail!("IPv6 addresses can only be used with 'ip6'");
+    }
+    let octets = addr.octets();
+    let words: Vec<u32> = octets
+        .chunks(4)
+        .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+        .collect();
+    let matches = q.dir.build(|src| {
+        let base = NET + if src { 8 } else { 24 };
+        let tests = (0..4).filter_map(|i| {
+            let bits = prefix.saturating_sub(i * 32).min(32);
+            (bits > 0).then(|| {
+                masked_eq(
+                    Load::Abs(BPF_W, base + i * 4),
+                    prefix_mask(bits),
+                    words[i as usize],
+                )
+            })
+        });
+        // `::/0` matches every IPv6 packet
+        all(tests).unwrap_or_else(|| ether_type(ETHERTYPE_IPV6))
+    });
+    Ok(and(ether_type(ETHERTYPE_IPV6), matches))
+}
+
+/// Whether any test loads from the Ethernet header other than its EtherType.
+fn reads_link_addresses(node: &Node) -> bool {
+    match node {
+        Node::Test {
+            load: Load::Abs(size, offset),
+            ..
+        } => *offset < NET && (*size, *offset) != (BPF_H, ETHERTYPE),
+        Node::Test { .. } => false,
+        Node::And(a, b) | Node::Or(a, b) => reads_link_addresses(a) || reads_link_addresses(b),
+        Node::Not(a) => reads_link_addresses(a),
+    }
+}
+
+enum Code {
+    Op(u16, u32),
+    Jump(u16, u32, usize, usize),
+    Always(usize),
+}
+
+struct Codegen {
+    framing: Framing,
+    code: Vec<Code>,
+    labels: Vec<usize>,
+}
+
+impl Codegen {
+    fn label(&mut self) -> usize {
+        self.labels.push(usize::MAX);
+        self.labels.len() - 1
+    }
+
+    fn place(&mut self, label: usize) {
+        self.labels[label] = self.code.len();
+    }
+
+    fn emit(&mut self, code: u16, k: u32) {
+        self.code.push(Code::Op(code, k));
+    }
+
+    fn node(&mut self, node: &Node, on_true: usize, on_false: usize) {
+        match node {
+            Node::Test {
+                load,
+                mask,
+                jump,
+                k,
+            } => {
+                let net = match self.framing {
+                    Framing::Ethernet => NET,
+                    Framing::NetworkLayer => 0,
+                };
+                match *load {
+                    Load::Abs(size, ETHERTYPE) if self.framing == Framing::NetworkLayer => {
+                        self.emit(BPF_LD | size | BPF_ABS, SKF_AD_PROTOCOL)
+                    }
+                    // link addresses were rejected up front, so this is in the network layer
+                    Load::Abs(size, offset) => {
+                        self.emit(BPF_LD | size | BPF_ABS, offset + net - NET)
+                    }
+                    Load::IpPayload(size, offset) => {
+                        self.emit(BPF_LDX | BPF_B | BPF_MSH, net);
+                        self.emit(BPF_LD | size | BPF_IND, net + offset);
+                    }
+                    Load::Len => self.emit(BPF_LD | BPF_W | BPF_LEN, 0),
+                    Load::Ancillary(k) => self.emit(BPF_LD | BPF_W | BPF_ABS, k),
+                }
+                if let Some(mask) = mask {
+                    self.emit(BPF_ALU | BPF_AND | BPF_K, *mask);
+                }
+                let op = match jump {
+                    Jump::Eq => BPF_JEQ,
+                    Jump::Gt => BPF_JGT,
+                    Jump::Ge => BPF_JGE,
+                    Jump::Set => BPF_JSET,
+                };
+                self.code
+                    .push(Code::Jump(BPF_JMP | op | BPF_K, *k, on_true, on_false));
+            }
+            Node::And(a, b) => {
+                let next = self.label();
+                self.node(a, next, on_false);
+                self.place(next);
+                self.node(b, on_true, on_false);
+            }
+            Node::Or(a, b) => {
+                let next = self.label();
+                self.node(a, on_true, next);
+                self.place(next);
+                self.node(b, on_true, on_false);
+            }
+            Node::Not(a) => self.node(a, on_false, on_true),
+        }
+    }
+
+    /// Resolves labels into relative offsets. Conditional jumps can only skip 255
+    /// instructions, so longer ones are routed through an unconditional jump placed
+    /// right after them.
+    fn assemble(mut self) -> anyhow::Result<BpfProgram> {
+        loop {
+            let far = self
+                .code
+                .iter()
+                .enumerate()
+                .find_map(|(i, code)| match code {
+                    Code::Jump(_, _, t, f) => {
+                        let too_far =
+                            |label: usize| self.labels[label] - (i + 1) > u8::MAX as usize;
+                        (too_far(*t) || too_far(*f)).then_some(i)
+                    }
+                    _ => None,
+                });
+            let Some(i) = far else { break };
+
+            let Code::Jump(code, k, t, f) = self.code[i] else {
+                unreachable!()
+            };
+            for label in self.labels.iter_mut() {
+                if *label > i {
+                    *label += 2;
+                }
+            }
+            let (t_hop, f_hop) = (self.label(), self.label());
+            self.labels[t_hop] = i + 1;
+            self.labels[f_hop] = i + 2;
+            self.code[i] = Code::Jump(code, k, t_hop, f_hop);
+            self.code.insert(i + 1, Code::Always(t));
+            self.code.insert(i + 2, Code::Always(f));
+        }
+
+        self.code
+            .iter()
+            .enumerate()
+            .map(|(i, code)| {
+                let offset = |label: usize| self.labels[label] - (i + 1);
+                Ok(match *code {
+                    Code::Op(code, k) => BpfInsn {
+                        code,
+                        jt: 0,
+                        jf: 0,
+                        k,
+                    },
+                    Code::Jump(code, k, t, f) => BpfInsn {
+                        code,
+                        jt: offset(t) as u8,
+                        jf: offset(f) as u8,
+                        k,
+                    },
+                    Code::Always(target) => BpfInsn {
+                        code: BPF_JMP | BPF_JA,
+                        jt: 0,
+                        jf: 0,
+                        k: offset(target) as u32,
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPLEN: u32 = 262_144;
+
+    /// What the kernel tells a filter besides the frame's bytes.
+    #[derive(Default)]
+    struct Meta {
+        protocol: u32,
+        vlan_tag: Option<u32>,
+    }
+
+    /// Runs `program` the way the kernel's classic BPF interpreter does, returning how many
+    /// bytes of `frame` it keeps. Loads past the end reject the frame.
+    fn run(program: &BpfProgram, frame: &[u8], meta: &Meta) -> u32 {
+        let load = |size: u16, at: u32| -> Option<u32> {
+            match at {
+                SKF_AD_PROTOCOL => return Some(meta.protocol),
+                SKF_AD_VLAN_TAG => return Some(meta.vlan_tag.unwrap_or_default()),
+                SKF_AD_VLAN_TAG_PRESENT => return Some(meta.vlan_tag.is_some() as u32),
+                _ => {}
+            }
+            let at = at as usize;
+            let len = match size {
+                BPF_W => 4,
+                BPF_H => 2,
+                _ => 1,
+            };
+            let bytes = frame.get(at..at + len)?;
+            Some(bytes.iter().fold(0, |acc, byte| acc << 8 | *byte as u32))
+        };
+        let (mut a, mut x, mut pc) = (0u32, 0u32, 0usize);
+        loop {
+            let insn = program[pc];
+            pc += 1;
+            let size = insn.code & 0x18;
+            match insn.code & 0x07 {
+                BPF_LD => {
+                    let loaded = match insn.code & 0xe0 {
+                        BPF_ABS => load(size, insn.k),
+                        BPF_IND => load(size, x.wrapping_add(insn.k)),
+                        BPF_LEN => Some(frame.len() as u32),
+                        mode => panic!("unexpected load mode {:#x}", mode),
+                    };
+                    let Some(loaded) = loaded else { return 0 };
+                    a = loaded;
+                }
+                BPF_LDX => {
+                    assert_eq!(insn.code, BPF_LDX | BPF_B | BPF_MSH);
+                    let Some(byte) = load(BPF_B, insn.k) else {
+                        return 0;
+                    };
+                    x = (byte & 0x0f) * 4;
+                }
+                BPF_ALU => {
+                    assert_eq!(insn.code, BPF_ALU | BPF_AND | BPF_K);
+                    a &= insn.k;
+                }
+                BPF_JMP => {
+                    let taken = match insn.code & 0xf0 {
+                        BPF_JA => {
+                            pc += insn.k as usize;
+                            continue;
+                        }
+                        BPF_JEQ => a == insn.k,
+                        BPF_JGT => a > insn.k,
+                        BPF_JGE => a >= insn.k,
+                        BPF_JSET => a & insn.k != 0,
+                        op => panic!("unexpected jump {:#x}", op),
+                    };
+                    pc += if taken { insn.jt } else { insn.jf } as usize;
+                }
+                BPF_RET => return insn.k,
+                class => panic!("unexpected class {:#x}", class),
+            }
+        }
+    }
+
+    /// An IPv4 packet from `src` to `dst` carrying `proto`, whose payload starts with the
+    /// given ports. `fragment` is the fragment offset in 8-byte units.
+    fn ipv4(proto: u8, src: [u8; 4], dst: [u8; 4], ports: (u16, u16), fragment: u16) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 40, 0, 0];
+        packet.extend(fragment.to_be_bytes());
+        packet.extend([64, proto, 0, 0]);
+        packet.extend(src);
+        packet.extend(dst);
+        packet.extend(ports.0.to_be_bytes());
+        packet.extend(ports.1.to_be_bytes());
+        packet.extend([0; 16]);
+        packet
+    }
+
+    fn ipv6(proto: u8, ports: (u16, u16)) -> Vec<u8> {
+        let mut packet = vec![0x60, 0, 0, 0, 0, 20, proto, 64];
+        packet.extend([0; 32]);
+        packet.extend(ports.0.to_be_bytes());
+        packet.extend(ports.1.to_be_bytes());
+        packet.extend([0; 16]);
+        packet
+    }
+
+    fn arp(sender: [u8; 4]) -> Vec<u8> {
+        let mut packet = vec![0, 1, 8, 0, 6, 4, 0, 1, 2, 0, 0, 0, 0, 1];
+        packet.extend(sender);
+        packet.extend([0; 6]);
+        packet.extend([10, 0, 0, 9]);
+        packet
+    }
+
+    fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![2, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 1];
+        frame.extend(ethertype.to_be_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    fn tagged(id: u16, ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut inner = id.to_be_bytes().to_vec();
+        inner.extend(ethertype.to_be_bytes());
+        inner.extend(payload);
+        ethernet(ETHERTYPE_VLAN as u16, &inner)
+    }
+
+    fn accepts(expr: &str, frame: &[u8], meta: &Meta) -> bool {
+        let program = compile(expr, SNAPLEN, Framing::Ethernet).unwrap();
+        run(&program, frame, meta) == SNAPLEN
+    }
+
+    const A: [u8; 4] = [10, 0, 0, 1];
+    const B: [u8; 4] = [10, 0, 0, 2];
+
+    #[test]
+    fn matches_ports() {
+        let none = Meta::default();
+        let cases = [
+            (ethernet(0x0800, &ipv4(6, A, B, (40000, 80), 0)), true),
+            (ethernet(0x0800, &ipv4(6, A, B, (80, 40000), 0)), true),
+            (ethernet(0x0800, &ipv4(6, A, B, (40000, 81), 0)), false),
+            (ethernet(0x0800, &ipv4(17, A, B, (40000, 80), 0)), false),
+            // a later fragment has no ports, whatever its payload looks like
+            (ethernet(0x0800, &ipv4(6, A, B, (40000, 80), 185)), false),
+            (ethernet(0x86dd, &ipv6(6, (40000, 80))), true),
+            (ethernet(0x86dd, &ipv6(17, (40000, 80))), false),
+            (ethernet(0x0806, &arp(A)), false),
+        ];
+        for (i, (frame, expected)) in cases.iter().enumerate() {
+            assert_eq!(
+                accepts("tcp port 80", frame, &none),
+                *expected,
+                "case {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn matches_hosts_and_protocols() {
+        let none = Meta::default();
+        let expr = "host 10.0.0.1 and not udp";
+        let cases = [
+            (ethernet(0x0800, &ipv4(6, A, B, (1, 2), 0)), true),
+            (ethernet(0x0800, &ipv4(6, B, A, (1, 2), 0)), true),
+            (ethernet(0x0800, &ipv4(17, A, B, (1, 2), 0)), false),
+            (
+                ethernet(0x0800, &ipv4(6, B, [10, 0, 0, 3], (1, 2), 0)),
+                false,
+            ),
+            (ethernet(0x0806, &arp(A)), true),
+            (ethernet(0x0806, &arp(B)), false),
+        ];
+        for (i, (frame, expected)) in cases.iter().enumerate() {
+            assert_eq!(accepts(expr, frame, &none), *expected, "case {}", i);
+        }
+    }
+
+    #[test]
+    fn matches_vlans() {
+        let packet = ipv4(6, A, B, (1, 2), 0);
+        let stripped = |tag| Meta {
+            protocol: ETHERTYPE_IPV4,
+            vlan_tag: Some(tag),
+        };
+        let untagged = ethernet(0x0800, &packet);
+        // the priority bits aren't part of the ID
+        assert!(accepts("vlan 10", &untagged, &stripped(0xa00a)));
+        assert!(!accepts("vlan 10", &untagged, &stripped(11)));
+        assert!(!accepts("vlan 10", &untagged, &Meta::default()));
+        assert!(accepts(
+            "vlan 10",
+            &tagged(10, 0x0800, &packet),
+            &Meta::default()
+        ));
+        assert!(!accepts(
+            "vlan 10",
+            &tagged(11, 0x0800, &packet),
+            &Meta::default()
+        ));
+        assert!(accepts("vlan", &untagged, &stripped(11)));
+        assert!(accepts("vlan and tcp", &untagged, &stripped(11)));
+        assert!(!accepts("vlan", &untagged, &Meta::default()));
+        assert!(compile("vlan 4096", SNAPLEN, Framing::Ethernet).is_err());
+    }
+
+    #[test]
+    fn matches_on_cooked_sockets() {
+        let program = compile("tcp port 80 and vlan 10", SNAPLEN, Framing::NetworkLayer).unwrap();
+        let packet = ipv4(6, A, B, (40000, 80), 0);
+        let meta = |vlan_tag| Meta {
+            protocol: ETHERTYPE_IPV4,
+            vlan_tag,
+        };
+        assert_eq!(run(&program, &packet, &meta(Some(10))), SNAPLEN);
+        assert_eq!(run(&program, &packet, &meta(None)), 0);
+        let udp = ipv4(17, A, B, (40000, 80), 0);
+        assert_eq!(run(&program, &udp, &meta(Some(10))), 0);
+        let err = compile(
+            "ether host 02:00:00:00:00:01",
+            SNAPLEN,
+            Framing::NetworkLayer,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Ethernet address filters"));
+    }
+
+    #[test]
+    fn routes_far_jumps_through_unconditional_ones() {
+        let hosts: Vec<String> = (1..=60).map(|i| format!("host 10.0.1.{}", i)).collect();
+        let expr = format!("({}) and tcp", hosts.join(" or "));
+        let program = compile(&expr, SNAPLEN, Framing::Ethernet).unwrap();
+        assert!(program.len() > 255 + 2);
+        assert!(program.iter().any(|insn| insn.code == BPF_JMP | BPF_JA));
+        let none = Meta::default();
+        for (host, expected) in [(1, true), (60, true), (61, false)] {
+            let frame = ethernet(0x0800, &ipv4(6, [10, 0, 1, host], B, (1, 2), 0));
+            assert_eq!(
+                run(&program, &frame, &none) == SNAPLEN,
+                expected,
+                "host {}",
+                host
+            );
+        }
+        let udp = ethernet(0x0800, &ipv4(17, [10, 0, 1, 1], B, (1, 2), 0));
+        assert_eq!(run(&program, &udp, &none), 0);
+    }
+
+    #[test]
+    fn rejects_bad_filters() {
+        for (expr, message) in [
+            ("tcp port", "ends unexpectedly"),
+            ("port 70000", "Invalid port"),
+            ("(tcp", "Missing ')'"),
+            ("tcp )", "Unexpected ')'"),
+            ("host example.com", "Invalid address"),
+            ("net 10.0.0.0/33", "Invalid prefix length"),
+            ("icmp port 1", "has no ports"),
+            ("ether", "needs a 'host' or 'proto'"),
+            ("less x", "Expected a number"),
+        ] {
+            let err = compile(expr, SNAPLEN, Framing::Ethernet).unwrap_err();
+            assert!(err.to_string().contains(message), "{}: {}", expr, err);
+        }
+    }
+}
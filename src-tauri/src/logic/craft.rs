@@ -0,0 +1,948 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use pnet::{
+    datalink::NetworkInterface,
+    packet::{
+        arp::{ArpHardwareTypes, ArpOperations, MutableArpPacket},
+        ethernet::{EtherType, EtherTypes, MutableEthernetPacket},
+        icmp::{self, MutableIcmpPacket},
+        icmpv6::{self, MutableIcmpv6Packet},
+        ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+        ipv4::{self, Ipv4Flags, MutableIpv4Packet},
+        ipv6::MutableIpv6Packet,
+        tcp::{self, MutableTcpPacket, TcpFlags},
+        udp::{self, MutableUdpPacket},
+        MutablePacket,
+    },
+    util::MacAddr,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    create_chanel,
+    frame::{hex_bytes, RawFrame},
+    link,
+    pcap::LINKTYPE_ETHERNET,
+    source::LiveSource,
+};
+use crate::errors::{AppError, AppResult};
+
+const ETHERNET_HEADER_LEN: usize = 14;
+/// Shortest Ethernet frame, not counting the FCS. Shorter frames are padded with zeros.
+const MIN_FRAME_LEN: usize = 60;
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const TCP_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const ICMP_HEADER_LEN: usize = 8;
+const DEFAULT_TTL: u8 = 64;
+const DEFAULT_WINDOW: u16 = 64_240;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const IPV6_FRAGMENT_HEADER_LEN: usize = 8;
+/// Most times a frame is sent in a row by one request.
+pub const MAX_SEND_COUNT: u32 = 10_000;
+
+/// A frame to build, described layer by layer. Lengths and checksums are filled in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FrameSpec {
+    /// Defaults to the MAC of the interface the frame is built for.
+    pub src_mac: Option<String>,
+    /// Defaults to broadcast.
+    pub dst_mac: Option<String>,
+    pub network: NetworkSpec,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NetworkSpec {
+    Arp {
+        #[serde(default)]
+        reply: bool,
+        sender_ip: Ipv4Addr,
+        target_ip: Ipv4Addr,
+        /// Left zero in requests, which ask for it. Replies default to the destination MAC.
+        target_mac: Option<String>,
+    },
+    /// IPv4 or IPv6, as the addresses say; both must be of the same version.
+    Ip {
+        src: IpAddr,
+        dst: IpAddr,
+        /// TTL or hop limit.
+        ttl: Option<u8>,
+        transport: TransportSpec,
+    },
+    /// Any other ethertype, with its payload as given.
+    Raw {
+        ethertype: u16,
+        #[serde(default, with = "hex_bytes")]
+        payload: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransportSpec {
+    Tcp {
+        src_port: u16,
+        dst_port: u16,
+        #[serde(default)]
+        seq: u32,
+        #[serde(default)]
+        ack: u32,
+        /// Flag names as packets show them, e.g. `["SYN", "ACK"]`.
+        #[serde(default)]
+        flags: Vec<String>,
+        window: Option<u16>,
+        #[serde(default, with = "hex_bytes")]
+        payload: Vec<u8>,
+    },
+    Udp {
+        src_port: u16,
+        dst_port: u16,
+        #[serde(default, with = "hex_bytes")]
+        payload: Vec<u8>,
+    },
+    /// ICMP over IPv4, ICMPv6 over IPv6. An echo request unless a type is given.
+    Icmp {
+        icmp_type: Option<u8>,
+        #[serde(default)]
+        code: u8,
+        /// Identifier and sequence number of echo messages, the rest of the header for others.
+        #[serde(default)]
+        identifier: u16,
+        #[serde(default)]
+        sequence: u16,
+        #[serde(default, with = "hex_bytes")]
+        payload: Vec<u8>,
+    },
+    /// Any other IP protocol, with its payload as given.
+    Raw {
+        protocol: u8,
+        #[serde(default, with = "hex_bytes")]
+        payload: Vec<u8>,
+    },
+}
+
+/// Which part of a datagram an IP packet carries. Only the first fragment has the transport
+/// header, and the transport checksum covers the whole datagram.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Fragment {
+    Whole,
+    First,
+    Later,
+}
+
+impl Fragment {
+    fn new(offset: u16, more: bool) -> Self {
+        match (offset, more) {
+            (0, false) => Fragment::Whole,
+            (0, true) => Fragment::First,
+            _ => Fragment::Later,
+        }
+    }
+}
+
+/// Changes made to a captured frame before it's sent again. Unset fields stay as captured.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FrameEdit {
+    pub src_mac: Option<String>,
+    pub dst_mac: Option<String>,
+    pub src_ip: Option<IpAddr>,
+    pub dst_ip: Option<IpAddr>,
+    /// TTL or hop limit.
+    pub ttl: Option<u8>,
+    /// Only TCP and UDP have ports to change.
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+}
+
+impl FrameSpec {
+    /// Builds the Ethernet frame, as sent from `interface`.
+    pub fn build(&self, interface: &NetworkInterface) -> AppResult<Vec<u8>> {
+        let src_mac = match &self.src_mac {
+            Some(mac) => parse_mac(mac)?,
+            None => interface.mac.unwrap_or_default(),
+        };
+        let dst_mac = match &self.dst_mac {
+            Some(mac) => parse_mac(mac)?,
+            None => MacAddr::broadcast(),
+        };
+        let (ethertype, payload) = match &self.network {
+            NetworkSpec::Arp {
+                reply,
+                sender_ip,
+                target_ip,
+                target_mac,
+            } => {
+                let target_mac = match target_mac {
+                    Some(mac) => parse_mac(mac)?,
+                    None if *reply => dst_mac,
+                    None => MacAddr::zero(),
+                };
+                let arp = arp(*reply, src_mac, *sender_ip, target_mac, *target_ip);
+                (EtherTypes::Arp, arp)
+            }
+            NetworkSpec::Ip {
+                src,
+                dst,
+                ttl,
+                transport,
+            } => ip(*src, *dst, ttl.unwrap_or(DEFAULT_TTL), transport)?,
+            NetworkSpec::Raw { ethertype, payload } => {
+                (EtherType::new(*ethertype), payload.clone())
+            }
+        };
+        let mut frame = ethernet(src_mac, dst_mac, ethertype, &payload);
+        fix_checksums(&mut frame);
+        Ok(frame)
+    }
+}
+
+impl TransportSpec {
+    fn build(&self, ipv6: bool) -> AppResult<(IpNextHeaderProtocol, Vec<u8>)> {
+        Ok(match self {
+            TransportSpec::Tcp {
+                src_port,
+                dst_port,
+                seq,
+                ack,
+                flags,
+                window,
+                payload,
+            } => {
+                let mut segment = vec![0; TCP_HEADER_LEN + payload.len()];
+                let mut tcp = MutableTcpPacket::new(&mut segment).expect("sized for the header");
+                tcp.set_source(*src_port);
+                tcp.set_destination(*dst_port);
+                tcp.set_sequence(*seq);
+                tcp.set_acknowledgement(*ack);
+                tcp.set_data_offset((TCP_HEADER_LEN / 4) as u8);
+                tcp.set_flags(tcp_flags(flags)?);
+                tcp.set_window(window.unwrap_or(DEFAULT_WINDOW));
+                tcp.set_payload(payload);
+                (IpNextHeaderProtocols::Tcp, segment)
+            }
+            TransportSpec::Udp {
+                src_port,
+                dst_port,
+                payload,
+            } => {
+                let mut datagram = vec![0; UDP_HEADER_LEN + payload.len()];
+                let length = u16::try_from(datagram.len()).map_err(|_| too_long())?;
+                let mut udp = MutableUdpPacket::new(&mut datagram).expect("sized for the header");
+                udp.set_source(*src_port);
+                udp.set_destination(*dst_port);
+                udp.set_length(length);
+                udp.set_payload(payload);
+                (IpNextHeaderProtocols::Udp, datagram)
+            }
+            TransportSpec::Icmp {
+                icmp_type,
+                code,
+                identifier,
+                sequence,
+                payload,
+            } => {
+                let (protocol, echo_request) = match ipv6 {
+                    true => (IpNextHeaderProtocols::Icmpv6, ICMPV6_ECHO_REQUEST),
+                    false => (IpNextHeaderProtocols::Icmp, ICMP_ECHO_REQUEST),
+                };
+                // type, code, checksum, then the type-specific rest of the header
+                let mut message = Vec::with_capacity(ICMP_HEADER_LEN + payload.len());
+                message.extend_from_slice(&[icmp_type.unwrap_or(echo_request), *code, 0, 0]);
+                message.extend_from_slice(&identifier.to_be_bytes());
+                message.extend_from_slice(&sequence.to_be_bytes());
+                message.extend_from_slice(payload);
+                (protocol, message)
+            }
+            TransportSpec::Raw { protocol, payload } => {
+                (IpNextHeaderProtocol::new(*protocol), payload.clone())
+            }
+        })
+    }
+}
+
+impl FrameEdit {
+    fn apply(&self, frame: &mut [u8]) -> AppResult<()> {
+        let mut ethernet = MutableEthernetPacket::new(frame)
+            .ok_or_else(|| anyhow::anyhow!("The frame is too short for an Ethernet header"))?;
+        if let Some(mac) = &self.src_mac {
+            ethernet.set_source(parse_mac(mac)?);
+        }
+        if let Some(mac) = &self.dst_mac {
+            ethernet.set_destination(parse_mac(mac)?);
+        }
+        let edits_ip = self.src_ip.is_some()
+            || self.dst_ip.is_some()
+            || self.ttl.is_some()
+            || self.src_port.is_some()
+            || self.dst_port.is_some();
+        if !edits_ip {
+            return Ok(());
+        }
+        let no_ip = || anyhow::anyhow!("The frame has no IP header to edit");
+        match ethernet.get_ethertype() {
+            EtherTypes::Ipv4 => {
+                let mut ip = MutableIpv4Packet::new(ethernet.payload_mut()).ok_or_else(no_ip)?;
+                if let Some(src) = self.src_ip {
+                    ip.set_source(ipv4_address(src)?);
+                }
+                if let Some(dst) = self.dst_ip {
+                    ip.set_destination(ipv4_address(dst)?);
+                }
+                if let Some(ttl) = self.ttl {
+                    ip.set_ttl(ttl);
+                }
+                let protocol = ip.get_next_level_protocol();
+                let fragment = Fragment::new(
+                    ip.get_fragment_offset(),
+                    ip.get_flags() & Ipv4Flags::MoreFragments != 0,
+                );
+                self.set_ports(ip.payload_mut(), protocol, fragment)
+            }
+            EtherTypes::Ipv6 => {
+                let mut ip = MutableIpv6Packet::new(ethernet.payload_mut()).ok_or_else(no_ip)?;
+                if let Some(src) = self.src_ip {
+                    ip.set_source(ipv6_address(src)?);
+                }
+                if let Some(dst) = self.dst_ip {
+                    ip.set_destination(ipv6_address(dst)?);
+                }
+                if let Some(ttl) = self.ttl {
+                    ip.set_hop_limit(ttl);
+                }
+                let protocol = ip.get_next_header();
+                let payload = ip.payload_mut();
+                if protocol != IpNextHeaderProtocols::Ipv6Frag {
+                    return self.set_ports(payload, protocol, Fragment::Whole);
+                }
+                if payload.len() < IPV6_FRAGMENT_HEADER_LEN {
+                    return Err(anyhow::anyhow!("The IPv6 fragment header is cut short").into());
+                }
+                // next header, reserved, then the offset in 8-byte units and the M flag
+                let protocol = IpNextHeaderProtocol::new(payload[0]);
+                let offset_and_flags = u16::from_be_bytes([payload[2], payload[3]]);
+                let fragment = Fragment::new(offset_and_flags >> 3, offset_and_flags & 1 != 0);
+                self.set_ports(&mut payload[IPV6_FRAGMENT_HEADER_LEN..], protocol, fragment)
+            }
+            _ => Err(no_ip().into()),
+        }
+    }
+
+    fn set_ports(
+        &self,
+        segment: &mut [u8],
+        protocol: IpNextHeaderProtocol,
+        fragment: Fragment,
+    ) -> AppResult<()> {
+        if self.src_port.is_none() && self.dst_port.is_none() {
+            return Ok(());
+        }
+        if fragment == Fragment::Later {
+            return Err(anyhow::anyhow!(
+                "The frame is a later fragment of its packet, only the first one has ports"
+            )
+            .into());
+        }
+        // TCP and UDP both start with the source and destination ports
+        if !matches!(
+            protocol,
+            IpNextHeaderProtocols::Tcp | IpNextHeaderProtocols::Udp
+        ) || segment.len() < 4
+        {
+            return Err(anyhow::anyhow!("The frame has no TCP or UDP header to edit").into());
+        }
+        let before = [
+            u16::from_be_bytes([segment[0], segment[1]]),
+            u16::from_be_bytes([segment[2], segment[3]]),
+        ];
+        if let Some(port) = self.src_port {
+            segment[0..2].copy_from_slice(&port.to_be_bytes());
+        }
+        if let Some(port) = self.dst_port {
+            segment[2..4].copy_from_slice(&port.to_be_bytes());
+        }
+        // the checksum of a fragmented datagram can't be redone from this fragment alone,
+        // but the ports' share of it can be swapped
+        if fragment == Fragment::First {
+            let checksum_at = match protocol {
+                IpNextHeaderProtocols::Tcp => 16,
+                _ => 6,
+            };
+            if let Some(field) = segment.get(checksum_at..checksum_at + 2) {
+                let mut checksum = u16::from_be_bytes([field[0], field[1]]);
+                // a zero UDP checksum means there is none
+                if checksum != 0 || protocol == IpNextHeaderProtocols::Tcp {
+                    checksum = adjust_checksum(checksum, before[0], self.src_port);
+                    checksum = adjust_checksum(checksum, before[1], self.dst_port);
+                    if protocol == IpNextHeaderProtocols::Udp {
+                        checksum = udp_checksum(checksum);
+                    }
+                    segment[checksum_at..checksum_at + 2].copy_from_slice(&checksum.to_be_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The Ethernet frame that reproduces a captured one. Frames captured on other links get an
+/// Ethernet header from `interface`, addressed to broadcast unless edited. Unedited frames go
+/// out exactly as captured, bad checksums included; edited ones have their checksums redone.
+pub fn rebuild(
+    frame: &RawFrame,
+    interface: &NetworkInterface,
+    edit: Option<&FrameEdit>,
+) -> AppResult<Vec<u8>> {
+    if frame.data.is_empty() {
+        return Err(anyhow::anyhow!("The packet was imported without its frame bytes").into());
+    }
+    if frame.is_truncated() {
+        return Err(anyhow::anyhow!(
+            "The frame was cut short by the snaplen, so it can't be sent as captured"
+        )
+        .into());
+    }
+    let mut data = if frame.link_type == LINKTYPE_ETHERNET {
+        frame.data.clone()
+    } else {
+        let header = link::decode(frame.link_type, &frame.data).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Can't read the {} header of the frame",
+                link::name(frame.link_type)
+            )
+        })?;
+        ethernet(
+            interface.mac.unwrap_or_default(),
+            MacAddr::broadcast(),
+            header.ethertype,
+            header.payload,
+        )
+    };
+    if let Some(edit) = edit {
+        edit.apply(&mut data)?;
+        fix_checksums(&mut data);
+    }
+    Ok(data)
+}
+
+/// Sends an Ethernet frame on `interface` `count` times in a row, at most
+/// [`MAX_SEND_COUNT`].
+pub fn inject(interface: &NetworkInterface, frame: &[u8], count: u32) -> AppResult<()> {
+    if count == 0 || count > MAX_SEND_COUNT {
+        return Err(anyhow::anyhow!(
+            "A frame can be sent 1 to {} times, not {}",
+            MAX_SEND_COUNT,
+            count
+        )
+        .into());
+    }
+    check_can_send(interface)?;
+    let (mut tx, _) =
+        create_chanel(interface).map_err(|e| AppError::capture(&interface.name, e))?;
+    for _ in 0..count {
+        tx.send_to(frame, None)
+            .unwrap_or(Ok(()))
+            .map_err(|e| AppError::capture(&interface.name, e))?;
+    }
+    log::info!("sent {} frames on {}", count, interface.name);
+    Ok(())
+}
+
+/// Frames can only be sent on a single Ethernet interface that is up.
+pub fn check_can_send(interface: &NetworkInterface) -> AppResult<()> {
+    if interface.index == 0 {
+        return Err(anyhow::anyhow!("Frames can only be sent on a single interface").into());
+    }
+    if !interface.is_up() {
+        return Err(AppError::InterfaceDown(interface.name.clone()));
+    }
+    let link_type = LiveSource::link_type(interface);
+    if link_type != LINKTYPE_ETHERNET {
+        return Err(anyhow::anyhow!(
+            "{} is a {} interface, only Ethernet frames can be sent",
+            interface.name,
+            link::name(link_type)
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn ethernet(src: MacAddr, dst: MacAddr, ethertype: EtherType, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0; (ETHERNET_HEADER_LEN + payload.len()).max(MIN_FRAME_LEN)];
+    let mut ethernet = MutableEthernetPacket::new(&mut frame).expect("sized for the header");
+    ethernet.set_source(src);
+    ethernet.set_destination(dst);
+    ethernet.set_ethertype(ethertype);
+    ethernet.payload_mut()[..payload.len()].copy_from_slice(payload);
+    frame
+}
+
+fn arp(
+    reply: bool,
+    sender_mac: MacAddr,
+    sender_ip: Ipv4Addr,
+    target_mac: MacAddr,
+    target_ip: Ipv4Addr,
+) -> Vec<u8> {
+    let mut buffer = vec![0; MutableArpPacket::minimum_packet_size()];
+    let mut arp = MutableArpPacket::new(&mut buffer).expect("sized for the header");
+    arp.set_hardware_type(ArpHardwareTypes::Ethernet);
+    arp.set_protocol_type(EtherTypes::Ipv4);
+    arp.set_hw_addr_len(6);
+    arp.set_proto_addr_len(4);
+    arp.set_operation(match reply {
+        true => ArpOperations::Reply,
+        false => ArpOperations::Request,
+    });
+    arp.set_sender_hw_addr(sender_mac);
+    arp.set_sender_proto_addr(sender_ip);
+    arp.set_target_hw_addr(target_mac);
+    arp.set_target_proto_addr(target_ip);
+    buffer
+}
+
+fn ip(
+    src: IpAddr,
+    dst: IpAddr,
+    ttl: u8,
+    transport: &TransportSpec,
+) -> AppResult<(EtherType, Vec<u8>)> {
+    let (protocol, segment) = transport.build(src.is_ipv6())?;
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut packet = vec![0; IPV4_HEADER_LEN + segment.len()];
+            let total_length = u16::try_from(packet.len()).map_err(|_| too_long())?;
+            let mut ip = MutableIpv4Packet::new(&mut packet).expect("sized for the header");
+            ip.set_version(4);
+            ip.set_header_length((IPV4_HEADER_LEN / 4) as u8);
+            ip.set_total_length(total_length);
+            ip.set_ttl(ttl);
+            ip.set_next_level_protocol(protocol);
+            ip.set_source(src);
+            ip.set_destination(dst);
+            ip.set_payload(&segment);
+            Ok((EtherTypes::Ipv4, packet))
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            let payload_length = u16::try_from(segment.len()).map_err(|_| too_long())?;
+            let mut packet = vec![0; IPV6_HEADER_LEN + segment.len()];
+            let mut ip = MutableIpv6Packet::new(&mut packet).expect("sized for the header");
+            ip.set_version(6);
+            ip.set_payload_length(payload_length);
+            ip.set_next_header(protocol);
+            ip.set_hop_limit(ttl);
+            ip.set_source(src);
+            ip.set_destination(dst);
+            ip.set_payload(&segment);
+            Ok((EtherTypes::Ipv6, packet))
+        }
+        _ => Err(anyhow::anyhow!(
+            "Source {} and destination {} are not the same IP version",
+            src,
+            dst
+        )
+        .into()),
+    }
+}
+
+/// Recomputes the IPv4 header checksum and the TCP, UDP, ICMP or ICMPv6 checksum of an
+/// Ethernet frame. Frames carrying anything else are left alone.
+fn fix_checksums(frame: &mut [u8]) {
+    let Some(mut ethernet) = MutableEthernetPacket::new(frame) else {
+        return;
+    };
+    match ethernet.get_ethertype() {
+        EtherTypes::Ipv4 => {
+            let Some(mut ip) = MutableIpv4Packet::new(ethernet.payload_mut()) else {
+                return;
+            };
+            // a fragment's transport checksum covers the whole datagram, which we don't have
+            let fragmented =
+                ip.get_fragment_offset() != 0 || ip.get_flags() & Ipv4Flags::MoreFragments != 0;
+            if !fragmented {
+                let (src, dst) = (ip.get_source(), ip.get_destination());
+                let protocol = ip.get_next_level_protocol();
+                fix_transport_checksum(ip.payload_mut(), protocol, src.into(), dst.into());
+            }
+            let checksum = ipv4::checksum(&ip.to_immutable());
+            ip.set_checksum(checksum);
+        }
+        EtherTypes::Ipv6 => {
+            let Some(mut ip) = MutableIpv6Packet::new(ethernet.payload_mut()) else {
+                return;
+            };
+            let (src, dst) = (ip.get_source(), ip.get_destination());
+            let protocol = ip.get_next_header();
+            fix_transport_checksum(ip.payload_mut(), protocol, src.into(), dst.into());
+        }
+        _ => {}
+    }
+}
+
+fn fix_transport_checksum(
+    segment: &mut [u8],
+    protocol: IpNextHeaderProtocol,
+    src: IpAddr,
+    dst: IpAddr,
+) {
+    match (protocol, src, dst) {
+        (IpNextHeaderProtocols::Tcp, IpAddr::V4(src), IpAddr::V4(dst)) => {
+            if let Some(mut tcp) = MutableTcpPacket::new(segment) {
+                let checksum = tcp::ipv4_checksum(&tcp.to_immutable(), &src, &dst);
+                tcp.set_checksum(checksum);
+            }
+        }
+        (IpNextHeaderProtocols::Tcp, IpAddr::V6(src), IpAddr::V6(dst)) => {
+            if let Some(mut tcp) = MutableTcpPacket::new(segment) {
+                let checksum = tcp::ipv6_checksum(&tcp.to_immutable(), &src, &dst);
+                tcp.set_checksum(checksum);
+            }
+        }
+        (IpNextHeaderProtocols::Udp, IpAddr::V4(src), IpAddr::V4(dst)) => {
+            if let Some(mut udp) = MutableUdpPacket::new(segment) {
+                let checksum = udp::ipv4_checksum(&udp.to_immutable(), &src, &dst);
+                udp.set_checksum(udp_checksum(checksum));
+            }
+        }
+        (IpNextHeaderProtocols::Udp, IpAddr::V6(src), IpAddr::V6(dst)) => {
+            if let Some(mut udp) = MutableUdpPacket::new(segment) {
+                let checksum = udp::ipv6_checksum(&udp.to_immutable(), &src, &dst);
+                udp.set_checksum(udp_checksum(checksum));
+            }
+        }
+        (IpNextHeaderProtocols::Icmp, IpAddr::V4(_), IpAddr::V4(_)) => {
+            if let Some(mut icmp) = MutableIcmpPacket::new(segment) {
+                let checksum = icmp::checksum(&icmp.to_immutable());
+                icmp.set_checksum(checksum);
+            }
+        }
+        (IpNextHeaderProtocols::Icmpv6, IpAddr::V6(src), IpAddr::V6(dst)) => {
+            if let Some(mut icmp) = MutableIcmpv6Packet::new(segment) {
+                let checksum = icmpv6::checksum(&icmp.to_immutable(), &src, &dst);
+                icmp.set_checksum(checksum);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `checksum` with the 16-bit word `old` replaced by `new`, if set (RFC 1624).
+fn adjust_checksum(checksum: u16, old: u16, new: Option<u16>) -> u16 {
+    let Some(new) = new else {
+        return checksum;
+    };
+    let mut sum = !checksum as u32 + !old as u32 + new as u32;
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// A UDP checksum of zero means "none", so one that computes to zero is sent as all ones.
+fn udp_checksum(checksum: u16) -> u16 {
+    match checksum {
+        0 => 0xffff,
+        checksum => checksum,
+    }
+}
+
+fn tcp_flags(names: &[String]) -> AppResult<u8> {
+    names.iter().try_fold(0, |flags, name| {
+        let flag = match name.to_ascii_uppercase().as_str() {
+            "FIN" => TcpFlags::FIN,
+            "SYN" => TcpFlags::SYN,
+            "RST" => TcpFlags::RST,
+            "PSH" => TcpFlags::PSH,
+            "ACK" => TcpFlags::ACK,
+            "URG" => TcpFlags::URG,
+            "ECE" => TcpFlags::ECE,
+            "CWR" => TcpFlags::CWR,
+            _ => return Err(anyhow::anyhow!("Unknown TCP flag {}", name).into()),
+        };
+        Ok(flags | flag)
+    })
+}
+
+fn parse_mac(mac: &str) -> AppResult<MacAddr> {
+    mac.parse()
+        .map_err(|_| anyhow::anyhow!("{} is not a MAC address", mac).into())
+}
+
+fn ipv4_address(ip: IpAddr) -> AppResult<Ipv4Addr> {
+    match ip {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(_) => Err(anyhow::anyhow!("{} is not an IPv4 address", ip).into()),
+    }
+}
+
+fn ipv6_address(ip: IpAddr) -> AppResult<Ipv6Addr> {
+    match ip {
+        IpAddr::V6(ip) => Ok(ip),
+        IpAddr::V4(_) => Err(anyhow::anyhow!("{} is not an IPv6 address", ip).into()),
+    }
+}
+
+fn too_long() -> AppError {
+    anyhow::anyhow!("The payload doesn't fit in a single IP packet").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use pnet::packet::{
+        ethernet::EthernetPacket, icmp::IcmpPacket, icmpv6::Icmpv6Packet, ipv4::Ipv4Packet,
+        ipv6::Ipv6Packet, tcp::TcpPacket, udp::UdpPacket, Packet,
+    };
+
+    use super::*;
+
+    const V4_SRC: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+    const V4_DST: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 20);
+    const V6_SRC: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const V6_DST: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+
+    fn interface() -> NetworkInterface {
+        NetworkInterface {
+            name: "eth0".into(),
+            description: String::new(),
+            index: 1,
+            mac: Some(MacAddr::new(2, 0, 0, 0, 0, 1)),
+            ips: Vec::new(),
+            flags: 0,
+        }
+    }
+
+    fn build(src: IpAddr, dst: IpAddr, transport: TransportSpec) -> Vec<u8> {
+        let spec = FrameSpec {
+            src_mac: None,
+            dst_mac: None,
+            network: NetworkSpec::Ip {
+                src,
+                dst,
+                ttl: None,
+                transport,
+            },
+        };
+        spec.build(&interface()).unwrap()
+    }
+
+    fn tcp_spec() -> TransportSpec {
+        TransportSpec::Tcp {
+            src_port: 40_000,
+            dst_port: 80,
+            seq: 1,
+            ack: 0,
+            flags: vec!["SYN".into()],
+            window: None,
+            payload: b"GET / HTTP/1.1\r\n".to_vec(),
+        }
+    }
+
+    fn udp_spec() -> TransportSpec {
+        TransportSpec::Udp {
+            src_port: 5353,
+            dst_port: 53,
+            payload: vec![0xab; 13],
+        }
+    }
+
+    fn icmp_spec() -> TransportSpec {
+        TransportSpec::Icmp {
+            icmp_type: None,
+            code: 0,
+            identifier: 7,
+            sequence: 1,
+            payload: vec![1, 2, 3],
+        }
+    }
+
+    /// Checks every checksum in the frame against pnet's own.
+    fn assert_checksums(frame: &[u8]) {
+        let ethernet = EthernetPacket::new(frame).unwrap();
+        match ethernet.get_ethertype() {
+            EtherTypes::Ipv4 => {
+                let ip = Ipv4Packet::new(ethernet.payload()).unwrap();
+                assert_eq!(ip.get_checksum(), ipv4::checksum(&ip));
+                let (src, dst) = (ip.get_source(), ip.get_destination());
+                match ip.get_next_level_protocol() {
+                    IpNextHeaderProtocols::Tcp => {
+                        let tcp = TcpPacket::new(ip.payload()).unwrap();
+                        assert_eq!(tcp.get_checksum(), tcp::ipv4_checksum(&tcp, &src, &dst));
+                    }
+                    IpNextHeaderProtocols::Udp => {
+                        let udp = UdpPacket::new(ip.payload()).unwrap();
+                        assert_eq!(udp.get_checksum(), udp::ipv4_checksum(&udp, &src, &dst));
+                    }
+                    IpNextHeaderProtocols::Icmp => {
+                        let icmp = IcmpPacket::new(ip.payload()).unwrap();
+                        assert_eq!(icmp.get_checksum(), icmp::checksum(&icmp));
+                    }
+                    protocol => panic!("unexpected protocol {protocol}"),
+                }
+            }
+            EtherTypes::Ipv6 => {
+                let ip = Ipv6Packet::new(ethernet.payload()).unwrap();
+                let (src, dst) = (ip.get_source(), ip.get_destination());
+                match ip.get_next_header() {
+                    IpNextHeaderProtocols::Tcp => {
+                        let tcp = TcpPacket::new(ip.payload()).unwrap();
+                        assert_eq!(tcp.get_checksum(), tcp::ipv6_checksum(&tcp, &src, &dst));
+                    }
+                    IpNextHeaderProtocols::Udp => {
+                        let udp = UdpPacket::new(ip.payload()).unwrap();
+                        assert_eq!(udp.get_checksum(), udp::ipv6_checksum(&udp, &src, &dst));
+                    }
+                    IpNextHeaderProtocols::Icmpv6 => {
+                        let icmp = Icmpv6Packet::new(ip.payload()).unwrap();
+                        assert_eq!(icmp.get_checksum(), icmpv6::checksum(&icmp, &src, &dst));
+                    }
+                    protocol => panic!("unexpected next header {protocol}"),
+                }
+            }
+            ethertype => panic!("unexpected ethertype {ethertype}"),
+        }
+    }
+
+    fn ports(frame: &[u8], ip_header_len: usize) -> (u16, u16) {
+        let segment = &frame[ETHERNET_HEADER_LEN + ip_header_len..];
+        (
+            u16::from_be_bytes([segment[0], segment[1]]),
+            u16::from_be_bytes([segment[2], segment[3]]),
+        )
+    }
+
+    #[test]
+    fn builds_ipv4_with_correct_checksums() {
+        for transport in [tcp_spec(), udp_spec(), icmp_spec()] {
+            let frame = build(V4_SRC.into(), V4_DST.into(), transport);
+            assert!(frame.len() >= MIN_FRAME_LEN);
+            assert_checksums(&frame);
+        }
+    }
+
+    #[test]
+    fn builds_ipv6_with_correct_checksums() {
+        for transport in [tcp_spec(), udp_spec(), icmp_spec()] {
+            let frame = build(V6_SRC.into(), V6_DST.into(), transport);
+            assert_checksums(&frame);
+        }
+    }
+
+    #[test]
+    fn rebuilds_an_edited_frame_with_correct_checksums() {
+        let edit = FrameEdit {
+            dst_ip: Some(Ipv4Addr::new(10, 0, 0, 1).into()),
+            ttl: Some(3),
+            src_port: Some(1234),
+            dst_port: Some(8080),
+            ..Default::default()
+        };
+        for transport in [tcp_spec(), udp_spec()] {
+            let frame = RawFrame::new(
+                "eth0",
+                &build(V4_SRC.into(), V4_DST.into(), transport),
+                0,
+                0,
+            );
+            let rebuilt = rebuild(&frame, &interface(), Some(&edit)).unwrap();
+
+            let ip = Ipv4Packet::new(&rebuilt[ETHERNET_HEADER_LEN..]).unwrap();
+            assert_eq!(ip.get_destination(), Ipv4Addr::new(10, 0, 0, 1));
+            assert_eq!(ip.get_ttl(), 3);
+            assert_eq!(ports(&rebuilt, IPV4_HEADER_LEN), (1234, 8080));
+            assert_checksums(&rebuilt);
+        }
+    }
+
+    #[test]
+    fn sends_an_unedited_frame_as_captured() {
+        let mut data = build(V4_SRC.into(), V4_DST.into(), udp_spec());
+        // a bad checksum stays bad
+        data[ETHERNET_HEADER_LEN + IPV4_HEADER_LEN + 6] ^= 0xff;
+        let frame = RawFrame::new("eth0", &data, 0, 0);
+        assert_eq!(rebuild(&frame, &interface(), None).unwrap(), data);
+    }
+
+    #[test]
+    fn adjusts_the_checksum_of_a_first_fragment() {
+        let mut data = build(V4_SRC.into(), V4_DST.into(), tcp_spec());
+        let flags = ETHERNET_HEADER_LEN + 6;
+        data[flags] |= 0x20;
+        let frame = RawFrame::new("eth0", &data, 0, 0);
+        let edit = FrameEdit {
+            src_port: Some(1234),
+            ..Default::default()
+        };
+        let mut rebuilt = rebuild(&frame, &interface(), Some(&edit)).unwrap();
+        assert_eq!(ports(&rebuilt, IPV4_HEADER_LEN), (1234, 80));
+
+        // whole again, the datagram's checksum must still add up
+        rebuilt[flags] &= !0x20;
+        fix_ipv4_header_checksum(&mut rebuilt);
+        assert_checksums(&rebuilt);
+    }
+
+    #[test]
+    fn refuses_to_edit_the_ports_of_a_later_ipv4_fragment() {
+        let mut data = build(V4_SRC.into(), V4_DST.into(), udp_spec());
+        // fragment offset 185, i.e. 1480 bytes in
+        data[ETHERNET_HEADER_LEN + 7] = 185;
+        let frame = RawFrame::new("eth0", &data, 0, 0);
+        let edit = FrameEdit {
+            dst_port: Some(1),
+            ..Default::default()
+        };
+        assert!(rebuild(&frame, &interface(), Some(&edit)).is_err());
+        // other fields can still be edited
+        let edit = FrameEdit {
+            ttl: Some(1),
+            ..Default::default()
+        };
+        let rebuilt = rebuild(&frame, &interface(), Some(&edit)).unwrap();
+        assert_eq!(
+            rebuilt[ETHERNET_HEADER_LEN + IPV4_HEADER_LEN..],
+            data[ETHERNET_HEADER_LEN + IPV4_HEADER_LEN..]
+        );
+    }
+
+    #[test]
+    fn refuses_to_edit_the_ports_of_a_later_ipv6_fragment() {
+        let udp = build(V6_SRC.into(), V6_DST.into(), udp_spec());
+        let segment = &udp[ETHERNET_HEADER_LEN + IPV6_HEADER_LEN..];
+        let fragment = |offset: u16| {
+            let mut payload = vec![IpNextHeaderProtocols::Udp.0, 0];
+            payload.extend_from_slice(&(offset << 3).to_be_bytes());
+            payload.extend_from_slice(&[0, 0, 0, 1]);
+            payload.extend_from_slice(segment);
+            let transport = TransportSpec::Raw {
+                protocol: IpNextHeaderProtocols::Ipv6Frag.0,
+                payload,
+            };
+            RawFrame::new(
+                "eth0",
+                &build(V6_SRC.into(), V6_DST.into(), transport),
+                0,
+                0,
+            )
+        };
+        let edit = FrameEdit {
+            dst_port: Some(1),
+            ..Default::default()
+        };
+        assert!(rebuild(&fragment(100), &interface(), Some(&edit)).is_err());
+
+        let rebuilt = rebuild(&fragment(0), &interface(), Some(&edit)).unwrap();
+        assert_eq!(
+            ports(&rebuilt, IPV6_HEADER_LEN + IPV6_FRAGMENT_HEADER_LEN),
+            (5353, 1)
+        );
+    }
+
+    fn fix_ipv4_header_checksum(frame: &mut [u8]) {
+        let mut ip = MutableIpv4Packet::new(&mut frame[ETHERNET_HEADER_LEN..]).unwrap();
+        let checksum = ipv4::checksum(&ip.to_immutable());
+        ip.set_checksum(checksum);
+    }
+}
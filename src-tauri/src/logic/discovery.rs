@@ -0,0 +1,529 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use pnet::{
+    datalink::NetworkInterface,
+    ipnetwork::{IpNetwork, Ipv4Network},
+    packet::{
+        arp::{ArpOperations, ArpPacket},
+        ethernet::{EtherTypes, EthernetPacket},
+        icmpv6::{Icmpv6Packet, Icmpv6Types},
+        ip::IpNextHeaderProtocols,
+        ipv6::Ipv6Packet,
+        Packet,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    craft::{self, FrameSpec, NetworkSpec, TransportSpec},
+    create_chanel,
+    show_packets::FormatedPacket,
+};
+use crate::errors::{AppError, AppResult};
+
+/// How long to keep listening for answers after the last probe went out.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+/// IPv4 subnets larger than this are only swept in the block of this size around the
+/// interface's own address, so a /8 doesn't mean sixteen million ARP requests.
+const MAX_SWEEP_PREFIX: u8 = 22;
+/// Gap between probes, so a sweep doesn't flood the link or overrun the receive buffers of
+/// the hosts answering. A /22 takes about a second.
+const PROBE_INTERVAL: Duration = Duration::from_millis(1);
+/// Neighbour discovery messages must be sent, and are only accepted, with this hop limit.
+const NDP_HOP_LIMIT: u8 = 255;
+const ICMPV6_NEIGHBOR_SOLICITATION: u8 = 135;
+const ALL_NODES_MAC: &str = "33:33:00:00:00:01";
+
+/// How a host was found.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Probe {
+    Arp,
+    NeighborSolicitation,
+    /// Answered an ICMPv6 echo sent to all nodes on the link.
+    Echo,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiscoveredHost {
+    pub ip: IpAddr,
+    pub mac: String,
+    pub probe: Probe,
+    /// From sending the probe to reading the answer.
+    pub response_ms: f64,
+    /// Packets the host sent in the session the scan was linked to, and the numbers of the
+    /// first and last of them.
+    pub packets: usize,
+    pub first_packet: Option<u32>,
+    pub last_packet: Option<u32>,
+}
+
+struct ProbeFrame {
+    /// Unset for the echo to all nodes, which any host may answer.
+    target: Option<IpAddr>,
+    frame: Vec<u8>,
+}
+
+/// Probes the subnets of `interface` and lists the hosts that answered, by address. IPv4
+/// subnets are swept with ARP. An IPv6 /64 is too large to sweep, so IPv6 neighbours are found
+/// with an echo to all nodes and a neighbour solicitation for each of `ipv6_candidates` on the
+/// link, e.g. addresses seen in a capture.
+pub fn discover(
+    interface: &NetworkInterface,
+    ipv6_candidates: &[Ipv6Addr],
+    timeout: Duration,
+) -> AppResult<Vec<DiscoveredHost>> {
+    craft::check_can_send(interface)?;
+    let probes = probes(interface, ipv6_candidates)?;
+    if probes.is_empty() {
+        return Err(anyhow::anyhow!("{} has no IP address to scan from", interface.name).into());
+    }
+    let own: HashSet<IpAddr> = interface.ips.iter().map(|network| network.ip()).collect();
+    let (mut tx, mut rx) =
+        create_chanel(interface).map_err(|e| AppError::capture(&interface.name, e))?;
+    log::info!(
+        "discovery on {}: sending {} probes",
+        interface.name,
+        probes.len()
+    );
+
+    let sent_at = Mutex::new(HashMap::new());
+    let sent_all_at = Mutex::new(None::<Instant>);
+    let mut hosts: HashMap<IpAddr, DiscoveredHost> = HashMap::new();
+    // answers are read while probes still go out, so response times aren't inflated by the
+    // time it takes to send the rest
+    let sent = thread::scope(|scope| {
+        let sender = scope.spawn(|| {
+            let result = probes.iter().enumerate().try_for_each(|(i, probe)| {
+                if i > 0 {
+                    thread::sleep(PROBE_INTERVAL);
+                }
+                sent_at.lock().unwrap().insert(probe.target, Instant::now());
+                tx.send_to(&probe.frame, None).unwrap_or(Ok(()))
+            });
+            *sent_all_at.lock().unwrap() = Some(Instant::now());
+            result
+        });
+        while sent_all_at
+            .lock()
+            .unwrap()
+            .is_none_or(|at| at.elapsed() < timeout)
+        {
+            let data = match rx.next() {
+                Ok(data) => data,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    log::warn!("discovery on {}: read failed: {}", interface.name, e);
+                    continue;
+                }
+            };
+            let Some((ip, mac, probe)) = parse_answer(data, &own) else {
+                continue;
+            };
+            if hosts.contains_key(&ip) {
+                continue;
+            }
+            let target = match probe {
+                Probe::Echo => None,
+                _ => Some(ip),
+            };
+            // answers to probes we didn't send, e.g. from another scanner, don't count
+            let Some(at) = sent_at.lock().unwrap().get(&target).copied() else {
+                continue;
+            };
+            hosts.insert(
+                ip,
+                DiscoveredHost {
+                    ip,
+                    mac,
+                    probe,
+                    response_ms: at.elapsed().as_secs_f64() * 1e3,
+                    packets: 0,
+                    first_packet: None,
+                    last_packet: None,
+                },
+            );
+        }
+        sender.join().expect("probe sender panicked")
+    });
+    sent.map_err(|e| AppError::capture(&interface.name, e))?;
+
+    let mut hosts: Vec<DiscoveredHost> = hosts.into_values().collect();
+    hosts.sort_by_key(|host| host.ip);
+    log::info!("discovery on {}: {} hosts", interface.name, hosts.len());
+    Ok(hosts)
+}
+
+/// Counts the packets each host sent in `packets`, and where they start and end, so the
+/// packet list can be filtered down to them.
+pub fn link_packets<'a>(
+    hosts: &mut [DiscoveredHost],
+    packets: impl IntoIterator<Item = &'a FormatedPacket>,
+) {
+    let index: HashMap<IpAddr, usize> = hosts
+        .iter()
+        .enumerate()
+        .map(|(i, host)| (host.ip, i))
+        .collect();
+    for packet in packets {
+        let Some(&i) = packet.src_ip.as_ref().and_then(|ip| index.get(ip)) else {
+            continue;
+        };
+        let host = &mut hosts[i];
+        host.packets += 1;
+        host.first_packet.get_or_insert(packet.number);
+        host.last_packet = Some(packet.number);
+    }
+}
+
+fn probes(
+    interface: &NetworkInterface,
+    ipv6_candidates: &[Ipv6Addr],
+) -> AppResult<Vec<ProbeFrame>> {
+    let mut probes = Vec::new();
+    let own: HashSet<IpAddr> = interface.ips.iter().map(|network| network.ip()).collect();
+    for network in &interface.ips {
+        let IpNetwork::V4(network) = network else {
+            continue;
+        };
+        for target in sweep(*network).filter(|ip| !own.contains(&IpAddr::V4(*ip))) {
+            let spec = FrameSpec {
+                src_mac: None,
+                dst_mac: None,
+                network: NetworkSpec::Arp {
+                    reply: false,
+                    sender_ip: network.ip(),
+                    target_ip: target,
+                    target_mac: None,
+                },
+            };
+            probes.push(ProbeFrame {
+                target: Some(target.into()),
+                frame: spec.build(interface)?,
+            });
+        }
+    }
+
+    // neighbours answer from, and to, link-local addresses
+    let Some(src) = interface
+        .ips
+        .iter()
+        .filter_map(|network| match network.ip() {
+            IpAddr::V6(ip) => Some(ip),
+            IpAddr::V4(_) => None,
+        })
+        .min_by_key(|ip| !is_link_local(ip))
+    else {
+        return Ok(probes);
+    };
+    let echo = FrameSpec {
+        src_mac: None,
+        dst_mac: Some(ALL_NODES_MAC.to_string()),
+        network: NetworkSpec::Ip {
+            src: src.into(),
+            dst: Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).into(),
+            ttl: Some(NDP_HOP_LIMIT),
+            transport: TransportSpec::Icmp {
+                icmp_type: None,
+                code: 0,
+                identifier: std::process::id() as u16,
+                sequence: 1,
+                payload: Vec::new(),
+            },
+        },
+    };
+    probes.push(ProbeFrame {
+        target: None,
+        frame: echo.build(interface)?,
+    });
+    let on_link = |ip: &Ipv6Addr| {
+        is_link_local(ip)
+            || interface.ips.iter().any(|network| match network {
+                IpNetwork::V6(network) => network.contains(*ip),
+                IpNetwork::V4(_) => false,
+            })
+    };
+    let candidates: HashSet<&Ipv6Addr> = ipv6_candidates
+        .iter()
+        .filter(|ip| on_link(ip) && !own.contains(&IpAddr::V6(**ip)))
+        .collect();
+    for target in candidates {
+        probes.push(ProbeFrame {
+            target: Some((*target).into()),
+            frame: neighbor_solicitation(interface, src, *target)?,
+        });
+    }
+    Ok(probes)
+}
+
+/// The IPv4 addresses to ask for on `network`, leaving out its network and broadcast addresses.
+fn sweep(network: Ipv4Network) -> impl Iterator<Item = Ipv4Addr> {
+    let excluded = match network.prefix() {
+        // point-to-point links use both addresses
+        31.. => None,
+        _ => Some((network.network(), network.broadcast())),
+    };
+    let block = Ipv4Network::new(network.ip(), network.prefix().max(MAX_SWEEP_PREFIX))
+        .expect("prefix is at most 32");
+    block
+        .iter()
+        .filter(move |ip| excluded.is_none_or(|(first, last)| *ip != first && *ip != last))
+}
+
+/// Asks who has `target`, at the solicited-node multicast address every host with it listens to.
+fn neighbor_solicitation(
+    interface: &NetworkInterface,
+    src: Ipv6Addr,
+    target: Ipv6Addr,
+) -> AppResult<Vec<u8>> {
+    let t = target.octets();
+    let group = Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | t[13] as u16,
+        u16::from_be_bytes([t[14], t[15]]),
+    );
+    // the target, then our link-layer address so the answer needs no solicitation of its own
+    let mut payload = t.to_vec();
+    payload.extend_from_slice(&[1, 1]);
+    payload.extend_from_slice(&interface.mac.unwrap_or_default().octets());
+    FrameSpec {
+        src_mac: None,
+        dst_mac: Some(format!(
+            "33:33:ff:{:02x}:{:02x}:{:02x}",
+            t[13], t[14], t[15]
+        )),
+        network: NetworkSpec::Ip {
+            src: src.into(),
+            dst: group.into(),
+            ttl: Some(NDP_HOP_LIMIT),
+            transport: TransportSpec::Icmp {
+                icmp_type: Some(ICMPV6_NEIGHBOR_SOLICITATION),
+                code: 0,
+                identifier: 0,
+                sequence: 0,
+                payload,
+            },
+        },
+    }
+    .build(interface)
+}
+
+/// The address and MAC of a host answering one of our probes: an ARP reply, a neighbour
+/// advertisement or an echo reply addressed to one of `own`.
+fn parse_answer(data: &[u8], own: &HashSet<IpAddr>) -> Option<(IpAddr, String, Probe)> {
+    let ethernet = EthernetPacket::new(data)?;
+    match ethernet.get_ethertype() {
+        EtherTypes::Arp => {
+            let arp = ArpPacket::new(ethernet.payload())?;
+            let to_us = own.contains(&IpAddr::V4(arp.get_target_proto_addr()));
+            (arp.get_operation() == ArpOperations::Reply && to_us).then(|| {
+                (
+                    IpAddr::V4(arp.get_sender_proto_addr()),
+                    arp.get_sender_hw_addr().to_string(),
+                    Probe::Arp,
+                )
+            })
+        }
+        EtherTypes::Ipv6 => {
+            let ip = Ipv6Packet::new(ethernet.payload())?;
+            if ip.get_next_header() != IpNextHeaderProtocols::Icmpv6
+                || !own.contains(&IpAddr::V6(ip.get_destination()))
+            {
+                return None;
+            }
+            let icmp = Icmpv6Packet::new(ip.payload())?;
+            let (host, probe) = match icmp.get_icmpv6_type() {
+                // flags and reserved bits, then the address being advertised
+                Icmpv6Types::NeighborAdvert => {
+                    let target: [u8; 16] = icmp.payload().get(4..20)?.try_into().ok()?;
+                    (Ipv6Addr::from(target), Probe::NeighborSolicitation)
+                }
+                Icmpv6Types::EchoReply => (ip.get_source(), Probe::Echo),
+                _ => return None,
+            };
+            Some((IpAddr::V6(host), ethernet.get_source().to_string(), probe))
+        }
+        _ => None,
+    }
+}
+
+fn is_link_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use pnet::{datalink::NetworkInterface, util::MacAddr};
+
+    use super::*;
+
+    const OWN_V4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+    const HOST_V4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 20);
+    const OWN_V6: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const HOST_V6: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    const HOST_MAC: &str = "02:00:00:00:00:02";
+
+    /// The host answering, as the interface its frames are built for.
+    fn host() -> NetworkInterface {
+        NetworkInterface {
+            name: "eth0".into(),
+            description: String::new(),
+            index: 1,
+            mac: Some(MacAddr::new(2, 0, 0, 0, 0, 2)),
+            ips: Vec::new(),
+            flags: 0,
+        }
+    }
+
+    fn own() -> HashSet<IpAddr> {
+        HashSet::from([OWN_V4.into(), OWN_V6.into()])
+    }
+
+    fn arp(reply: bool, target_ip: Ipv4Addr) -> Vec<u8> {
+        FrameSpec {
+            src_mac: None,
+            dst_mac: None,
+            network: NetworkSpec::Arp {
+                reply,
+                sender_ip: HOST_V4,
+                target_ip,
+                target_mac: None,
+            },
+        }
+        .build(&host())
+        .unwrap()
+    }
+
+    fn icmpv6(dst: Ipv6Addr, icmp_type: u8, payload: Vec<u8>) -> Vec<u8> {
+        FrameSpec {
+            src_mac: None,
+            dst_mac: None,
+            network: NetworkSpec::Ip {
+                src: HOST_V6.into(),
+                dst: dst.into(),
+                ttl: Some(NDP_HOP_LIMIT),
+                transport: TransportSpec::Icmp {
+                    icmp_type: Some(icmp_type),
+                    code: 0,
+                    identifier: 0,
+                    sequence: 0,
+                    payload,
+                },
+            },
+        }
+        .build(&host())
+        .unwrap()
+    }
+
+    fn network(ip: Ipv4Addr, prefix: u8) -> Ipv4Network {
+        Ipv4Network::new(ip, prefix).unwrap()
+    }
+
+    #[test]
+    fn sweeps_a_subnet_without_its_network_and_broadcast_addresses() {
+        let swept: Vec<Ipv4Addr> = sweep(network(OWN_V4, 24)).collect();
+        assert_eq!(swept.len(), 254);
+        assert_eq!(swept.first(), Some(&Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(swept.last(), Some(&Ipv4Addr::new(192, 168, 1, 254)));
+    }
+
+    #[test]
+    fn sweeps_both_addresses_of_a_point_to_point_link() {
+        let swept: Vec<Ipv4Addr> = sweep(network(OWN_V4, 31)).collect();
+        assert_eq!(
+            swept,
+            [
+                Ipv4Addr::new(192, 168, 1, 10),
+                Ipv4Addr::new(192, 168, 1, 11)
+            ]
+        );
+        assert_eq!(sweep(network(OWN_V4, 32)).collect::<Vec<_>>(), [OWN_V4]);
+    }
+
+    #[test]
+    fn sweeps_only_the_block_around_the_address_of_a_large_subnet() {
+        let swept: Vec<Ipv4Addr> = sweep(network(Ipv4Addr::new(10, 1, 6, 7), 8)).collect();
+        // 10.1.4.0/22, all of whose addresses are hosts of the /8
+        assert_eq!(swept.len(), 1024);
+        assert_eq!(swept.first(), Some(&Ipv4Addr::new(10, 1, 4, 0)));
+        assert_eq!(swept.last(), Some(&Ipv4Addr::new(10, 1, 7, 255)));
+
+        // the /8's own network address is still left out
+        let swept: Vec<Ipv4Addr> = sweep(network(Ipv4Addr::new(10, 0, 0, 7), 8)).collect();
+        assert_eq!(swept.len(), 1023);
+        assert_eq!(swept.first(), Some(&Ipv4Addr::new(10, 0, 0, 1)));
+    }
+
+    #[test]
+    fn reads_an_arp_reply_to_us() {
+        assert_eq!(
+            parse_answer(&arp(true, OWN_V4), &own()),
+            Some((HOST_V4.into(), HOST_MAC.to_string(), Probe::Arp))
+        );
+    }
+
+    #[test]
+    fn ignores_arp_requests_and_replies_to_others() {
+        assert_eq!(parse_answer(&arp(false, OWN_V4), &own()), None);
+        assert_eq!(
+            parse_answer(&arp(true, Ipv4Addr::new(192, 168, 1, 30)), &own()),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_a_neighbour_advertisement_for_its_target() {
+        // the rest of the header, where the flags are, is left zero
+        let frame = icmpv6(
+            OWN_V6,
+            Icmpv6Types::NeighborAdvert.0,
+            HOST_V6.octets().to_vec(),
+        );
+        assert_eq!(
+            parse_answer(&frame, &own()),
+            Some((
+                HOST_V6.into(),
+                HOST_MAC.to_string(),
+                Probe::NeighborSolicitation
+            ))
+        );
+
+        // cut short before the whole address
+        let frame = icmpv6(OWN_V6, Icmpv6Types::NeighborAdvert.0, vec![0xfe, 0x80]);
+        assert_eq!(parse_answer(&frame, &own()), None);
+    }
+
+    #[test]
+    fn reads_an_echo_reply_to_us() {
+        let frame = icmpv6(OWN_V6, Icmpv6Types::EchoReply.0, Vec::new());
+        assert_eq!(
+            parse_answer(&frame, &own()),
+            Some((HOST_V6.into(), HOST_MAC.to_string(), Probe::Echo))
+        );
+
+        let elsewhere = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 3);
+        let frame = icmpv6(elsewhere, Icmpv6Types::EchoReply.0, Vec::new());
+        assert_eq!(parse_answer(&frame, &own()), None);
+        let frame = icmpv6(OWN_V6, Icmpv6Types::EchoRequest.0, Vec::new());
+        assert_eq!(parse_answer(&frame, &own()), None);
+    }
+}
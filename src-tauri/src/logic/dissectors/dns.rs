@@ -0,0 +1,237 @@
+use super::{be16, Dissection, Dissector};
+use crate::logic::tree::ProtocolNode;
+
+const HEADER_LEN: usize = 12;
+/// Compression pointers may chain; more than this many is a loop.
+const MAX_POINTERS: usize = 16;
+
+pub struct Dns;
+
+impl Dissector for Dns {
+    fn name(&self) -> &'static str {
+        "DNS"
+    }
+
+    fn dissect(&self, payload: &[u8], offset: usize) -> Option<Dissection> {
+        let id = be16(payload, 0)?;
+        let flags = be16(payload, 2)?;
+        let counts = [
+            be16(payload, 4)?,
+            be16(payload, 6)?,
+            be16(payload, 8)?,
+            be16(payload, 10)?,
+        ];
+        let response = flags & 0x8000 != 0;
+        let opcode = (flags >> 11) & 0xf;
+        let rcode = flags & 0xf;
+        let question = (counts[0] > 0)
+            .then(|| name_at(payload, HEADER_LEN))
+            .flatten()
+            .and_then(|(name, end)| Some((name, end, be16(payload, end)?)));
+
+        let mut layer = ProtocolNode::layer(
+            "dns",
+            format!(
+                "Domain Name System ({})",
+                if response { "response" } else { "query" }
+            ),
+            offset,
+            payload.len(),
+        );
+        layer.field("dns.id", "Transaction ID", format!("0x{:04x}", id), 0, 2);
+        let flags_node = layer.field("dns.flags", "Flags", format!("0x{:04x}", flags), 2, 2);
+        flags_node.field("dns.flags.response", "Response", response, 0, 1);
+        flags_node.field("dns.flags.opcode", "Opcode", opcode, 0, 1);
+        flags_node.field("dns.flags.rcode", "Reply code", rcode_name(rcode), 1, 1);
+        let labels = ["Questions", "Answer RRs", "Authority RRs", "Additional RRs"];
+        let names = [
+            "dns.count.queries",
+            "dns.count.answers",
+            "dns.count.auth_rr",
+            "dns.count.add_rr",
+        ];
+        for (i, count) in counts.iter().enumerate() {
+            layer.field(names[i], labels[i], count, 4 + i * 2, 2);
+        }
+        if let Some((name, end, qtype)) = &question {
+            layer.field("dns.qry.name", "Name", name, HEADER_LEN, end - HEADER_LEN);
+            layer.field("dns.qry.type", "Type", type_name(*qtype), *end, 2);
+        }
+
+        let mut info = format!(
+            "Standard query{} 0x{:04x}",
+            if response { " response" } else { "" },
+            id
+        );
+        if opcode != 0 {
+            info = format!("Opcode {} 0x{:04x}", opcode, id);
+        }
+        if response && rcode != 0 {
+            info.push(' ');
+            info.push_str(&rcode_name(rcode));
+        }
+        if let Some((name, _, qtype)) = question {
+            info.push_str(&format!(" {} {}", type_name(qtype), name));
+        }
+        Some(Dissection { info, layer })
+    }
+}
+
+/// The domain name starting at `at`, and where it ends in the message.
+fn name_at(message: &[u8], mut at: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = *message.get(at)? as usize;
+        match len {
+            0 => break,
+            _ if len & 0xc0 == 0xc0 => {
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return None;
+                }
+                end.get_or_insert(at + 2);
+                at = (be16(message, at)? & 0x3fff) as usize;
+            }
+            _ => {
+                let label = message.get(at + 1..at + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                at += 1 + len;
+            }
+        }
+    }
+    let name = if labels.is_empty() {
+        "<Root>".to_string()
+    } else {
+        labels.join(".")
+    };
+    Some((name, end.unwrap_or(at + 1)))
+}
+
+fn type_name(qtype: u16) -> String {
+    match qtype {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        15 => "MX",
+        16 => "TXT",
+        28 => "AAAA",
+        33 => "SRV",
+        41 => "OPT",
+        65 => "HTTPS",
+        255 => "ANY",
+        _ => return format!("TYPE{}", qtype),
+    }
+    .to_string()
+}
+
+fn rcode_name(rcode: u16) -> String {
+    match rcode {
+        0 => "No error",
+        1 => "Format error",
+        2 => "Server failure",
+        3 => "No such name",
+        4 => "Not implemented",
+        5 => "Refused",
+        _ => return rcode.to_string(),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A header with one question and the given flags, then `question`.
+    fn message(flags: u16, question: &[u8]) -> Vec<u8> {
+        let mut message = vec![0x1a, 0x2b];
+        message.extend_from_slice(&flags.to_be_bytes());
+        message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+        message.extend_from_slice(question);
+        message
+    }
+
+    const EXAMPLE_COM: &[u8] = b"\x07example\x03com\x00";
+
+    fn question(name: &[u8], qtype: u16) -> Vec<u8> {
+        let mut question = name.to_vec();
+        question.extend_from_slice(&qtype.to_be_bytes());
+        question.extend_from_slice(&[0, 1]);
+        question
+    }
+
+    fn field<'a>(dissection: &'a Dissection, name: &str) -> Option<&'a ProtocolNode> {
+        dissection
+            .layer
+            .children
+            .iter()
+            .find(|node| node.name == name)
+    }
+
+    #[test]
+    fn dissects_a_query() {
+        let dissection = Dns
+            .dissect(&message(0x0100, &question(EXAMPLE_COM, 1)), 42)
+            .unwrap();
+        assert_eq!(dissection.info, "Standard query 0x1a2b A example.com");
+        let name = field(&dissection, "dns.qry.name").unwrap();
+        assert_eq!(name.value, "example.com");
+        assert_eq!((name.offset, name.length), (42 + 12, EXAMPLE_COM.len()));
+        assert_eq!(field(&dissection, "dns.qry.type").unwrap().offset, 42 + 25);
+    }
+
+    #[test]
+    fn dissects_an_error_response() {
+        let dissection = Dns
+            .dissect(&message(0x8183, &question(EXAMPLE_COM, 28)), 0)
+            .unwrap();
+        assert_eq!(
+            dissection.info,
+            "Standard query response 0x1a2b No such name AAAA example.com"
+        );
+    }
+
+    #[test]
+    fn follows_compression_pointers() {
+        // www, then a pointer back to example.com
+        let mut message = message(0x0100, EXAMPLE_COM);
+        let at = message.len();
+        message.extend_from_slice(b"\x03www\xc0\x0c");
+        assert_eq!(
+            name_at(&message, at),
+            Some(("www.example.com".to_string(), message.len()))
+        );
+    }
+
+    #[test]
+    fn gives_up_on_a_pointer_loop() {
+        // a name pointing at itself
+        let dissection = Dns
+            .dissect(&message(0x0100, &question(&[0xc0, 12], 1)), 0)
+            .unwrap();
+        assert!(field(&dissection, "dns.qry.name").is_none());
+        assert_eq!(dissection.info, "Standard query 0x1a2b");
+
+        // two names pointing at each other
+        let mut message = message(0x0100, &[0xc0, 14, 0xc0, 12]);
+        message.extend_from_slice(&[0, 1, 0, 1]);
+        assert_eq!(name_at(&message, 12), None);
+    }
+
+    #[test]
+    fn gives_up_on_a_name_past_the_end() {
+        assert_eq!(name_at(&message(0x0100, &[0xc0, 0xff]), 12), None);
+        assert_eq!(name_at(&message(0x0100, b"\x07exam"), 12), None);
+        // no question type after the name
+        let dissection = Dns.dissect(&message(0x0100, EXAMPLE_COM), 0).unwrap();
+        assert!(field(&dissection, "dns.qry.name").is_none());
+    }
+
+    #[test]
+    fn rejects_a_truncated_header() {
+        assert!(Dns.dissect(&[0x1a, 0x2b, 0x01, 0x00, 0, 1], 0).is_none());
+    }
+}
@@ -0,0 +1,146 @@
+use super::{Dissection, Dissector};
+use crate::logic::tree::ProtocolNode;
+
+const METHODS: [&str; 9] = [
+    "GET", "POST", "HEAD", "PUT", "DELETE", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+];
+
+/// HTTP/1.x. Only segments starting a request or response are recognised; the rest of a
+/// message body stays plain TCP.
+pub struct Http;
+
+impl Dissector for Http {
+    fn name(&self) -> &'static str {
+        "HTTP"
+    }
+
+    fn looks_like(&self, payload: &[u8]) -> bool {
+        first_line(payload).is_some_and(|line| is_request(line) || is_response(line))
+    }
+
+    fn dissect(&self, payload: &[u8], offset: usize) -> Option<Dissection> {
+        let line = first_line(payload)?;
+        let (name, label) = if is_request(line) {
+            ("http.request.line", "Request")
+        } else if is_response(line) {
+            ("http.response.line", "Response")
+        } else {
+            return None;
+        };
+        let mut layer = ProtocolNode::layer(
+            "http",
+            "Hypertext Transfer Protocol".to_string(),
+            offset,
+            payload.len(),
+        );
+        layer.field(name, label, line, 0, line.len());
+
+        let mut at = line.len() + 2;
+        while let Some(header) = first_line(&payload[at..]) {
+            if header.is_empty() {
+                at += 2;
+                break;
+            }
+            match header.split_once(':') {
+                Some((field, value)) => layer.field(
+                    &format!("http.{}", field.trim().to_ascii_lowercase()),
+                    field.trim(),
+                    value.trim(),
+                    at,
+                    header.len(),
+                ),
+                None => layer.field("http.header", "Header", header, at, header.len()),
+            };
+            at += header.len() + 2;
+        }
+        if at < payload.len() {
+            let body = payload.len() - at;
+            layer.field(
+                "http.file_data",
+                "Body",
+                format!("{} bytes", body),
+                at,
+                body,
+            );
+        }
+        Some(Dissection {
+            info: line.to_string(),
+            layer,
+        })
+    }
+}
+
+/// The text before the first CRLF, if it's printable.
+fn first_line(payload: &[u8]) -> Option<&str> {
+    let end = payload.windows(2).position(|pair| pair == b"\r\n")?;
+    let line = std::str::from_utf8(&payload[..end]).ok()?;
+    line.chars()
+        .all(|c| c.is_ascii_graphic() || c == ' ' || c == '\t')
+        .then_some(line)
+}
+
+fn is_request(line: &str) -> bool {
+    let method = line.split(' ').next().unwrap_or_default();
+    METHODS.contains(&method) && (line.ends_with(" HTTP/1.1") || line.ends_with(" HTTP/1.0"))
+}
+
+fn is_response(line: &str) -> bool {
+    line.starts_with("HTTP/1.") && line.len() >= 12
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dissects_a_request_with_headers_and_a_body() {
+        let request = b"POST /login HTTP/1.1\r\nHost: example.com\r\n\r\nuser=a";
+        assert!(Http.looks_like(request));
+        let dissection = Http.dissect(request, 54).unwrap();
+        assert_eq!(dissection.info, "POST /login HTTP/1.1");
+        let fields: Vec<(&str, &str, usize, usize)> = dissection
+            .layer
+            .children
+            .iter()
+            .map(|node| {
+                (
+                    node.name.as_str(),
+                    node.value.as_str(),
+                    node.offset,
+                    node.length,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("http.request.line", "POST /login HTTP/1.1", 54, 20),
+                ("http.host", "example.com", 54 + 22, 17),
+                ("http.file_data", "6 bytes", 54 + 43, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn dissects_a_response() {
+        let response = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+        assert!(Http.looks_like(response));
+        assert_eq!(
+            Http.dissect(response, 0).unwrap().info,
+            "HTTP/1.1 404 Not Found"
+        );
+    }
+
+    #[test]
+    fn ignores_what_isnt_a_request_or_response() {
+        for payload in [
+            &b"GET / HTTP/2\r\n"[..],
+            b"SSH-2.0-OpenSSH_9.6\r\n",
+            b"GET / HTTP/1.1",
+            b"\x16\x03\x01\x00\x05",
+        ] {
+            assert!(!Http.looks_like(payload));
+            assert!(Http.dissect(payload, 0).is_none());
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use super::{be16, text_or_hex, Dissection, Dissector};
+use crate::logic::tree::ProtocolNode;
+
+pub const ETHERTYPE: u16 = 0x88cc;
+
+/// Link Layer Discovery Protocol, which switches and phones announce themselves with.
+pub struct Lldp;
+
+impl Dissector for Lldp {
+    fn name(&self) -> &'static str {
+        "LLDP"
+    }
+
+    fn dissect(&self, payload: &[u8], offset: usize) -> Option<Dissection> {
+        let mut layer = ProtocolNode::layer(
+            "lldp",
+            "Link Layer Discovery Protocol".to_string(),
+            offset,
+            0,
+        );
+        let (mut chassis, mut port, mut system) = (None, None, None);
+        let mut at = 0;
+        // an LLDPDU cut short by the snaplen, or missing its end, still shows the TLVs before
+        while let Some(header) = be16(payload, at) {
+            let (tlv_type, length) = ((header >> 9) as u8, (header & 0x1ff) as usize);
+            let Some(value) = payload.get(at + 2..at + 2 + length) else {
+                break;
+            };
+            let (name, label) = tlv_name(tlv_type);
+            // chassis and port IDs start with a subtype, which says if they're a MAC address
+            let mac_subtype = if tlv_type == 1 { 4 } else { 3 };
+            let shown = match tlv_type {
+                0 => String::new(),
+                1 | 2 if value.len() == 7 && value[0] == mac_subtype => value[1..]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<Vec<_>>()
+                    .join(":"),
+                1 | 2 => text_or_hex(value.get(1..).unwrap_or_default()),
+                3 => be16(value, 0)
+                    .map_or_else(|| format!("{} bytes", length), |ttl| ttl.to_string()),
+                4..=6 => text_or_hex(value),
+                _ => format!("{} bytes", length),
+            };
+            match tlv_type {
+                1 => chassis = Some(shown.clone()),
+                2 => port = Some(shown.clone()),
+                5 => system = Some(shown.clone()),
+                _ => {}
+            }
+            let node = layer.field(name, label, &shown, at, 2 + length);
+            if shown.is_empty() {
+                node.display = label.to_string();
+            }
+            at += 2 + length;
+            if tlv_type == 0 {
+                break;
+            }
+        }
+        if layer.children.is_empty() {
+            return None;
+        }
+        layer.length = at;
+        let info = [("Chassis", chassis), ("Port", port), ("System", system)]
+            .into_iter()
+            .filter_map(|(label, value)| Some(format!("{} {}", label, value?)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(Dissection { info, layer })
+    }
+}
+
+fn tlv_name(tlv_type: u8) -> (&'static str, &'static str) {
+    match tlv_type {
+        0 => ("lldp.end", "End of LLDPDU"),
+        1 => ("lldp.chassis.id", "Chassis ID"),
+        2 => ("lldp.port.id", "Port ID"),
+        3 => ("lldp.time_to_live", "Time to live"),
+        4 => ("lldp.port.desc", "Port description"),
+        5 => ("lldp.tlv.system.name", "System name"),
+        6 => ("lldp.tlv.system.desc", "System description"),
+        7 => ("lldp.tlv.system.cap", "System capabilities"),
+        8 => ("lldp.mgn.addr", "Management address"),
+        127 => ("lldp.orgtlv", "Organization specific"),
+        _ => ("lldp.unknown_tlv", "Unknown TLV"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tlv_type: u8, value: &[u8]) -> Vec<u8> {
+        let header = (tlv_type as u16) << 9 | value.len() as u16;
+        let mut tlv = header.to_be_bytes().to_vec();
+        tlv.extend_from_slice(value);
+        tlv
+    }
+
+    /// Chassis MAC, port name, TTL and system name, without the end TLV.
+    fn tlvs() -> Vec<u8> {
+        let mut lldpdu = tlv(1, &[4, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        lldpdu.extend(tlv(2, b"\x05ge-0/0/1"));
+        lldpdu.extend(tlv(3, &[0, 120]));
+        lldpdu.extend(tlv(5, b"switch1"));
+        lldpdu
+    }
+
+    fn names(dissection: &Dissection) -> Vec<&str> {
+        dissection
+            .layer
+            .children
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn dissects_an_lldpdu() {
+        let mut lldpdu = tlvs();
+        lldpdu.extend(tlv(0, &[]));
+        let dissection = Lldp.dissect(&lldpdu, 14).unwrap();
+        assert_eq!(
+            dissection.info,
+            "Chassis 00:11:22:33:44:55, Port ge-0/0/1, System switch1"
+        );
+        assert_eq!(
+            names(&dissection),
+            [
+                "lldp.chassis.id",
+                "lldp.port.id",
+                "lldp.time_to_live",
+                "lldp.tlv.system.name",
+                "lldp.end"
+            ]
+        );
+        assert_eq!(dissection.layer.children[2].value, "120");
+        assert_eq!(dissection.layer.length, lldpdu.len());
+    }
+
+    #[test]
+    fn keeps_the_tlvs_before_a_truncation() {
+        let lldpdu = tlvs();
+        // cut short in the middle of the system name
+        let cut = &lldpdu[..lldpdu.len() - 3];
+        let dissection = Lldp.dissect(cut, 14).unwrap();
+        assert_eq!(dissection.info, "Chassis 00:11:22:33:44:55, Port ge-0/0/1");
+        assert_eq!(
+            names(&dissection),
+            ["lldp.chassis.id", "lldp.port.id", "lldp.time_to_live"]
+        );
+        let system_name = tlv(5, b"switch1");
+        assert_eq!(dissection.layer.length, lldpdu.len() - system_name.len());
+    }
+
+    #[test]
+    fn keeps_the_tlvs_of_an_lldpdu_without_an_end() {
+        let lldpdu = tlvs();
+        let dissection = Lldp.dissect(&lldpdu, 14).unwrap();
+        assert_eq!(names(&dissection).len(), 4);
+        assert_eq!(dissection.layer.length, lldpdu.len());
+    }
+
+    #[test]
+    fn rejects_an_lldpdu_with_no_whole_tlv() {
+        assert!(Lldp.dissect(&[], 14).is_none());
+        assert!(Lldp.dissect(&[0x02, 0x07, 0x04, 0x00], 14).is_none());
+    }
+}
@@ -0,0 +1,342 @@
+//! Application protocols, dissected from what TCP, UDP or the link layer carry. Each one is a
+//! [`Dissector`] in the [`Registry`], reached by port, by ethertype or by recognising its
+//! payload, so adding one doesn't touch the core dissection in `show_packets`.
+
+mod dns;
+mod http;
+mod lldp;
+mod tls;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::tree::ProtocolNode;
+use crate::errors::AppResult;
+
+/// What a dissector made of a payload.
+pub struct Dissection {
+    /// One line for the packet list, e.g. `Standard query 0x1a2b A example.com`.
+    pub info: String,
+    /// The protocol's layer, for the packet's tree. Bytes past it are shown as data.
+    pub layer: ProtocolNode,
+}
+
+pub trait Dissector: Send + Sync {
+    /// Shown as the packet's protocol, and what port mappings refer to it by.
+    fn name(&self) -> &'static str;
+
+    /// Whether `payload` looks like this protocol, for traffic on ports nothing is mapped to.
+    /// Dissectors that can't tell from the bytes alone leave this as is.
+    fn looks_like(&self, _payload: &[u8]) -> bool {
+        false
+    }
+
+    /// Dissects `payload`, found `offset` bytes into the frame. `None` if it isn't this
+    /// protocol after all, or is cut short.
+    fn dissect(&self, payload: &[u8], offset: usize) -> Option<Dissection>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transport::Tcp => "tcp",
+            Transport::Udp => "udp",
+        })
+    }
+}
+
+/// Traffic on `port` is dissected as `protocol`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PortMapping {
+    pub transport: Transport,
+    pub port: u16,
+    pub protocol: String,
+}
+
+/// Which dissector handles what. Dissectors are tried by port first, the lower of the two
+/// ports winning as it's usually the server's, then by heuristic in the order they were
+/// registered.
+#[derive(Default)]
+pub struct Registry {
+    by_name: HashMap<&'static str, Arc<dyn Dissector>>,
+    ports: HashMap<(Transport, u16), Arc<dyn Dissector>>,
+    ethertypes: HashMap<u16, Arc<dyn Dissector>>,
+    heuristics: Vec<Arc<dyn Dissector>>,
+}
+
+impl Registry {
+    /// The dissectors that ship with the app, on their well-known ports.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        let dns: Arc<dyn Dissector> = Arc::new(dns::Dns);
+        for port in [53, 5353] {
+            registry.register_port(Transport::Udp, port, dns.clone());
+        }
+        let http: Arc<dyn Dissector> = Arc::new(http::Http);
+        for port in [80, 8000, 8080] {
+            registry.register_port(Transport::Tcp, port, http.clone());
+        }
+        registry.register_heuristic(http);
+        let tls: Arc<dyn Dissector> = Arc::new(tls::Tls);
+        for port in [443, 465, 993, 995] {
+            registry.register_port(Transport::Tcp, port, tls.clone());
+        }
+        registry.register_heuristic(tls);
+        registry.register_ethertype(lldp::ETHERTYPE, Arc::new(lldp::Lldp));
+        registry
+    }
+
+    pub fn register_port(
+        &mut self,
+        transport: Transport,
+        port: u16,
+        dissector: Arc<dyn Dissector>,
+    ) {
+        let dissector = self.known(dissector);
+        self.ports.insert((transport, port), dissector);
+    }
+
+    pub fn register_ethertype(&mut self, ethertype: u16, dissector: Arc<dyn Dissector>) {
+        let dissector = self.known(dissector);
+        self.ethertypes.insert(ethertype, dissector);
+    }
+
+    pub fn register_heuristic(&mut self, dissector: Arc<dyn Dissector>) {
+        let dissector = self.known(dissector);
+        self.heuristics.push(dissector);
+    }
+
+    fn known(&mut self, dissector: Arc<dyn Dissector>) -> Arc<dyn Dissector> {
+        self.by_name
+            .entry(dissector.name())
+            .or_insert(dissector)
+            .clone()
+    }
+
+    /// Dissects traffic on `port` as `protocol` from now on, or by heuristic only when
+    /// `protocol` is `None`.
+    pub fn map_port(
+        &mut self,
+        transport: Transport,
+        port: u16,
+        protocol: Option<&str>,
+    ) -> AppResult<()> {
+        match protocol {
+            Some(protocol) => {
+                let dissector = self
+                    .by_name
+                    .values()
+                    .find(|dissector| dissector.name().eq_ignore_ascii_case(protocol))
+                    .ok_or_else(|| anyhow::anyhow!("There is no {} dissector", protocol))?;
+                self.ports.insert((transport, port), dissector.clone());
+            }
+            None => {
+                self.ports.remove(&(transport, port));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn port_mappings(&self) -> Vec<PortMapping> {
+        let mut mappings: Vec<PortMapping> = self
+            .ports
+            .iter()
+            .map(|(&(transport, port), dissector)| PortMapping {
+                transport,
+                port,
+                protocol: dissector.name().to_string(),
+            })
+            .collect();
+        mappings.sort_by_key(|mapping| (mapping.transport as u8, mapping.port));
+        mappings
+    }
+
+    /// Every protocol that can be dissected, for mapping ports to.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.by_name.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Dissects what a TCP segment or UDP datagram between `ports` carries.
+    pub fn for_ports(
+        &self,
+        transport: Transport,
+        ports: (u16, u16),
+        payload: &[u8],
+        offset: usize,
+    ) -> Option<(&'static str, Dissection)> {
+        if payload.is_empty() {
+            return None;
+        }
+        let (low, high) = (ports.0.min(ports.1), ports.0.max(ports.1));
+        [low, high]
+            .iter()
+            .filter_map(|port| self.ports.get(&(transport, *port)))
+            .chain(
+                self.heuristics
+                    .iter()
+                    .filter(|dissector| dissector.looks_like(payload)),
+            )
+            .find_map(|dissector| Some((dissector.name(), dissector.dissect(payload, offset)?)))
+    }
+
+    pub fn for_ethertype(
+        &self,
+        ethertype: u16,
+        payload: &[u8],
+        offset: usize,
+    ) -> Option<(&'static str, Dissection)> {
+        let dissector = self.ethertypes.get(&ethertype)?;
+        Some((dissector.name(), dissector.dissect(payload, offset)?))
+    }
+}
+
+/// Dissection runs on capture threads, imports and re-dissection alike, all of which share
+/// the one registry so a port mapping applies everywhere at once.
+static REGISTRY: LazyLock<RwLock<Registry>> =
+    LazyLock::new(|| RwLock::new(Registry::with_builtins()));
+
+pub fn registry() -> RwLockReadGuard<'static, Registry> {
+    REGISTRY.read().unwrap()
+}
+
+pub fn registry_mut() -> RwLockWriteGuard<'static, Registry> {
+    REGISTRY.write().unwrap()
+}
+
+/// Printable ASCII as is, anything else as hex.
+fn text_or_hex(bytes: &[u8]) -> String {
+    if !bytes.is_empty()
+        && bytes
+            .iter()
+            .all(|byte| byte.is_ascii_graphic() || *byte == b' ')
+    {
+        String::from_utf8_lossy(bytes).into_owned()
+    } else {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+fn be16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Takes anything, to tell which dissector a registry picked.
+    struct Anything(&'static str);
+
+    impl Dissector for Anything {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn dissect(&self, _payload: &[u8], offset: usize) -> Option<Dissection> {
+            Some(Dissection {
+                info: String::new(),
+                layer: ProtocolNode::layer(self.0, String::new(), offset, 0),
+            })
+        }
+    }
+
+    const HTTP_REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    /// A handshake record whose version the TLS heuristic doesn't take.
+    const ODD_TLS: &[u8] = &[22, 2, 0, 0, 1, 1];
+
+    fn protocol(registry: &Registry, ports: (u16, u16), payload: &[u8]) -> Option<&'static str> {
+        registry
+            .for_ports(Transport::Tcp, ports, payload, 54)
+            .map(|(name, _)| name)
+    }
+
+    #[test]
+    fn prefers_the_lower_port() {
+        let mut registry = Registry::default();
+        registry.register_port(Transport::Tcp, 1000, Arc::new(Anything("low")));
+        registry.register_port(Transport::Tcp, 2000, Arc::new(Anything("high")));
+        assert_eq!(protocol(&registry, (2000, 1000), b"x"), Some("low"));
+        assert_eq!(protocol(&registry, (1000, 2000), b"x"), Some("low"));
+        assert_eq!(protocol(&registry, (3000, 2000), b"x"), Some("high"));
+        // ports are per transport
+        let udp = registry.for_ports(Transport::Udp, (1000, 2000), b"x", 54);
+        assert!(udp.is_none());
+    }
+
+    #[test]
+    fn falls_back_to_the_next_port_and_then_heuristics() {
+        let registry = Registry::with_builtins();
+        // the HTTP dissector on port 80 declines a TLS record, which 443 takes
+        let tls = [22, 3, 3, 0, 4, 1, 0, 0, 0];
+        assert_eq!(protocol(&registry, (443, 80), &tls), Some("TLS"));
+        // nothing on either port
+        assert_eq!(
+            protocol(&registry, (50000, 5001), HTTP_REQUEST),
+            Some("HTTP")
+        );
+        assert_eq!(protocol(&registry, (50000, 5001), &tls), Some("TLS"));
+        assert_eq!(protocol(&registry, (50000, 5001), b"\x00\x01junk"), None);
+        assert_eq!(protocol(&registry, (50000, 5001), b""), None);
+    }
+
+    #[test]
+    fn remaps_a_port() {
+        let mut registry = Registry::with_builtins();
+        assert_eq!(protocol(&registry, (50000, 8443), ODD_TLS), None);
+
+        registry
+            .map_port(Transport::Tcp, 8443, Some("tls"))
+            .unwrap();
+        assert_eq!(protocol(&registry, (50000, 8443), ODD_TLS), Some("TLS"));
+        assert!(registry.port_mappings().contains(&PortMapping {
+            transport: Transport::Tcp,
+            port: 8443,
+            protocol: "TLS".to_string(),
+        }));
+
+        registry.map_port(Transport::Tcp, 8443, None).unwrap();
+        assert_eq!(protocol(&registry, (50000, 8443), ODD_TLS), None);
+    }
+
+    #[test]
+    fn unmapping_a_port_leaves_it_to_heuristics() {
+        let mut registry = Registry::with_builtins();
+        registry.map_port(Transport::Tcp, 80, Some("TLS")).unwrap();
+        assert_eq!(protocol(&registry, (50000, 80), HTTP_REQUEST), Some("HTTP"));
+        registry.map_port(Transport::Tcp, 80, None).unwrap();
+        assert_eq!(protocol(&registry, (50000, 80), HTTP_REQUEST), Some("HTTP"));
+        assert_eq!(protocol(&registry, (50000, 80), ODD_TLS), None);
+    }
+
+    #[test]
+    fn refuses_to_map_an_unknown_protocol() {
+        let mut registry = Registry::with_builtins();
+        assert!(registry
+            .map_port(Transport::Tcp, 70, Some("Gopher"))
+            .is_err());
+        assert!(!registry.port_mappings().iter().any(|m| m.port == 70));
+    }
+
+    #[test]
+    fn dissects_by_ethertype() {
+        let registry = Registry::with_builtins();
+        let lldp = [0x02, 0x07, 0x04, 0, 0x11, 0x22, 0x33, 0x44, 0x55, 0, 0];
+        let (name, _) = registry.for_ethertype(0x88cc, &lldp, 14).unwrap();
+        assert_eq!(name, "LLDP");
+        assert!(registry.for_ethertype(0x88b5, &lldp, 14).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+use super::{be16, Dissection, Dissector};
+use crate::logic::tree::ProtocolNode;
+
+const RECORD_HEADER_LEN: usize = 5;
+
+/// TLS records, as far as they can be read without the keys: their types and, for
+/// handshakes in the clear, the message type.
+pub struct Tls;
+
+impl Dissector for Tls {
+    fn name(&self) -> &'static str {
+        "TLS"
+    }
+
+    fn looks_like(&self, payload: &[u8]) -> bool {
+        payload.len() >= RECORD_HEADER_LEN
+            && content_type_name(payload[0]).is_some()
+            && payload[1] == 3
+            && payload[2] <= 4
+    }
+
+    fn dissect(&self, payload: &[u8], offset: usize) -> Option<Dissection> {
+        let mut layer =
+            ProtocolNode::layer("tls", "Transport Layer Security".to_string(), offset, 0);
+        let mut messages = Vec::new();
+        let mut at = 0;
+        let mut encrypted = false;
+        // a segment may end partway into a record, which is still worth showing
+        while payload.len() - at >= RECORD_HEADER_LEN {
+            let content_type = payload[at];
+            let Some(content) = content_type_name(content_type) else {
+                break;
+            };
+            let version = be16(payload, at + 1)?;
+            let length = be16(payload, at + 3)? as usize;
+            let shown = length.min(payload.len() - at - RECORD_HEADER_LEN);
+            // handshakes after a cipher change are encrypted, so their first byte means nothing
+            let message = match (content_type, payload.get(at + RECORD_HEADER_LEN)) {
+                (22, _) if encrypted => "Encrypted Handshake Message",
+                (22, Some(&handshake)) => handshake_name(handshake).unwrap_or(content),
+                _ => content,
+            };
+            encrypted |= content_type == 20;
+            let record = layer.field(
+                "tls.record",
+                "Record",
+                message,
+                at,
+                RECORD_HEADER_LEN + shown,
+            );
+            record.field("tls.record.content_type", "Content type", content, 0, 1);
+            record.field("tls.record.version", "Version", version_name(version), 1, 2);
+            record.field("tls.record.length", "Length", length, 3, 2);
+            messages.push(message);
+            at += RECORD_HEADER_LEN + shown;
+        }
+        if messages.is_empty() {
+            return None;
+        }
+        layer.length = at;
+        messages.dedup();
+        Some(Dissection {
+            info: messages.join(", "),
+            layer,
+        })
+    }
+}
+
+fn content_type_name(content_type: u8) -> Option<&'static str> {
+    Some(match content_type {
+        20 => "Change Cipher Spec",
+        21 => "Alert",
+        22 => "Handshake",
+        23 => "Application Data",
+        24 => "Heartbeat",
+        _ => return None,
+    })
+}
+
+fn handshake_name(handshake: u8) -> Option<&'static str> {
+    Some(match handshake {
+        1 => "Client Hello",
+        2 => "Server Hello",
+        4 => "New Session Ticket",
+        8 => "Encrypted Extensions",
+        11 => "Certificate",
+        12 => "Server Key Exchange",
+        13 => "Certificate Request",
+        14 => "Server Hello Done",
+        15 => "Certificate Verify",
+        16 => "Client Key Exchange",
+        20 => "Finished",
+        _ => return None,
+    })
+}
+
+fn version_name(version: u16) -> String {
+    match version {
+        0x0300 => "SSL 3.0".to_string(),
+        0x0301 => "TLS 1.0".to_string(),
+        0x0302 => "TLS 1.1".to_string(),
+        0x0303 => "TLS 1.2".to_string(),
+        0x0304 => "TLS 1.3".to_string(),
+        _ => format!("0x{:04x}", version),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(content_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut record = vec![content_type, 3, 3];
+        record.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        record.extend_from_slice(body);
+        record
+    }
+
+    #[test]
+    fn dissects_records_in_the_clear_and_after_a_cipher_change() {
+        let mut payload = record(22, &[2, 0, 0, 0]);
+        payload.extend(record(20, &[1]));
+        payload.extend(record(22, &[0x8f, 0x11, 0x22]));
+        assert!(Tls.looks_like(&payload));
+        let dissection = Tls.dissect(&payload, 54).unwrap();
+        assert_eq!(
+            dissection.info,
+            "Server Hello, Change Cipher Spec, Encrypted Handshake Message"
+        );
+        let records: Vec<(usize, usize)> = dissection
+            .layer
+            .children
+            .iter()
+            .map(|node| (node.offset, node.length))
+            .collect();
+        assert_eq!(records, [(54, 9), (63, 6), (69, 8)]);
+        assert_eq!(dissection.layer.length, payload.len());
+    }
+
+    #[test]
+    fn shows_a_record_cut_short_by_the_segment() {
+        let mut payload = record(23, &[0; 100]);
+        payload.truncate(40);
+        let dissection = Tls.dissect(&payload, 0).unwrap();
+        assert_eq!(dissection.info, "Application Data");
+        let record = &dissection.layer.children[0];
+        assert_eq!(record.length, 40);
+        assert_eq!(record.children[2].value, "100");
+    }
+
+    #[test]
+    fn ignores_what_isnt_tls() {
+        assert!(!Tls.looks_like(b"GET / HTTP/1.1\r\n"));
+        assert!(!Tls.looks_like(&[22, 2, 0, 0, 1]));
+        assert!(Tls.dissect(b"GET / HTTP/1.1\r\n", 0).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use super::pcap::{CaptureRecord, LINKTYPE_ETHERNET};
+
+/// A frame exactly as it came off the wire or out of a capture file, possibly cut short to a
+/// snaplen. Everything shown for a packet is dissected from these bytes.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct RawFrame {
+    pub interface: String,
+    /// How `data` is framed, as a pcap `LINKTYPE_*`. Exports from before this was recorded
+    /// only ever held Ethernet frames.
+    #[serde(default = "ethernet")]
+    pub link_type: u32,
+    pub ts_sec: i64,
+    pub ts_nsec: u32,
+    /// Length of the frame on the wire, which is larger than `data` when it was truncated.
+    pub orig_len: u32,
+    #[serde(with = "hex_bytes")]
+    pub data: Vec<u8>,
+}
+
+impl RawFrame {
+    pub fn new(interface: &str, data: &[u8], ts_sec: i64, ts_nsec: u32) -> Self {
+        Self {
+            interface: interface.to_string(),
+            link_type: LINKTYPE_ETHERNET,
+            ts_sec,
+            ts_nsec,
+            orig_len: data.len() as u32,
+            data: data.to_vec(),
+        }
+    }
+
+    /// A frame read from a live interface, with the time the kernel received it.
+    pub fn captured_at(
+        interface: &str,
+        data: &[u8],
+        snaplen: Option<usize>,
+        ts_sec: i64,
+        ts_nsec: u32,
+    ) -> Self {
+        let mut frame = Self::new(interface, data, ts_sec, ts_nsec);
+        if let Some(snaplen) = snaplen {
+            frame.data.truncate(snaplen);
+        }
+        frame
+    }
+
+    /// Stamps a frame that was just read from a live interface, for sources that don't say
+    /// when it arrived.
+    pub fn captured_now(interface: &str, data: &[u8], snaplen: Option<usize>) -> Self {
+        let now = Local::now();
+        Self::captured_at(
+            interface,
+            data,
+            snaplen,
+            now.timestamp(),
+            now.timestamp_subsec_nanos(),
+        )
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        (self.data.len() as u32) < self.orig_len
+    }
+
+    pub fn timestamp(&self) -> f64 {
+        self.ts_sec as f64 + self.ts_nsec as f64 / 1e9
+    }
+
+    /// Seconds from an earlier capture time to this frame, worked out before converting to
+    /// floating point so nanoseconds survive.
+    pub fn seconds_since(&self, (ts_sec, ts_nsec): (i64, u32)) -> f64 {
+        (self.ts_sec - ts_sec) as f64 + (self.ts_nsec as f64 - ts_nsec as f64) / 1e9
+    }
+
+    pub fn to_record(&self) -> CaptureRecord {
+        CaptureRecord {
+            link_type: self.link_type,
+            interface: Some(self.interface.clone()),
+            ts_sec: self.ts_sec,
+            ts_nsec: self.ts_nsec,
+            orig_len: self.orig_len,
+            data: self.data.clone(),
+        }
+    }
+}
+
+fn ethernet() -> u32 {
+    LINKTYPE_ETHERNET
+}
+
+/// Frame bytes are kept as a hex string in JSON so exports stay readable and compact.
+pub(crate) mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if !hex.is_ascii() || hex.len() % 2 != 0 {
+            return Err(D::Error::custom("frame data is not a hex string"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use pnet::datalink::{self, NetworkInterface};
+use serde::{Deserialize, Serialize};
+
+use super::{link, source::LiveSource};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InterfaceAddress {
+    pub ip: IpAddr,
+    pub prefix: u8,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InterfaceFlags {
+    pub up: bool,
+    /// The link has a carrier, e.g. a cable is plugged in.
+    pub running: bool,
+    pub promiscuous: bool,
+    pub loopback: bool,
+    pub point_to_point: bool,
+}
+
+/// Totals since the interface came up, as the kernel counts them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+}
+
+/// Traffic since the interfaces were last listed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceRates {
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub description: String,
+    pub index: u32,
+    pub mac: Option<String>,
+    pub addresses: Vec<InterfaceAddress>,
+    pub mtu: Option<u32>,
+    pub flags: InterfaceFlags,
+    /// The pcap `LINKTYPE_*` frames captured here are stored with.
+    pub link_type: u32,
+    pub link_type_name: String,
+    /// Only available where the kernel exposes them, i.e. on Linux.
+    pub counters: Option<InterfaceCounters>,
+    /// Unset the first time an interface is listed, and where there are no counters.
+    pub rates: Option<InterfaceRates>,
+}
+
+impl InterfaceInfo {
+    pub fn new(interface: &NetworkInterface) -> Self {
+        let link_type = LiveSource::link_type(interface);
+        Self {
+            name: interface.name.clone(),
+            description: interface.description.clone(),
+            index: interface.index,
+            mac: interface.mac.map(|mac| mac.to_string()),
+            addresses: interface
+                .ips
+                .iter()
+                .map(|network| InterfaceAddress {
+                    ip: network.ip(),
+                    prefix: network.prefix(),
+                })
+                .collect(),
+            mtu: read_attribute(&interface.name, "mtu").map(|mtu| mtu as u32),
+            flags: InterfaceFlags {
+                up: interface.is_up(),
+                running: interface.is_running(),
+                promiscuous: is_promiscuous(interface),
+                loopback: interface.is_loopback(),
+                point_to_point: interface.is_point_to_point(),
+            },
+            link_type,
+            link_type_name: link::name(link_type).to_string(),
+            counters: counters(&interface.name),
+            rates: None,
+        }
+    }
+}
+
+/// The counters each interface had when the interfaces were last listed, to tell how busy
+/// they are now rather than since boot.
+#[derive(Default)]
+pub struct InterfaceMonitor {
+    readings: Mutex<HashMap<String, (Instant, InterfaceCounters)>>,
+}
+
+impl InterfaceMonitor {
+    /// Every interface of the host, in the order the OS lists them, with its rates since the
+    /// previous call.
+    pub fn list(&self) -> Vec<InterfaceInfo> {
+        let mut interfaces: Vec<InterfaceInfo> = datalink::interfaces()
+            .iter()
+            .map(InterfaceInfo::new)
+            .collect();
+        let now = Instant::now();
+        let mut readings = self.readings.lock().unwrap();
+        for interface in interfaces.iter_mut() {
+            let Some(counters) = &interface.counters else {
+                continue;
+            };
+            if let Some((at, before)) =
+                readings.insert(interface.name.clone(), (now, counters.clone()))
+            {
+                interface.rates = rates(&before, counters, now - at);
+            }
+        }
+        readings.retain(|name, _| interfaces.iter().any(|interface| &interface.name == name));
+        interfaces
+    }
+}
+
+/// Bytes per second between two readings. Counters that went back, e.g. because the
+/// interface was recreated, count as no traffic.
+fn rates(
+    before: &InterfaceCounters,
+    after: &InterfaceCounters,
+    elapsed: Duration,
+) -> Option<InterfaceRates> {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return None;
+    }
+    Some(InterfaceRates {
+        rx_bytes_per_sec: after.rx_bytes.saturating_sub(before.rx_bytes) as f64 / secs,
+        tx_bytes_per_sec: after.tx_bytes.saturating_sub(before.tx_bytes) as f64 / secs,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn is_promiscuous(interface: &NetworkInterface) -> bool {
+    interface.flags & libc::IFF_PROMISC as u32 != 0
+}
+
+/// pnet doesn't say elsewhere, and not every platform's libc has the flag.
+#[cfg(not(target_os = "linux"))]
+fn is_promiscuous(_interface: &NetworkInterface) -> bool {
+    false
+}
+
+fn counters(name: &str) -> Option<InterfaceCounters> {
+    let counter = |stat: &str| read_attribute(name, &format!("statistics/{}", stat));
+    Some(InterfaceCounters {
+        rx_bytes: counter("rx_bytes")?,
+        rx_packets: counter("rx_packets")?,
+        tx_bytes: counter("tx_bytes")?,
+        tx_packets: counter("tx_packets")?,
+    })
+}
+
+/// Reads a numeric attribute of the interface from sysfs, which only Linux has.
+fn read_attribute(name: &str, attribute: &str) -> Option<u64> {
+    fs::read_to_string(format!("/sys/class/net/{}/{}", name, attribute))
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters(rx_bytes: u64, tx_bytes: u64) -> InterfaceCounters {
+        InterfaceCounters {
+            rx_bytes,
+            tx_bytes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rates_are_the_change_per_second() {
+        let rates = rates(
+            &counters(1_000, 500),
+            &counters(5_000, 1_500),
+            Duration::from_secs(2),
+        );
+        assert_eq!(
+            rates,
+            Some(InterfaceRates {
+                rx_bytes_per_sec: 2_000.0,
+                tx_bytes_per_sec: 500.0,
+            })
+        );
+    }
+
+    #[test]
+    fn counters_that_went_back_are_no_traffic() {
+        let rates = rates(
+            &counters(5_000, 5_000),
+            &counters(10, 20),
+            Duration::from_secs(1),
+        );
+        assert_eq!(rates, Some(InterfaceRates::default()));
+    }
+
+    #[test]
+    fn readings_at_the_same_instant_have_no_rates() {
+        assert_eq!(
+            rates(&counters(0, 0), &counters(10, 10), Duration::ZERO),
+            None
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use pnet::{
+    packet::ethernet::{EtherType, EtherTypes, EthernetPacket},
+    util::MacAddr,
+};
+use serde::{Deserialize, Serialize};
+
+use super::pcap::{
+    LINKTYPE_ETHERNET, LINKTYPE_IPV4, LINKTYPE_IPV6, LINKTYPE_LINUX_SLL, LINKTYPE_LINUX_SLL2,
+    LINKTYPE_LOOP, LINKTYPE_NULL, LINKTYPE_RAW,
+};
+
+const SLL_HEADER_LEN: usize = 16;
+/// Live captures on "any" and on non-Ethernet devices are framed with this header.
+pub const SLL2_HEADER_LEN: usize = 20;
+/// Loopback and null frames start with the address family in the capturing host's byte order.
+const NULL_HEADER_LEN: usize = 4;
+/// A VLAN tag is its TPID, which takes the place of the ethertype, and the tag control info.
+pub const VLAN_TAG_LEN: usize = 4;
+/// 802.1Q customer tags, 802.1ad service tags that carry them in QinQ, and the pre-standard
+/// QinQ tag some switches still send.
+const VLAN_TPIDS: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+
+/// The link-layer part of a frame, whatever kind of link it was captured on.
+pub struct LinkHeader<'a> {
+    pub src: Option<String>,
+    pub dst: Option<String>,
+    /// What the payload is, past any VLAN tags.
+    pub ethertype: EtherType,
+    /// Outermost first: a QinQ frame has the service tag, then the customer tag.
+    pub vlans: Vec<VlanTag>,
+    pub payload: &'a [u8],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VlanTag {
+    /// 0x8100 for an 802.1Q tag, 0x88a8 for an 802.1ad service tag.
+    pub tpid: u16,
+    pub id: u16,
+    /// Priority code point, 0 to 7.
+    pub pcp: u8,
+    /// Drop eligible indicator.
+    pub dei: bool,
+}
+
+impl VlanTag {
+    pub fn new(tpid: u16, tci: u16) -> Self {
+        Self {
+            tpid,
+            id: tci & 0x0fff,
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 != 0,
+        }
+    }
+
+    pub fn is_tpid(ethertype: u16) -> bool {
+        VLAN_TPIDS.contains(&ethertype)
+    }
+}
+
+pub fn is_supported(link_type: u32) -> bool {
+    matches!(
+        link_type,
+        LINKTYPE_ETHERNET
+            | LINKTYPE_LINUX_SLL
+            | LINKTYPE_LINUX_SLL2
+            | LINKTYPE_RAW
+            | LINKTYPE_IPV4
+            | LINKTYPE_IPV6
+            | LINKTYPE_NULL
+            | LINKTYPE_LOOP
+    )
+}
+
+/// A short name for a link type, as shown next to an interface.
+pub fn name(link_type: u32) -> &'static str {
+    match link_type {
+        LINKTYPE_ETHERNET => "Ethernet",
+        LINKTYPE_LINUX_SLL => "Linux cooked",
+        LINKTYPE_LINUX_SLL2 => "Linux cooked v2",
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => "Raw IP",
+        LINKTYPE_NULL | LINKTYPE_LOOP => "BSD loopback",
+        _ => "Unknown",
+    }
+}
+
+/// Splits `data` into its link-layer header, any VLAN tags and the network-layer payload.
+pub fn decode(link_type: u32, data: &[u8]) -> Option<LinkHeader<'_>> {
+    link_header(link_type, data).map(untag)
+}
+
+fn link_header(link_type: u32, data: &[u8]) -> Option<LinkHeader<'_>> {
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let ethernet = EthernetPacket::new(data)?;
+            Some(LinkHeader {
+                src: Some(ethernet.get_source().to_string()),
+                dst: Some(ethernet.get_destination().to_string()),
+                ethertype: ethernet.get_ethertype(),
+                vlans: Vec::new(),
+                payload: &data[EthernetPacket::minimum_packet_size()..],
+            })
+        }
+        // packet type, ARPHRD type, address length, address, protocol
+        LINKTYPE_LINUX_SLL => {
+            let header = data.get(..SLL_HEADER_LEN)?;
+            Some(LinkHeader {
+                src: link_address(&header[6..14], u16::from_be_bytes([header[4], header[5]])),
+                dst: None,
+                ethertype: EtherType::new(u16::from_be_bytes([header[14], header[15]])),
+                vlans: Vec::new(),
+                payload: &data[SLL_HEADER_LEN..],
+            })
+        }
+        // protocol, reserved, interface index, ARPHRD type, packet type, address length, address
+        LINKTYPE_LINUX_SLL2 => {
+            let header = data.get(..SLL2_HEADER_LEN)?;
+            Some(LinkHeader {
+                src: link_address(&header[12..20], header[11] as u16),
+                dst: None,
+                ethertype: EtherType::new(u16::from_be_bytes([header[0], header[1]])),
+                vlans: Vec::new(),
+                payload: &data[SLL2_HEADER_LEN..],
+            })
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => network_layer(data),
+        LINKTYPE_NULL | LINKTYPE_LOOP => network_layer(data.get(NULL_HEADER_LEN..)?),
+        _ => None,
+    }
+}
+
+/// A frame with no link-layer header; the IP version tells what it carries.
+fn network_layer(data: &[u8]) -> Option<LinkHeader<'_>> {
+    let ethertype = match data.first()? >> 4 {
+        4 => EtherTypes::Ipv4,
+        6 => EtherTypes::Ipv6,
+        _ => return None,
+    };
+    Some(LinkHeader {
+        src: None,
+        dst: None,
+        ethertype,
+        vlans: Vec::new(),
+        payload: data,
+    })
+}
+
+/// Moves the VLAN tags at the start of the payload into `vlans`. A tag cut short is left
+/// in the payload, for the frame to be shown as what it is.
+fn untag(mut header: LinkHeader<'_>) -> LinkHeader<'_> {
+    while VlanTag::is_tpid(header.ethertype.0) && header.payload.len() >= VLAN_TAG_LEN {
+        let tci = u16::from_be_bytes([header.payload[0], header.payload[1]]);
+        header.vlans.push(VlanTag::new(header.ethertype.0, tci));
+        header.ethertype =
+            EtherType::new(u16::from_be_bytes([header.payload[2], header.payload[3]]));
+        header.payload = &header.payload[VLAN_TAG_LEN..];
+    }
+    header
+}
+
+/// Formats the sender address of a cooked header, a MAC when it has the length of one.
+fn link_address(address: &[u8], len: u16) -> Option<String> {
+    let address = address.get(..len as usize).filter(|a| !a.is_empty())?;
+    Some(match *address {
+        [a, b, c, d, e, f] => MacAddr::new(a, b, c, d, e, f).to_string(),
+        _ => address
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(":"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ethernet up to the ethertype, then `rest`.
+    fn ethernet(ethertype: u16, rest: &[u8]) -> Vec<u8> {
+        let mut frame = vec![2, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 1];
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(rest);
+        frame
+    }
+
+    const IPV4: [u8; 4] = [0x45, 0, 0, 20];
+
+    #[test]
+    fn decodes_a_single_tag() {
+        // priority 5, drop eligible, ID 100
+        let frame = ethernet(
+            0x8100,
+            &[[0xb0, 0x64, 0x08, 0x00].as_slice(), &IPV4].concat(),
+        );
+        let header = decode(LINKTYPE_ETHERNET, &frame).unwrap();
+        assert_eq!(
+            header.vlans,
+            [VlanTag {
+                tpid: 0x8100,
+                id: 100,
+                pcp: 5,
+                dei: true
+            }]
+        );
+        assert_eq!(header.ethertype, EtherTypes::Ipv4);
+        assert_eq!(header.payload, IPV4);
+    }
+
+    #[test]
+    fn decodes_qinq_outermost_first() {
+        let tags = [0x00, 0x0a, 0x81, 0x00, 0x20, 0x14, 0x08, 0x00];
+        let frame = ethernet(0x88a8, &[tags.as_slice(), &IPV4].concat());
+        let header = decode(LINKTYPE_ETHERNET, &frame).unwrap();
+        assert_eq!(
+            header.vlans,
+            [
+                VlanTag {
+                    tpid: 0x88a8,
+                    id: 10,
+                    pcp: 0,
+                    dei: false
+                },
+                VlanTag {
+                    tpid: 0x8100,
+                    id: 20,
+                    pcp: 1,
+                    dei: false
+                },
+            ]
+        );
+        assert_eq!(header.ethertype, EtherTypes::Ipv4);
+        assert_eq!(header.payload, IPV4);
+    }
+
+    #[test]
+    fn leaves_a_truncated_tag_in_the_payload() {
+        let frame = ethernet(0x8100, &[0x00, 0x64, 0x08]);
+        let header = decode(LINKTYPE_ETHERNET, &frame).unwrap();
+        assert!(header.vlans.is_empty());
+        assert_eq!(header.ethertype.0, 0x8100);
+        assert_eq!(header.payload, [0x00, 0x64, 0x08]);
+
+        // the outer tag of a QinQ frame is still read
+        let frame = ethernet(0x88a8, &[0x00, 0x0a, 0x81, 0x00, 0x00, 0x14]);
+        let header = decode(LINKTYPE_ETHERNET, &frame).unwrap();
+        assert_eq!(header.vlans.len(), 1);
+        assert_eq!(header.ethertype.0, 0x8100);
+        assert_eq!(header.payload, [0x00, 0x14]);
+    }
+}
@@ -1,36 +1,79 @@
+pub mod bpf;
+pub mod craft;
+pub mod discovery;
+pub mod dissectors;
+pub mod frame;
+pub mod interfaces;
+pub mod link;
+#[cfg(target_os = "linux")]
+pub mod packet_socket;
+pub mod pcap;
+pub mod protocols;
+pub mod reassembly;
+pub mod ring;
+pub mod session;
 pub mod show_packets;
+pub mod source;
+pub mod stats;
+pub mod stop;
+pub mod store;
+pub mod tree;
+pub mod trigger;
 
-use pnet::datalink::{self, NetworkInterface};
-use pnet::datalink::{Channel, DataLinkReceiver, DataLinkSender};
+use std::time::Duration;
 
-pub fn get_default_interface() -> NetworkInterface {
-    let interfaces = datalink::interfaces();
-    interfaces
-        .into_iter()
-        .find(|iface| {
-            iface.is_up() && !iface.is_loopback() && iface.ips.iter().any(|ip| ip.is_ipv4())
-        })
-        .expect("No available network interface found")
-}
+use pnet::datalink::{self, Channel, Config, DataLinkReceiver, DataLinkSender, NetworkInterface};
+
+use crate::errors::{AppError, AppResult};
+
+/// How long a blocking read waits before giving the capture loop a chance to check for stop.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// Large enough for jumbo frames and loopback's 64k MTU.
+const READ_BUFFER_SIZE: usize = 65_536;
+/// Kernel-side queue for the capture socket. Bursts that briefly outrun the dissector wait
+/// here instead of being dropped.
+#[cfg(target_os = "linux")]
+const SOCKET_RCVBUF: libc::c_int = 8 * 1024 * 1024;
+
+/// Name of the pseudo-interface that captures on every interface at once.
+pub const ANY_INTERFACE: &str = "any";
 
-pub fn get_interface(interface_name: String) -> NetworkInterface {
-    let interfaces = datalink::interfaces();
-    interfaces
+pub fn get_interface(interface_name: String) -> AppResult<NetworkInterface> {
+    if interface_name == ANY_INTERFACE {
+        if cfg!(not(target_os = "linux")) {
+            return Err(
+                anyhow::anyhow!("Capturing on all interfaces is only supported on Linux").into(),
+            );
+        }
+        return Ok(NetworkInterface {
+            name: ANY_INTERFACE.to_string(),
+            description: String::new(),
+            index: 0,
+            mac: None,
+            ips: Vec::new(),
+            flags: 0,
+        });
+    }
+    datalink::interfaces()
         .into_iter()
         .find(|iface| iface.name == interface_name)
-        .unwrap_or(get_default_interface())
+        .ok_or(AppError::InterfaceNotFound(interface_name))
 }
 
+/// Captures outside Linux read from the receiver; crafted frames are sent on the sender.
 pub fn create_chanel(
-    interface: NetworkInterface,
-) -> (Box<dyn DataLinkSender>, Box<dyn DataLinkReceiver>) {
-    let (mut tx, mut rx) = match datalink::channel(&interface, Default::default()) {
-        Ok(Channel::Ethernet(tx, rx)) => (tx, rx),
-        Ok(_) => panic!("Unhandled channel type"),
-        Err(e) => panic!(
-            "An error occurred when creating the datalink channel: {}",
-            e
-        ),
+    interface: &NetworkInterface,
+) -> std::io::Result<(Box<dyn DataLinkSender>, Box<dyn DataLinkReceiver>)> {
+    let config = Config {
+        read_timeout: Some(READ_TIMEOUT),
+        read_buffer_size: READ_BUFFER_SIZE,
+        ..Default::default()
     };
-    (tx, rx)
+    match datalink::channel(interface, config)? {
+        Channel::Ethernet(tx, rx) => Ok((tx, rx)),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Unhandled channel type",
+        )),
+    }
 }
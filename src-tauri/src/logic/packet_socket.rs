@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    fs, io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use pnet::datalink::NetworkInterface;
+
+use super::{
+    bpf::{self, BpfProgram, Framing},
+    frame::RawFrame,
+    link::{SLL2_HEADER_LEN, VLAN_TAG_LEN},
+    pcap::{LINKTYPE_ETHERNET, LINKTYPE_LINUX_SLL2},
+    READ_BUFFER_SIZE, READ_TIMEOUT, SOCKET_RCVBUF,
+};
+
+/// How frames are read off a packet socket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureMode {
+    /// Frames come with their Ethernet header, as on Ethernet, Wi-Fi and loopback devices.
+    Ethernet,
+    /// The kernel strips the link-layer header and frames are given an SLL2 header instead,
+    /// which records the arrival interface. Used for "any" and devices without Ethernet framing.
+    Cooked,
+}
+
+impl CaptureMode {
+    /// Picks the mode for `interface`. Index 0 is the pseudo-interface for all of them.
+    pub fn for_interface(interface: &NetworkInterface) -> Self {
+        if interface.index == 0 {
+            return CaptureMode::Cooked;
+        }
+        let arphrd = fs::read_to_string(format!("/sys/class/net/{}/type", interface.name))
+            .ok()
+            .and_then(|ty| ty.trim().parse::<u16>().ok());
+        match arphrd {
+            Some(libc::ARPHRD_ETHER | libc::ARPHRD_LOOPBACK) => CaptureMode::Ethernet,
+            _ => CaptureMode::Cooked,
+        }
+    }
+
+    pub fn framing(self) -> Framing {
+        match self {
+            CaptureMode::Ethernet => Framing::Ethernet,
+            CaptureMode::Cooked => Framing::NetworkLayer,
+        }
+    }
+
+    pub fn link_type(self) -> u32 {
+        match self {
+            CaptureMode::Ethernet => LINKTYPE_ETHERNET,
+            CaptureMode::Cooked => LINKTYPE_LINUX_SLL2,
+        }
+    }
+}
+
+/// An `AF_PACKET` socket bound to one interface, or to all of them.
+pub struct PacketSocket {
+    fd: OwnedFd,
+    mode: CaptureMode,
+    buffer: Vec<u8>,
+    snaplen: Option<usize>,
+    names: HashMap<i32, String>,
+}
+
+impl PacketSocket {
+    /// Opens the socket with a large receive buffer and the capture filter, if any, attached
+    /// before it is bound, so unfiltered frames never get queued.
+    pub fn open(
+        interface: &NetworkInterface,
+        mode: CaptureMode,
+        snaplen: Option<usize>,
+        filter: Option<&BpfProgram>,
+    ) -> io::Result<Self> {
+        let socket_type = match mode {
+            CaptureMode::Ethernet => libc::SOCK_RAW,
+            CaptureMode::Cooked => libc::SOCK_DGRAM,
+        };
+        // protocol 0 receives nothing until the socket is bound
+        let fd = unsafe { libc::socket(libc::AF_PACKET, socket_type | libc::SOCK_CLOEXEC, 0) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SO_RCVBUFFORCE ignores rmem_max but needs CAP_NET_ADMIN
+        if setsockopt(&fd, libc::SOL_SOCKET, libc::SO_RCVBUFFORCE, &SOCKET_RCVBUF).is_err() {
+            if let Err(e) = setsockopt(&fd, libc::SOL_SOCKET, libc::SO_RCVBUF, &SOCKET_RCVBUF) {
+                log::warn!("could not enlarge the socket receive buffer: {}", e);
+            }
+        }
+        if let Some(program) = filter {
+            bpf::attach(fd.as_raw_fd(), program)?;
+        }
+        let timeout = libc::timeval {
+            tv_sec: READ_TIMEOUT.as_secs() as libc::time_t,
+            tv_usec: READ_TIMEOUT.subsec_micros() as libc::suseconds_t,
+        };
+        setsockopt(&fd, libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeout)?;
+        // stamps frames when the kernel receives them rather than when we get round to them
+        if let Err(e) = setsockopt(
+            &fd,
+            libc::SOL_SOCKET,
+            libc::SO_TIMESTAMPNS,
+            &(1 as libc::c_int),
+        ) {
+            log::warn!("no kernel timestamps, frames are stamped on read: {}", e);
+        }
+        // NICs that offload VLAN tagging hand frames over with the tag removed; this gets it back
+        if let Err(e) = setsockopt(
+            &fd,
+            libc::SOL_PACKET,
+            libc::PACKET_AUXDATA,
+            &(1 as libc::c_int),
+        ) {
+            log::warn!("VLAN tags removed by the NIC won't be seen: {}", e);
+        }
+
+        let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = (libc::ETH_P_ALL as u16).to_be();
+        addr.sll_ifindex = interface.index as i32;
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if interface.index != 0 {
+            let membership = libc::packet_mreq {
+                mr_ifindex: interface.index as i32,
+                mr_type: libc::PACKET_MR_PROMISC as u16,
+                mr_alen: 0,
+                mr_address: [0; 8],
+            };
+            if let Err(e) = setsockopt(
+                &fd,
+                libc::SOL_PACKET,
+                libc::PACKET_ADD_MEMBERSHIP,
+                &membership,
+            ) {
+                log::warn!(
+                    "could not put {} in promiscuous mode: {}",
+                    interface.name,
+                    e
+                );
+            }
+        }
+
+        Ok(Self {
+            fd,
+            mode,
+            buffer: vec![0; SLL2_HEADER_LEN + READ_BUFFER_SIZE + VLAN_TAG_LEN],
+            snaplen,
+            names: HashMap::new(),
+        })
+    }
+
+    /// Blocks until a frame arrives or the read timeout expires.
+    pub fn recv(&mut self) -> io::Result<RawFrame> {
+        let header_len = match self.mode {
+            CaptureMode::Ethernet => 0,
+            CaptureMode::Cooked => SLL2_HEADER_LEN,
+        };
+        loop {
+            let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+            // the end of the buffer is kept free for a VLAN tag to be put back
+            let end = self.buffer.len() - VLAN_TAG_LEN;
+            let room = &mut self.buffer[header_len..end];
+            let mut iov = libc::iovec {
+                iov_base: room.as_mut_ptr() as *mut libc::c_void,
+                iov_len: room.len(),
+            };
+            // u64 keeps the control buffer aligned for cmsghdr
+            let mut control = [0u64; 16];
+            let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+            msg.msg_name = &mut addr as *mut libc::sockaddr_ll as *mut libc::c_void;
+            msg.msg_namelen = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = mem::size_of_val(&control) as _;
+            // with MSG_TRUNC the length on the wire is returned even if the frame didn't fit
+            let res = unsafe { libc::recvmsg(self.fd.as_raw_fd(), &mut msg, libc::MSG_TRUNC) };
+            if res == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            // loopback hands every frame over twice, once going out and once coming in
+            if addr.sll_pkttype == libc::PACKET_OUTGOING && addr.sll_hatype == libc::ARPHRD_LOOPBACK
+            {
+                continue;
+            }
+
+            let mut wire_len = res as usize;
+            let mut len = header_len + wire_len.min(room.len());
+            if self.mode == CaptureMode::Cooked {
+                write_sll2_header(&mut self.buffer[..SLL2_HEADER_LEN], &addr);
+            }
+            if let Some((tpid, tci)) = stripped_vlan_tag(&msg) {
+                self.put_back_vlan_tag(len, tpid, tci);
+                len += VLAN_TAG_LEN;
+                wire_len += VLAN_TAG_LEN;
+            }
+            let interface = self.interface_name(addr.sll_ifindex);
+            let data = &self.buffer[..len];
+            let mut frame = match kernel_timestamp(&msg) {
+                Some(at) => RawFrame::captured_at(
+                    &interface,
+                    data,
+                    self.snaplen,
+                    at.tv_sec,
+                    at.tv_nsec as u32,
+                ),
+                None => RawFrame::captured_now(&interface, data, self.snaplen),
+            };
+            frame.link_type = self.mode.link_type();
+            frame.orig_len = (header_len + wire_len) as u32;
+            return Ok(frame);
+        }
+    }
+
+    /// Puts a VLAN tag the kernel removed back where it was on the wire, into the first `len`
+    /// bytes of the buffer. Cooked frames get it as their protocol, followed by the tag
+    /// control info and the protocol it was tagging, like an in-band tag reads.
+    fn put_back_vlan_tag(&mut self, len: usize, tpid: u16, tci: u16) {
+        let at = match self.mode {
+            CaptureMode::Ethernet => 12,
+            CaptureMode::Cooked => SLL2_HEADER_LEN,
+        };
+        let at = at.min(len);
+        self.buffer.copy_within(at..len, at + VLAN_TAG_LEN);
+        match self.mode {
+            CaptureMode::Ethernet => {
+                self.buffer[at..at + 2].copy_from_slice(&tpid.to_be_bytes());
+                self.buffer[at + 2..at + 4].copy_from_slice(&tci.to_be_bytes());
+            }
+            CaptureMode::Cooked => {
+                self.buffer[at..at + 2].copy_from_slice(&tci.to_be_bytes());
+                self.buffer.copy_within(0..2, at + 2);
+                self.buffer[0..2].copy_from_slice(&tpid.to_be_bytes());
+            }
+        }
+    }
+
+    /// Frames the kernel dropped since the last call, because the receive buffer was full.
+    /// Reading the statistics resets them.
+    pub fn take_drops(&mut self) -> io::Result<u64> {
+        let mut stats: libc::tpacket_stats = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::tpacket_stats>() as libc::socklen_t;
+        let res = unsafe {
+            libc::getsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_PACKET,
+                libc::PACKET_STATISTICS,
+                &mut stats as *mut libc::tpacket_stats as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stats.tp_drops as u64)
+    }
+
+    /// Interfaces can come and go during a capture, so names are looked up the first time
+    /// an index is seen rather than once up front.
+    fn interface_name(&mut self, index: i32) -> String {
+        self.names
+            .entry(index)
+            .or_insert_with(|| {
+                let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+                let res = unsafe { libc::if_indextoname(index as libc::c_uint, name.as_mut_ptr()) };
+                if res.is_null() {
+                    return format!("if{}", index);
+                }
+                unsafe { CStr::from_ptr(name.as_ptr()) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .clone()
+    }
+}
+
+/// The receive time the kernel attached to a message read with `SO_TIMESTAMPNS` on.
+fn kernel_timestamp(msg: &libc::msghdr) -> Option<libc::timespec> {
+    control_message(msg, libc::SOL_SOCKET, libc::SCM_TIMESTAMPNS)
+}
+
+/// The TPID and tag control info of the VLAN tag the kernel removed from the frame, if it
+/// removed one. Kernels that don't say which TPID it had only ever removed 802.1Q tags.
+fn stripped_vlan_tag(msg: &libc::msghdr) -> Option<(u16, u16)> {
+    let aux: libc::tpacket_auxdata = control_message(msg, libc::SOL_PACKET, libc::PACKET_AUXDATA)?;
+    if aux.tp_status & libc::TP_STATUS_VLAN_VALID == 0 && aux.tp_vlan_tci == 0 {
+        return None;
+    }
+    let tpid = if aux.tp_status & libc::TP_STATUS_VLAN_TPID_VALID != 0 {
+        aux.tp_vlan_tpid
+    } else {
+        0x8100
+    };
+    Some((tpid, aux.tp_vlan_tci))
+}
+
+/// The control message of type `ty` at `level` that came with `msg`.
+fn control_message<T>(msg: &libc::msghdr, level: libc::c_int, ty: libc::c_int) -> Option<T> {
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(msg) };
+    while !cmsg.is_null() {
+        let header = unsafe { &*cmsg };
+        if header.cmsg_level == level && header.cmsg_type == ty {
+            return Some(unsafe { (libc::CMSG_DATA(cmsg) as *const T).read_unaligned() });
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(msg, cmsg) };
+    }
+    None
+}
+
+/// Fills in the header libpcap gives cooked captures, from the address the kernel reported.
+fn write_sll2_header(header: &mut [u8], addr: &libc::sockaddr_ll) {
+    // sll_protocol is already in network byte order
+    header[0..2].copy_from_slice(&addr.sll_protocol.to_ne_bytes());
+    header[2..4].fill(0);
+    header[4..8].copy_from_slice(&(addr.sll_ifindex as u32).to_be_bytes());
+    header[8..10].copy_from_slice(&addr.sll_hatype.to_be_bytes());
+    header[10] = addr.sll_pkttype;
+    header[11] = addr.sll_halen.min(8);
+    header[12..20].copy_from_slice(&addr.sll_addr);
+}
+
+fn setsockopt<T>(
+    fd: &OwnedFd,
+    level: libc::c_int,
+    option: libc::c_int,
+    value: &T,
+) -> io::Result<()> {
+    let res = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            level,
+            option,
+            value as *const T as *const libc::c_void,
+            mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
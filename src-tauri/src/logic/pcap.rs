@@ -0,0 +1,634 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail};
+
+use super::link;
+
+pub const LINKTYPE_NULL: u32 = 0;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_RAW: u32 = 101;
+pub const LINKTYPE_LOOP: u32 = 108;
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+pub const LINKTYPE_IPV4: u32 = 228;
+pub const LINKTYPE_IPV6: u32 = 229;
+pub const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const MAX_SNAPLEN: u32 = 262_144;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const PCAPNG_SIMPLE_PACKET: u32 = 0x0000_0003;
+const PCAPNG_ENHANCED_PACKET: u32 = 0x0000_0006;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_IF_TSOFFSET: u16 = 14;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureFormat {
+    Json,
+    Pcap,
+    Pcapng,
+}
+
+impl CaptureFormat {
+    /// Picks the export format from the extension the user typed in the save dialog.
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("pcap") | Some("cap") => CaptureFormat::Pcap,
+            Some("pcapng") | Some("ntar") => CaptureFormat::Pcapng,
+            _ => CaptureFormat::Json,
+        }
+    }
+
+    /// Sniffs the file magic, so imports don't depend on the file being named correctly.
+    pub fn detect(data: &[u8]) -> Self {
+        let Some(magic) = data.get(..4) else {
+            return CaptureFormat::Json;
+        };
+        let le = u32::from_le_bytes([magic[0], magic[1], magic[2], magic[3]]);
+        let be = u32::from_be_bytes([magic[0], magic[1], magic[2], magic[3]]);
+        if le == PCAPNG_SECTION_HEADER {
+            CaptureFormat::Pcapng
+        } else if [PCAP_MAGIC_MICROS, PCAP_MAGIC_NANOS].contains(&le)
+            || [PCAP_MAGIC_MICROS, PCAP_MAGIC_NANOS].contains(&be)
+        {
+            CaptureFormat::Pcap
+        } else {
+            CaptureFormat::Json
+        }
+    }
+}
+
+/// One frame as stored in a capture file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureRecord {
+    pub link_type: u32,
+    pub interface: Option<String>,
+    pub ts_sec: i64,
+    pub ts_nsec: u32,
+    pub orig_len: u32,
+    pub data: Vec<u8>,
+}
+
+pub fn read_capture(data: &[u8]) -> anyhow::Result<Vec<CaptureRecord>> {
+    match CaptureFormat::detect(data) {
+        CaptureFormat::Pcap => read_pcap(data),
+        CaptureFormat::Pcapng => read_pcapng(data),
+        CaptureFormat::Json => bail!("Not a pcap or pcapng file"),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            big_endian: false,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.remaining() < len {
+            bail!("Unexpected end of capture file at offset {}", self.pos);
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        let b = self.bytes(2)?;
+        let b = [b[0], b[1]];
+        Ok(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let b = self.bytes(4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        let (a, b) = (self.u32()? as u64, self.u32()? as u64);
+        Ok(if self.big_endian {
+            (a << 32) | b
+        } else {
+            (b << 32) | a
+        })
+    }
+
+    /// pcapng timestamps are stored as two 32-bit words, high word first, regardless of endianness.
+    fn u64_split(&mut self) -> anyhow::Result<u64> {
+        let high = self.u32()? as u64;
+        let low = self.u32()? as u64;
+        Ok((high << 32) | low)
+    }
+
+    fn with_endian(mut self, big_endian: bool) -> Self {
+        self.big_endian = big_endian;
+        self
+    }
+}
+
+fn read_pcap(data: &[u8]) -> anyhow::Result<Vec<CaptureRecord>> {
+    let mut reader = Reader::new(data);
+    let magic = reader.u32()?;
+    let nanos = match magic {
+        PCAP_MAGIC_MICROS => false,
+        PCAP_MAGIC_NANOS => true,
+        _ => {
+            reader.big_endian = true;
+            match magic.swap_bytes() {
+                PCAP_MAGIC_MICROS => false,
+                PCAP_MAGIC_NANOS => true,
+                _ => bail!("Bad pcap magic {:#x}", magic),
+            }
+        }
+    };
+    // version major/minor, thiszone, sigfigs, snaplen
+    reader.bytes(16)?;
+    // upper bits carry FCS information we don't use
+    let link_type = reader.u32()? & 0xffff;
+
+    let mut records = Vec::new();
+    while reader.remaining() > 0 {
+        let record = (|| {
+            let ts_sec = reader.u32()? as i64;
+            let ts_frac = reader.u32()?;
+            let incl_len = reader.u32()? as usize;
+            let orig_len = reader.u32()?;
+            let data = reader.bytes(incl_len)?.to_vec();
+            Ok::<_, anyhow::Error>(CaptureRecord {
+                link_type,
+                interface: None,
+                ts_sec,
+                // malformed files can hold a fraction of a second or more
+                ts_nsec: if nanos {
+                    ts_frac
+                } else {
+                    ts_frac.saturating_mul(1000)
+                }
+                .min(999_999_999),
+                orig_len,
+                data,
+            })
+        })();
+        match record {
+            Ok(record) => records.push(record),
+            Err(e) => {
+                log::warn!("pcap file truncated after {} records: {}", records.len(), e);
+                break;
+            }
+        }
+    }
+    Ok(records)
+}
+
+struct PcapngInterface {
+    link_type: u32,
+    snaplen: u32,
+    name: Option<String>,
+    units_per_sec: u64,
+    ts_offset: i64,
+}
+
+fn read_pcapng(data: &[u8]) -> anyhow::Result<Vec<CaptureRecord>> {
+    let mut reader = Reader::new(data);
+    // a corrupt interface block keeps its index as `None`, so later packets can't be
+    // attributed to the wrong interface
+    let mut interfaces: Vec<Option<PcapngInterface>> = Vec::new();
+    let mut records = Vec::new();
+
+    while reader.remaining() >= 12 {
+        let block_start = reader.pos;
+        let block_type = reader.u32()?;
+        if block_type == PCAPNG_SECTION_HEADER {
+            // the byte-order magic decides how this whole section, including its length, is read
+            let bom = &data[block_start + 8..block_start + 12];
+            reader.big_endian = match u32::from_le_bytes([bom[0], bom[1], bom[2], bom[3]]) {
+                PCAPNG_BYTE_ORDER_MAGIC => false,
+                m if m.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+                m => bail!("Bad pcapng byte-order magic {:#x}", m),
+            };
+            interfaces.clear();
+        }
+        let total_len = reader.u32()? as usize;
+        if total_len < 12 || block_start + total_len > data.len() {
+            log::warn!(
+                "pcapng file truncated after {} records at offset {}",
+                records.len(),
+                block_start
+            );
+            break;
+        }
+        let mut body = Reader {
+            data: &data[..block_start + total_len - 4],
+            pos: reader.pos,
+            big_endian: reader.big_endian,
+        };
+
+        // the block's length is sound, so a corrupt body only loses this block
+        let block = (|| {
+            match block_type {
+                PCAPNG_INTERFACE_DESCRIPTION => {
+                    interfaces.push(None);
+                    let link_type = body.u16()? as u32;
+                    body.u16()?;
+                    let snaplen = body.u32()?;
+                    let mut interface = PcapngInterface {
+                        link_type,
+                        snaplen,
+                        name: None,
+                        units_per_sec: 1_000_000,
+                        ts_offset: 0,
+                    };
+                    while body.remaining() >= 4 {
+                        let code = body.u16()?;
+                        let len = body.u16()? as usize;
+                        if code == OPT_ENDOFOPT {
+                            break;
+                        }
+                        let value = body.bytes(len)?;
+                        body.bytes((4 - len % 4) % 4)?;
+                        match code {
+                            OPT_IF_NAME => {
+                                interface.name = Some(
+                                    String::from_utf8_lossy(value)
+                                        .trim_end_matches('\0')
+                                        .to_string(),
+                                )
+                            }
+                            OPT_IF_TSRESOL if len == 1 => {
+                                let exp = (value[0] & 0x7f) as u32;
+                                interface.units_per_sec = if value[0] & 0x80 == 0 {
+                                    10u64.checked_pow(exp)
+                                } else {
+                                    2u64.checked_pow(exp)
+                                }
+                                .ok_or_else(|| anyhow!("Unsupported if_tsresol {}", value[0]))?;
+                            }
+                            OPT_IF_TSOFFSET if len == 8 => {
+                                interface.ts_offset =
+                                    Reader::new(value).with_endian(body.big_endian).u64()? as i64;
+                            }
+                            _ => {}
+                        }
+                    }
+                    *interfaces.last_mut().unwrap() = Some(interface);
+                }
+                PCAPNG_ENHANCED_PACKET => {
+                    let interface_id = body.u32()? as usize;
+                    let ts = body.u64_split()?;
+                    let cap_len = body.u32()? as usize;
+                    let orig_len = body.u32()?;
+                    let data = body.bytes(cap_len)?.to_vec();
+                    let interface = interfaces
+                        .get(interface_id)
+                        .and_then(Option::as_ref)
+                        .ok_or_else(|| {
+                            anyhow!("Packet references unknown interface {}", interface_id)
+                        })?;
+                    let units = interface.units_per_sec;
+                    let ts_nsec = ((ts % units) as u128 * 1_000_000_000 / units as u128) as u32;
+                    records.push(CaptureRecord {
+                        link_type: interface.link_type,
+                        interface: interface.name.clone(),
+                        ts_sec: ((ts / units) as i64)
+                            .checked_add(interface.ts_offset)
+                            .ok_or_else(|| anyhow!("Timestamp out of range"))?,
+                        ts_nsec,
+                        orig_len,
+                        data,
+                    });
+                }
+                PCAPNG_SIMPLE_PACKET => {
+                    let interface = interfaces
+                        .first()
+                        .and_then(Option::as_ref)
+                        .ok_or_else(|| anyhow!("Simple packet block before any interface"))?;
+                    let orig_len = body.u32()?;
+                    let mut cap_len = (orig_len as usize).min(body.remaining());
+                    if interface.snaplen > 0 {
+                        cap_len = cap_len.min(interface.snaplen as usize);
+                    }
+                    records.push(CaptureRecord {
+                        link_type: interface.link_type,
+                        interface: interface.name.clone(),
+                        ts_sec: 0,
+                        ts_nsec: 0,
+                        orig_len,
+                        data: body.bytes(cap_len)?.to_vec(),
+                    });
+                }
+                _ => {}
+            }
+            Ok::<_, anyhow::Error>(())
+        })();
+        if let Err(e) = block {
+            log::warn!(
+                "skipping corrupt pcapng block at offset {}: {}",
+                block_start,
+                e
+            );
+        }
+        reader.pos = block_start + total_len;
+    }
+    Ok(records)
+}
+
+/// Writes a pcap file, which has a single link type for all its frames. Records of different
+/// link types are refused rather than left out, as pcapng can hold them all.
+pub fn write_pcap<W: Write>(writer: &mut W, records: &[CaptureRecord]) -> io::Result<()> {
+    let link_type = records
+        .first()
+        .map(|record| record.link_type)
+        .unwrap_or(LINKTYPE_ETHERNET);
+    if let Some(other) = records.iter().find(|record| record.link_type != link_type) {
+        return Err(io::Error::other(format!(
+            "The capture has both {} and {} frames, which a pcap file can't hold. Export it as pcapng instead",
+            link::name(link_type),
+            link::name(other.link_type)
+        )));
+    }
+
+    writer.write_all(&PCAP_MAGIC_NANOS.to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&4u16.to_le_bytes())?;
+    writer.write_all(&0i32.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&MAX_SNAPLEN.to_le_bytes())?;
+    writer.write_all(&link_type.to_le_bytes())?;
+
+    for record in records {
+        // the seconds field is unsigned 32-bit, so earlier or later times are clamped to it
+        let ts_sec = record.ts_sec.clamp(0, u32::MAX as i64) as u32;
+        writer.write_all(&ts_sec.to_le_bytes())?;
+        writer.write_all(&record.ts_nsec.to_le_bytes())?;
+        writer.write_all(&(record.data.len() as u32).to_le_bytes())?;
+        writer.write_all(&record.orig_len.to_le_bytes())?;
+        writer.write_all(&record.data)?;
+    }
+    Ok(())
+}
+
+/// Writes one pcapng block and returns its length.
+fn write_block<W: Write>(writer: &mut W, block_type: u32, body: &[u8]) -> io::Result<u64> {
+    let padding = (4 - body.len() % 4) % 4;
+    let total_len = (12 + body.len() + padding) as u32;
+    writer.write_all(&block_type.to_le_bytes())?;
+    writer.write_all(&total_len.to_le_bytes())?;
+    writer.write_all(body)?;
+    writer.write_all(&[0u8; 3][..padding])?;
+    writer.write_all(&total_len.to_le_bytes())?;
+    Ok(total_len as u64)
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.extend(std::iter::repeat_n(0, (4 - value.len() % 4) % 4));
+}
+
+pub fn write_pcapng<W: Write>(writer: &mut W, records: &[CaptureRecord]) -> io::Result<()> {
+    let mut writer = PcapngWriter::new(writer)?;
+    for record in records {
+        writer.write_record(record)?;
+    }
+    Ok(())
+}
+
+/// Writes a pcapng section one record at a time, adding an interface block the first time each
+/// (link type, interface) pair shows up.
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+    interfaces: HashMap<(u32, Option<String>), u32>,
+    written: u64,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let mut section = Vec::new();
+        section.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        section.extend_from_slice(&1u16.to_le_bytes());
+        section.extend_from_slice(&0u16.to_le_bytes());
+        section.extend_from_slice(&(-1i64).to_le_bytes());
+        let written = write_block(&mut writer, PCAPNG_SECTION_HEADER, &section)?;
+        Ok(Self {
+            writer,
+            interfaces: HashMap::new(),
+            written,
+        })
+    }
+
+    pub fn write_record(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        let key = (record.link_type, record.interface.clone());
+        let interface_id = match self.interfaces.get(&key) {
+            Some(id) => *id,
+            None => {
+                let mut idb = Vec::new();
+                idb.extend_from_slice(&(record.link_type as u16).to_le_bytes());
+                idb.extend_from_slice(&0u16.to_le_bytes());
+                idb.extend_from_slice(&0u32.to_le_bytes());
+                if let Some(name) = &record.interface {
+                    push_option(&mut idb, OPT_IF_NAME, name.as_bytes());
+                }
+                push_option(&mut idb, OPT_IF_TSRESOL, &[9]);
+                push_option(&mut idb, OPT_ENDOFOPT, &[]);
+                self.written += write_block(&mut self.writer, PCAPNG_INTERFACE_DESCRIPTION, &idb)?;
+
+                let id = self.interfaces.len() as u32;
+                self.interfaces.insert(key, id);
+                id
+            }
+        };
+
+        let ts = record.ts_sec.max(0) as u64 * 1_000_000_000 + record.ts_nsec as u64;
+        let mut epb = Vec::with_capacity(20 + record.data.len());
+        epb.extend_from_slice(&interface_id.to_le_bytes());
+        epb.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(ts as u32).to_le_bytes());
+        epb.extend_from_slice(&(record.data.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&record.orig_len.to_le_bytes());
+        epb.extend_from_slice(&record.data);
+        let padding = (4 - record.data.len() % 4) % 4;
+        epb.extend(std::iter::repeat_n(0, padding));
+        push_option(&mut epb, OPT_ENDOFOPT, &[]);
+        self.written += write_block(&mut self.writer, PCAPNG_ENHANCED_PACKET, &epb)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Bytes written so far, section header included.
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ts_sec: i64, data: &[u8]) -> CaptureRecord {
+        CaptureRecord {
+            link_type: LINKTYPE_ETHERNET,
+            interface: Some("eth0".into()),
+            ts_sec,
+            ts_nsec: 0,
+            orig_len: data.len() as u32,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn pcap_round_trip() {
+        let mut truncated = record(2, &[2; 20]);
+        truncated.orig_len = 1500;
+        truncated.ts_nsec = 123_456_789;
+        let records = vec![record(1, &[1; 60]), truncated];
+        let mut file = Vec::new();
+        write_pcap(&mut file, &records).unwrap();
+
+        let read = read_capture(&file).unwrap();
+        // pcap has no interface names
+        let expected: Vec<CaptureRecord> = records
+            .into_iter()
+            .map(|record| CaptureRecord {
+                interface: None,
+                ..record
+            })
+            .collect();
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn pcapng_round_trip() {
+        let mut other_interface = record(2, &[2; 33]);
+        other_interface.interface = Some("eth1".into());
+        other_interface.ts_nsec = 999_999_999;
+        let mut other_link = record(3, &[0x45; 40]);
+        other_link.link_type = LINKTYPE_RAW;
+        other_link.orig_len = 1400;
+        let records = vec![record(1, &[1; 60]), other_interface, other_link];
+        let mut file = Vec::new();
+        write_pcapng(&mut file, &records).unwrap();
+
+        assert_eq!(read_capture(&file).unwrap(), records);
+    }
+
+    #[test]
+    fn pcap_refuses_mixed_link_types() {
+        let mut raw = record(2, &[0x45; 20]);
+        raw.link_type = LINKTYPE_RAW;
+        let e = write_pcap(&mut Vec::new(), &[record(1, &[1; 60]), raw]).unwrap_err();
+        assert!(e.to_string().contains("pcapng"));
+    }
+
+    #[test]
+    fn pcap_clamps_timestamps_it_cant_hold() {
+        let records = [record(-5, &[1; 20]), record(1 << 40, &[2; 20])];
+        let mut file = Vec::new();
+        write_pcap(&mut file, &records).unwrap();
+        let seconds: Vec<i64> = read_capture(&file)
+            .unwrap()
+            .iter()
+            .map(|record| record.ts_sec)
+            .collect();
+        assert_eq!(seconds, [0, u32::MAX as i64]);
+    }
+
+    #[test]
+    fn clamps_fractions_of_a_second_or_more() {
+        let mut file = Vec::new();
+        file.extend_from_slice(&PCAP_MAGIC_MICROS.to_le_bytes());
+        file.extend_from_slice(&[0; 16]);
+        file.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        for ts_frac in [5, 1_000_000, u32::MAX] {
+            file.extend_from_slice(&1u32.to_le_bytes());
+            file.extend_from_slice(&ts_frac.to_le_bytes());
+            file.extend_from_slice(&0u32.to_le_bytes());
+            file.extend_from_slice(&0u32.to_le_bytes());
+        }
+        let nanos: Vec<u32> = read_capture(&file)
+            .unwrap()
+            .iter()
+            .map(|record| record.ts_nsec)
+            .collect();
+        assert_eq!(nanos, [5_000, 999_999_999, 999_999_999]);
+    }
+
+    #[test]
+    fn skips_corrupt_pcapng_blocks() {
+        let mut file = Vec::new();
+        let mut writer = PcapngWriter::new(&mut file).unwrap();
+        writer.write_record(&record(1, &[1; 20])).unwrap();
+        let second = writer.bytes_written() as usize;
+        writer.write_record(&record(2, &[2; 20])).unwrap();
+        writer.write_record(&record(3, &[3; 20])).unwrap();
+        drop(writer);
+        // the second packet claims more bytes than its block holds
+        file[second + 20..second + 24].copy_from_slice(&1000u32.to_le_bytes());
+
+        let records = read_capture(&file).unwrap();
+        let seconds: Vec<i64> = records.iter().map(|record| record.ts_sec).collect();
+        assert_eq!(seconds, [1, 3]);
+        assert_eq!(records[1].data, [3; 20]);
+    }
+
+    #[test]
+    fn drops_packets_of_corrupt_interfaces() {
+        let mut file = Vec::new();
+        let mut writer = PcapngWriter::new(&mut file).unwrap();
+        let interface = writer.bytes_written() as usize;
+        writer.write_record(&record(1, &[1; 20])).unwrap();
+        let mut other = record(2, &[2; 20]);
+        other.interface = Some("eth1".into());
+        writer.write_record(&other).unwrap();
+        drop(writer);
+        // an if_tsresol of 10^127 can't be used
+        let tsresol = file[interface..]
+            .windows(4)
+            .position(|option| option == [9, 0, 1, 0])
+            .unwrap();
+        file[interface + tsresol + 4] = 127;
+
+        let records = read_capture(&file).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].interface.as_deref(), Some("eth1"));
+    }
+}
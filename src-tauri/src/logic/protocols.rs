@@ -0,0 +1,111 @@
+/// The keyword the IANA protocol numbers registry lists for an IP protocol, so protocols we
+/// don't dissect still get a meaningful label.
+pub fn ip_protocol_name(protocol: u8) -> Option<&'static str> {
+    Some(match protocol {
+        0 => "HOPOPT",
+        1 => "ICMP",
+        2 => "IGMP",
+        3 => "GGP",
+        4 => "IPv4",
+        5 => "ST",
+        6 => "TCP",
+        7 => "CBT",
+        8 => "EGP",
+        9 => "IGP",
+        17 => "UDP",
+        27 => "RDP",
+        29 => "ISO-TP4",
+        33 => "DCCP",
+        36 => "XTP",
+        41 => "IPv6",
+        43 => "IPv6-Route",
+        44 => "IPv6-Frag",
+        45 => "IDRP",
+        46 => "RSVP",
+        47 => "GRE",
+        50 => "ESP",
+        51 => "AH",
+        55 => "MOBILE",
+        58 => "ICMPv6",
+        59 => "IPv6-NoNxt",
+        60 => "IPv6-Opts",
+        88 => "EIGRP",
+        89 => "OSPF",
+        94 => "IPIP",
+        97 => "ETHERIP",
+        98 => "ENCAP",
+        103 => "PIM",
+        108 => "IPComp",
+        112 => "VRRP",
+        113 => "PGM",
+        115 => "L2TP",
+        124 => "IS-IS",
+        132 => "SCTP",
+        133 => "FC",
+        135 => "Mobility",
+        136 => "UDPLite",
+        137 => "MPLS-in-IP",
+        139 => "HIP",
+        140 => "Shim6",
+        141 => "WESP",
+        142 => "ROHC",
+        143 => "Ethernet",
+        _ => return None,
+    })
+}
+
+/// A short name for an ethertype from the IEEE registry, as protocol analyzers usually show it.
+pub fn ether_type_name(ethertype: u16) -> Option<&'static str> {
+    Some(match ethertype {
+        // below 0x0600 the field is an 802.3 length, and an LLC header follows
+        0..=0x05ff => "LLC",
+        0x0800 => "IPv4",
+        0x0806 => "ARP",
+        0x0842 => "WoL",
+        0x22f0 => "AVTP",
+        0x22f3 => "TRILL",
+        0x6003 => "DECnet",
+        0x8035 => "RARP",
+        0x809b => "AppleTalk",
+        0x80f3 => "AARP",
+        0x8100 => "802.1Q",
+        0x8137 => "IPX",
+        0x8204 => "QNX",
+        0x86dd => "IPv6",
+        0x8808 => "Ethernet flow control",
+        0x8809 => "Slow protocols (LACP)",
+        0x8819 => "CobraNet",
+        0x8847 => "MPLS",
+        0x8848 => "MPLS multicast",
+        0x8863 => "PPPoE discovery",
+        0x8864 => "PPPoE session",
+        0x887b => "HomePlug",
+        0x888e => "EAPOL",
+        0x8892 => "PROFINET",
+        0x889a => "HyperSCSI",
+        0x88a2 => "ATA over Ethernet",
+        0x88a4 => "EtherCAT",
+        0x88a8 => "802.1ad",
+        0x88ab => "Ethernet Powerlink",
+        0x88b8 => "GOOSE",
+        0x88ba => "IEC 61850 SV",
+        0x88cc => "LLDP",
+        0x88cd => "SERCOS III",
+        0x88e1 => "HomePlug AV",
+        0x88e3 => "MRP",
+        0x88e5 => "MACsec",
+        0x88e7 => "PBB",
+        0x88f7 => "PTP",
+        0x88fb => "PRP",
+        0x8902 => "CFM",
+        0x8906 => "FCoE",
+        0x8914 => "FIP",
+        0x8915 => "RoCE",
+        0x891d => "TTE",
+        0x893a => "IEEE 1905",
+        0x892f => "HSR",
+        0x9000 => "Loopback",
+        0x9100 => "QinQ",
+        _ => return None,
+    })
+}
@@ -0,0 +1,350 @@
+//! IP datagrams split into fragments on the way, put back together. Dissection looks at one
+//! frame at a time, so fragments only show as data there; the [`Reassembler`] follows them
+//! in capture order and dissects the whole datagram onto the packet of the fragment that
+//! completed it.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+};
+
+use super::show_packets::{self, FormatedPacket};
+
+/// Seconds of capture time a datagram may take to arrive in full, RFC 8200's limit for
+/// IPv6. Longer than Linux waits for IPv4, so nothing the receiver reassembled is missed.
+const TIMEOUT_SECS: f64 = 60.0;
+/// Fragment bytes held at most, as Linux's default `ipfrag_high_thresh`. The oldest
+/// datagrams are given up on beyond it, so a flood of fragments can't exhaust memory.
+const MAX_PENDING_BYTES: usize = 4 * 1024 * 1024;
+/// The longest payload an IPv4 datagram can have: 65,535 bytes less the shortest header.
+const MAX_IPV4_PAYLOAD: usize = 65_515;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct DatagramKey {
+    src: IpAddr,
+    dst: IpAddr,
+    protocol: u8,
+    id: u32,
+}
+
+/// The fragments of one datagram seen so far.
+struct Datagram {
+    /// Fragment bytes by where they go in the payload.
+    pieces: BTreeMap<usize, (u32, Vec<u8>)>,
+    /// Known once the last fragment has arrived.
+    length: Option<usize>,
+    first_seen: f64,
+    bytes: usize,
+    /// Why the datagram can't be reassembled, once that's known.
+    invalid: Option<&'static str>,
+}
+
+impl Datagram {
+    /// Checks the fragment `at` against those already here: bytes it shares with them must
+    /// be the same, as a receiver would otherwise keep one or the other depending on its OS.
+    fn conflicts(&self, at: usize, bytes: &[u8]) -> bool {
+        let end = at + bytes.len();
+        self.pieces
+            .range(..end)
+            .map(|(&start, (_, piece))| (start, piece))
+            .filter(|(start, piece)| start + piece.len() > at)
+            .any(|(start, piece)| {
+                let (from, to) = (at.max(start), end.min(start + piece.len()));
+                piece[from - start..to - start] != bytes[from - at..to - at]
+            })
+    }
+
+    /// The payload, if every byte of it has arrived.
+    fn payload(&self) -> Option<Vec<u8>> {
+        let length = self.length?;
+        let mut payload = vec![0; length];
+        let mut filled = 0;
+        for (&at, (_, piece)) in &self.pieces {
+            if at > filled || at + piece.len() > length {
+                return None;
+            }
+            payload[at..at + piece.len()].copy_from_slice(piece);
+            filled = filled.max(at + piece.len());
+        }
+        (filled == length).then_some(payload)
+    }
+}
+
+/// Collects the fragments of a capture's datagrams, in the order they were captured.
+#[derive(Default)]
+pub struct Reassembler {
+    pending: HashMap<DatagramKey, Datagram>,
+    bytes: usize,
+}
+
+impl Reassembler {
+    /// Takes in `packet` if it's a fragment. The one that completes a datagram has the
+    /// datagram dissected onto it, and one that can't be reassembled says why in its info.
+    pub fn add(&mut self, packet: &mut FormatedPacket) {
+        let Some(fragment) = show_packets::fragment(&packet.frame) else {
+            return;
+        };
+        let key = DatagramKey {
+            src: fragment.src,
+            dst: fragment.dst,
+            protocol: fragment.protocol.0,
+            id: fragment.id,
+        };
+        let (at, more, protocol) = (fragment.offset, fragment.more, fragment.protocol);
+        let bytes = fragment.payload.to_vec();
+        self.expire(packet.timestamp);
+
+        let datagram = self.pending.entry(key).or_insert_with(|| Datagram {
+            pieces: BTreeMap::new(),
+            length: None,
+            first_seen: packet.timestamp,
+            bytes: 0,
+            invalid: None,
+        });
+        let end = at + bytes.len();
+        let problem = if datagram.conflicts(at, &bytes) {
+            Some("overlaps an earlier fragment with different data")
+        } else if key.src.is_ipv4() && end > MAX_IPV4_PAYLOAD {
+            Some("reaches past the longest possible IPv4 datagram")
+        } else if datagram.length.is_some_and(|length| end > length)
+            || (!more
+                && (datagram.length.is_some_and(|length| length != end)
+                    || datagram
+                        .pieces
+                        .iter()
+                        .any(|(&start, (_, piece))| start + piece.len() > end)))
+        {
+            Some("disagrees with the other fragments on the datagram's length")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            // the fragment's bytes and length are left out, so they can't skew the others
+            packet.info.push_str(&format!(" [fragment {}]", problem));
+            datagram.invalid.get_or_insert(problem);
+        } else if !more {
+            datagram.length.get_or_insert(end);
+        }
+        // a repeated fragment adds nothing, unless it's longer
+        if problem.is_none()
+            && datagram
+                .pieces
+                .get(&at)
+                .is_none_or(|(_, piece)| piece.len() < bytes.len())
+        {
+            self.bytes += bytes.len();
+            datagram.bytes += bytes.len();
+            if let Some((_, replaced)) = datagram.pieces.insert(at, (packet.number, bytes)) {
+                self.bytes -= replaced.len();
+                datagram.bytes -= replaced.len();
+            }
+        }
+
+        let Some(payload) = datagram.payload() else {
+            self.shed();
+            return;
+        };
+        let datagram = self.pending.remove(&key).expect("just added to");
+        self.bytes -= datagram.bytes;
+        if let Some(problem) = datagram.invalid {
+            packet
+                .info
+                .push_str(&format!(" [not reassembled: a fragment {}]", problem));
+            return;
+        }
+        let pieces: Vec<(u32, usize, usize)> = datagram
+            .pieces
+            .iter()
+            .map(|(&at, (number, piece))| (*number, at, piece.len()))
+            .collect();
+        show_packets::dissect_reassembled(packet, protocol, &payload, &pieces);
+    }
+
+    /// Gives up on datagrams that didn't arrive in full in time, as the receiver would.
+    fn expire(&mut self, now: f64) {
+        let bytes = &mut self.bytes;
+        self.pending.retain(|_, datagram| {
+            let alive = now - datagram.first_seen <= TIMEOUT_SECS;
+            if !alive {
+                *bytes -= datagram.bytes;
+            }
+            alive
+        });
+    }
+
+    /// Gives up on the oldest datagrams while too many bytes are held.
+    fn shed(&mut self) {
+        while self.bytes > MAX_PENDING_BYTES {
+            let Some(oldest) = self
+                .pending
+                .iter()
+                .min_by(|(_, a), (_, b)| a.first_seen.total_cmp(&b.first_seen))
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            if let Some(datagram) = self.pending.remove(&oldest) {
+                self.bytes -= datagram.bytes;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::{frame::RawFrame, show_packets::dissect};
+
+    /// An Ethernet frame with an IPv4 fragment of a UDP datagram from 10.0.0.1 to 10.0.0.2.
+    fn fragment(id: u16, at: usize, more: bool, bytes: &[u8]) -> Vec<u8> {
+        let mut frame = vec![2, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 1, 0x08, 0x00, 0x45, 0];
+        frame.extend(((20 + bytes.len()) as u16).to_be_bytes());
+        frame.extend(id.to_be_bytes());
+        frame.extend(((at / 8) as u16 | if more { 0x2000 } else { 0 }).to_be_bytes());
+        frame.extend([64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend(bytes);
+        frame
+    }
+
+    /// A UDP datagram from port 12345 to 9, `len` bytes long with its header.
+    fn datagram(len: usize) -> Vec<u8> {
+        let mut bytes = vec![0x30, 0x39, 0, 9];
+        bytes.extend((len as u16).to_be_bytes());
+        bytes.extend([0, 0]);
+        bytes.extend((8..len).map(|i| i as u8));
+        bytes
+    }
+
+    /// Dissects each frame, captured at the given millisecond, and passes it through one
+    /// reassembler.
+    fn reassemble(frames: Vec<(Vec<u8>, i64)>) -> (Reassembler, Vec<FormatedPacket>) {
+        let mut reassembler = Reassembler::default();
+        let mut count = 0;
+        let packets = frames
+            .into_iter()
+            .map(|(data, millis)| {
+                let frame = RawFrame::new(
+                    "eth0",
+                    &data,
+                    millis / 1000,
+                    (millis % 1000) as u32 * 1_000_000,
+                );
+                let (mut packet, _) = dissect(frame, &mut count);
+                reassembler.add(&mut packet);
+                packet
+            })
+            .collect();
+        (reassembler, packets)
+    }
+
+    #[test]
+    fn reassembles_in_order() {
+        let udp = datagram(3000);
+        let (reassembler, packets) = reassemble(vec![
+            (fragment(1, 0, true, &udp[..1480]), 0),
+            (fragment(1, 1480, true, &udp[1480..2960]), 0),
+            (fragment(1, 2960, false, &udp[2960..]), 0),
+        ]);
+        assert_eq!(packets[0].protocol, "IPv4");
+        assert_eq!(packets[1].protocol, "IPv4");
+        assert_eq!(packets[2].protocol, "UDP");
+        assert_eq!(packets[2].lenght, 3000);
+        assert!(packets[2].info.ends_with("[reassembled from frames 1..3]"));
+        assert!(reassembler.pending.is_empty());
+        assert_eq!(reassembler.bytes, 0);
+    }
+
+    #[test]
+    fn reassembles_out_of_order() {
+        let udp = datagram(3000);
+        let (reassembler, packets) = reassemble(vec![
+            (fragment(2, 2960, false, &udp[2960..]), 0),
+            (fragment(2, 0, true, &udp[..1480]), 0),
+            (fragment(2, 0, true, &udp[..1480]), 0),
+            (fragment(2, 1480, true, &udp[1480..2960]), 0),
+        ]);
+        assert_eq!(packets[3].protocol, "UDP");
+        assert!(packets[3].info.ends_with("[reassembled from frames 1..4]"));
+        let tree = &packets[3].detailed_info.as_ref().unwrap().tree;
+        let fragments = tree
+            .iter()
+            .find(|node| node.name == "ip.fragments")
+            .unwrap();
+        assert!(fragments
+            .display
+            .starts_with("3 IPv4 fragments (3000 bytes)"));
+        assert!(reassembler.pending.is_empty());
+    }
+
+    #[test]
+    fn rejects_overlaps_with_different_data() {
+        let udp = datagram(3000);
+        let mut changed = udp[1400..2960].to_vec();
+        changed[10] ^= 1;
+        let (reassembler, packets) = reassemble(vec![
+            (fragment(3, 0, true, &udp[..1480]), 0),
+            (fragment(3, 1400, true, &changed), 0),
+            (fragment(3, 1480, true, &udp[1480..2960]), 0),
+            (fragment(3, 2960, false, &udp[2960..]), 0),
+        ]);
+        assert!(packets[1].info.contains("overlaps an earlier fragment"));
+        assert_eq!(packets[3].protocol, "IPv4");
+        assert!(packets[3]
+            .info
+            .contains("[not reassembled: a fragment overlaps"));
+        assert!(reassembler.pending.is_empty());
+    }
+
+    #[test]
+    fn rejects_lengths_that_disagree() {
+        let udp = datagram(100);
+        // a last fragment ending inside bytes already seen must not be trusted for the length
+        let (reassembler, packets) = reassemble(vec![
+            (fragment(4, 0, true, &udp[..96]), 0),
+            (fragment(4, 48, false, &udp[48..56]), 0),
+            (fragment(4, 96, false, &udp[96..]), 0),
+        ]);
+        assert!(packets[1]
+            .info
+            .contains("disagrees with the other fragments"));
+        assert!(packets[2]
+            .info
+            .contains("[not reassembled: a fragment disagrees"));
+        assert!(reassembler.pending.is_empty());
+    }
+
+    #[test]
+    fn gives_up_after_the_timeout() {
+        let udp = datagram(3000);
+        let (reassembler, packets) = reassemble(vec![
+            (fragment(5, 0, true, &udp[..1480]), 0),
+            (
+                fragment(5, 1480, false, &udp[1480..]),
+                (TIMEOUT_SECS as i64 + 1) * 1000,
+            ),
+        ]);
+        assert_eq!(packets[1].protocol, "IPv4");
+        assert!(!packets[1].info.contains("reassembled"));
+        // only the late fragment is left, waiting for the start of a new datagram
+        assert_eq!(reassembler.pending.len(), 1);
+        assert_eq!(reassembler.bytes, 1520);
+    }
+
+    #[test]
+    fn sheds_the_oldest_datagrams_past_the_limit() {
+        let piece = vec![0; 60_000];
+        let count = MAX_PENDING_BYTES / piece.len() + 2;
+        let (reassembler, _) = reassemble(
+            (0..count)
+                .map(|id| (fragment(id as u16, 0, true, &piece), id as i64 * 100))
+                .collect(),
+        );
+        assert!(reassembler.bytes <= MAX_PENDING_BYTES);
+        assert_eq!(reassembler.bytes, reassembler.pending.len() * piece.len());
+        assert!(!reassembler.pending.keys().any(|key| key.id == 0));
+        assert!(reassembler
+            .pending
+            .keys()
+            .any(|key| key.id == count as u32 - 1));
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use super::{frame::RawFrame, pcap::PcapngWriter, store::StoreLimits};
+
+/// While the files hold the whole capture, the store only keeps this many of the newest
+/// packets, from this many seconds back at most.
+const STORE_WINDOW_PACKETS: usize = 100_000;
+const STORE_WINDOW_SECS: f64 = 300.0;
+
+/// Where and how a capture is written to disk as it runs, like dumpcap's ring buffer.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RingBufferOptions {
+    pub directory: PathBuf,
+    /// Starts a new file once the current one reaches this size.
+    pub max_file_bytes: Option<u64>,
+    /// Starts a new file once the current one is this old.
+    pub max_file_secs: Option<f64>,
+    /// Deletes the oldest file once there are more than this many. Unset keeps them all.
+    pub max_files: Option<usize>,
+}
+
+/// Writes frames to a rotating set of pcapng files. Each file is a complete capture of its
+/// own, so any of them can be imported on its own while the others are still being written.
+pub struct RingWriter {
+    options: RingBufferOptions,
+    /// Goes into every file name, e.g. the interface captured on.
+    name: String,
+    current: Option<(PcapngWriter<BufWriter<File>>, Instant)>,
+    /// `max_file_secs`, checked.
+    max_file_age: Option<Duration>,
+    /// Files this writer created that are still on disk, oldest first.
+    files: VecDeque<PathBuf>,
+    next_index: u32,
+}
+
+impl RingWriter {
+    /// Checks the options and that the directory is usable up front; the first file is only
+    /// created with the first frame.
+    pub fn new(options: RingBufferOptions, name: &str) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message);
+        if options.max_file_bytes == Some(0) {
+            return Err(invalid("The ring buffer's file size must be above zero"));
+        }
+        if options.max_files == Some(0) {
+            return Err(invalid("The ring buffer must keep at least one file"));
+        }
+        let max_file_age = options
+            .max_file_secs
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs)
+                    .ok()
+                    .filter(|age| !age.is_zero())
+                    .ok_or_else(|| invalid("The ring buffer's file duration must be above zero"))
+            })
+            .transpose()?;
+        fs::create_dir_all(&options.directory)?;
+        Ok(Self {
+            options,
+            name: name.replace(|c: char| !c.is_ascii_alphanumeric(), "-"),
+            current: None,
+            max_file_age,
+            files: VecDeque::new(),
+            next_index: 1,
+        })
+    }
+
+    pub fn write(&mut self, frame: &RawFrame) -> io::Result<()> {
+        if self.is_due() {
+            self.rotate()?;
+        }
+        let (writer, _) = match self.current.as_mut() {
+            Some(current) => current,
+            None => self.open()?,
+        };
+        writer.write_record(&frame.to_record())
+    }
+
+    /// Pushes buffered frames to disk, so the newest file is readable up to here.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some((writer, _)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the current file once it's too old, even if no frame came to write since. The
+    /// capture calls this between frames too, so a quiet link still rotates on time.
+    pub fn rotate_if_due(&mut self) -> io::Result<()> {
+        match self.is_due() {
+            true => self.rotate(),
+            false => Ok(()),
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        let Some((writer, opened_at)) = &self.current else {
+            return false;
+        };
+        self.options
+            .max_file_bytes
+            .is_some_and(|max| writer.bytes_written() >= max)
+            || self
+                .max_file_age
+                .is_some_and(|age| opened_at.elapsed() >= age)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some((mut writer, _)) = self.current.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> io::Result<&mut (PcapngWriter<BufWriter<File>>, Instant)> {
+        let path = self.options.directory.join(format!(
+            "netwatch_{}_{:05}_{}.pcapng",
+            self.name,
+            self.next_index,
+            Local::now().format("%Y%m%d%H%M%S")
+        ));
+        let writer = PcapngWriter::new(BufWriter::new(File::create(&path)?))?;
+        log::info!("writing capture to {}", path.display());
+        self.next_index += 1;
+        self.files.push_back(path);
+        while self
+            .options
+            .max_files
+            .is_some_and(|max| self.files.len() > max)
+        {
+            if let Some(oldest) = self.files.pop_front() {
+                if let Err(e) = fs::remove_file(&oldest) {
+                    log::warn!("failed to remove {}: {}", oldest.display(), e);
+                }
+            }
+        }
+        Ok(self.current.insert((writer, Instant::now())))
+    }
+}
+
+/// `limits` narrowed to the recent window the store keeps in ring mode. Tighter limits the
+/// user set are kept.
+pub fn window_limits(limits: StoreLimits) -> StoreLimits {
+    StoreLimits {
+        max_packets: Some(
+            limits
+                .max_packets
+                .map_or(STORE_WINDOW_PACKETS, |max| max.min(STORE_WINDOW_PACKETS)),
+        ),
+        max_age_secs: Some(
+            limits
+                .max_age_secs
+                .map_or(STORE_WINDOW_SECS, |max| max.min(STORE_WINDOW_SECS)),
+        ),
+        ..limits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(directory: PathBuf) -> RingBufferOptions {
+        RingBufferOptions {
+            directory,
+            max_file_bytes: None,
+            max_file_secs: None,
+            max_files: None,
+        }
+    }
+
+    #[test]
+    fn rejects_options_that_cant_work() {
+        let directory = std::env::temp_dir().join("netwatch-ring-options");
+        for options in [
+            RingBufferOptions {
+                max_file_bytes: Some(0),
+                ..options(directory.clone())
+            },
+            RingBufferOptions {
+                max_files: Some(0),
+                ..options(directory.clone())
+            },
+            RingBufferOptions {
+                max_file_secs: Some(-1.0),
+                ..options(directory.clone())
+            },
+            RingBufferOptions {
+                max_file_secs: Some(0.0),
+                ..options(directory.clone())
+            },
+            RingBufferOptions {
+                max_file_secs: Some(f64::NAN),
+                ..options(directory.clone())
+            },
+            RingBufferOptions {
+                max_file_secs: Some(f64::INFINITY),
+                ..options(directory.clone())
+            },
+        ] {
+            let err = RingWriter::new(options, "eth0").err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        let options = RingBufferOptions {
+            max_file_bytes: Some(1),
+            max_file_secs: Some(0.5),
+            max_files: Some(1),
+            ..options(directory)
+        };
+        assert!(RingWriter::new(options, "eth0").is_ok());
+    }
+
+    #[test]
+    fn rotates_a_quiet_file_on_time() {
+        let directory = std::env::temp_dir().join(format!("netwatch-ring-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let options = RingBufferOptions {
+            max_file_secs: Some(0.05),
+            ..options(directory.clone())
+        };
+        let mut writer = RingWriter::new(options, "eth0").unwrap();
+        let frame = RawFrame::new("eth0", &[0; 60], 1, 0);
+        writer.write(&frame).unwrap();
+        writer.rotate_if_due().unwrap();
+        assert!(writer.current.is_some());
+
+        std::thread::sleep(Duration::from_millis(60));
+        writer.rotate_if_due().unwrap();
+        assert!(writer.current.is_none());
+        writer.write(&frame).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn keeps_a_recent_window_in_the_store() {
+        let limits = window_limits(StoreLimits {
+            max_packets: None,
+            max_bytes: None,
+            max_age_secs: Some(10.0),
+            spill_path: None,
+        });
+        assert_eq!(limits.max_packets, Some(STORE_WINDOW_PACKETS));
+        assert_eq!(limits.max_age_secs, Some(10.0));
+        assert_eq!(limits.max_bytes, None);
+
+        let limits = window_limits(StoreLimits {
+            max_packets: Some(STORE_WINDOW_PACKETS * 2),
+            ..StoreLimits::default()
+        });
+        assert_eq!(limits.max_packets, Some(STORE_WINDOW_PACKETS));
+        assert_eq!(limits.max_age_secs, Some(STORE_WINDOW_SECS));
+    }
+}
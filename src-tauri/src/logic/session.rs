@@ -0,0 +1,183 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    stats::CaptureCounters,
+    stop::StopReason,
+    store::{PacketStore, StoreLimits},
+    trigger::TriggerBuffer,
+};
+
+pub type SessionId = u32;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: SessionId,
+    /// Interface name for live captures, file name for imports and replays.
+    pub interface: String,
+    pub filter: Option<String>,
+    pub started_at: f64,
+    pub stopped_at: Option<f64>,
+    pub running: bool,
+    pub stop_reason: Option<StopReason>,
+    /// Why the capture stopped on its own, if it failed.
+    pub error: Option<String>,
+}
+
+/// One capture: where its packets come from and the store they go to.
+pub struct Session {
+    pub id: SessionId,
+    pub interface: String,
+    pub filter: Option<String>,
+    pub started_at: f64,
+    stopped_at: Mutex<Option<f64>>,
+    running: AtomicBool,
+    stop_reason: Mutex<Option<StopReason>>,
+    error: Mutex<Option<String>>,
+    store: Mutex<PacketStore>,
+    /// Set for captures started in pre-trigger mode.
+    trigger: Mutex<Option<TriggerBuffer>>,
+    counters: CaptureCounters,
+}
+
+impl Session {
+    /// The flag the capture loop polls; cleared by `stop`.
+    pub fn running(&self) -> &AtomicBool {
+        &self.running
+    }
+
+    /// Asks the capture to stop. Only the first reason given is kept. The time is recorded
+    /// once the loop has actually exited, through `mark_stopped`.
+    pub fn stop(&self, reason: StopReason) {
+        self.stop_reason.lock().unwrap().get_or_insert(reason);
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    /// Called once nothing more will be added. A capture nobody stopped ran out of frames.
+    pub fn mark_stopped(&self) {
+        self.stop(StopReason::EndOfSource);
+        self.stopped_at.lock().unwrap().get_or_insert_with(now);
+    }
+
+    /// Records why the capture ended early. It still has to be marked stopped.
+    pub fn fail(&self, error: String) {
+        *self.error.lock().unwrap() = Some(error);
+        self.stop(StopReason::Error);
+    }
+
+    pub fn counters(&self) -> &CaptureCounters {
+        &self.counters
+    }
+
+    pub fn store(&self) -> MutexGuard<'_, PacketStore> {
+        match self.store.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub fn set_trigger(&self, buffer: TriggerBuffer) {
+        *self.trigger.lock().unwrap() = Some(buffer);
+    }
+
+    pub fn trigger(&self) -> MutexGuard<'_, Option<TriggerBuffer>> {
+        match self.trigger.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub fn info(&self) -> SessionInfo {
+        SessionInfo {
+            id: self.id,
+            interface: self.interface.clone(),
+            filter: self.filter.clone(),
+            started_at: self.started_at,
+            stopped_at: *self.stopped_at.lock().unwrap(),
+            running: self.running.load(Ordering::SeqCst),
+            stop_reason: *self.stop_reason.lock().unwrap(),
+            error: self.error.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Every capture of this run of the app, live or finished, by ID.
+#[derive(Default)]
+pub struct SessionManager {
+    sessions: Mutex<BTreeMap<SessionId, Arc<Session>>>,
+    next_id: AtomicU32,
+    /// Limits new sessions start with, minus any spill file, which belongs to one session.
+    limits: Mutex<StoreLimits>,
+}
+
+impl SessionManager {
+    /// Registers a new running session with an empty store.
+    pub fn create(&self, interface: String, filter: Option<String>) -> Arc<Session> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut store = PacketStore::default();
+        // no spill file, so this can't fail
+        let _ = store.set_limits(self.limits.lock().unwrap().clone());
+        let session = Arc::new(Session {
+            id,
+            interface,
+            filter,
+            started_at: now(),
+            stopped_at: Mutex::new(None),
+            running: AtomicBool::new(true),
+            stop_reason: Mutex::new(None),
+            error: Mutex::new(None),
+            store: Mutex::new(store),
+            trigger: Mutex::new(None),
+            counters: CaptureCounters::default(),
+        });
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(id, Arc::clone(&session));
+        session
+    }
+
+    pub fn get(&self, id: SessionId) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<SessionInfo> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|session| session.info())
+            .collect()
+    }
+
+    /// Applies `limits` to a session and makes them the default for sessions created later.
+    pub fn set_limits(&self, session: &Session, limits: StoreLimits) -> std::io::Result<()> {
+        session.store().set_limits(limits.clone())?;
+        *self.limits.lock().unwrap() = StoreLimits {
+            spill_path: None,
+            ..limits
+        };
+        Ok(())
+    }
+
+    /// Stops the session and drops it, freeing its packets once the capture thread lets go.
+    pub fn remove(&self, id: SessionId) -> Option<Arc<Session>> {
+        let session = self.sessions.lock().unwrap().remove(&id)?;
+        session.stop(StopReason::User);
+        Some(session)
+    }
+}
+
+/// The current time as a capture timestamp.
+pub fn now() -> f64 {
+    let now = Local::now();
+    now.timestamp() as f64 + now.timestamp_subsec_nanos() as f64 / 1e9
+}
@@ -1,16 +1,16 @@
-use std::net::IpAddr;
+use std::{fmt::Write, net::IpAddr};
 
-use chrono::Local;
+use chrono::{DateTime, SecondsFormat};
 use pnet::packet::{
     arp::{ArpOperation, ArpOperations, ArpPacket},
-    ethernet::{EtherTypes, EthernetPacket},
+    ethernet::EtherTypes,
     icmp::{IcmpPacket, IcmpType},
     icmpv6::{Icmpv6Packet, Icmpv6Type, Icmpv6Types},
     ip::{
         IpNextHeaderProtocol,
         IpNextHeaderProtocols::{self, Tlsp},
     },
-    ipv4::Ipv4Packet,
+    ipv4::{Ipv4Flags, Ipv4Packet},
     ipv6::Ipv6Packet,
     tcp::{TcpFlags, TcpPacket},
     udp::UdpPacket,
@@ -18,18 +18,86 @@ use pnet::packet::{
 };
 use serde::{Deserialize, Serialize};
 
+use super::{
+    dissectors::{self, Dissection, Transport},
+    frame::RawFrame,
+    link::{self, VlanTag, VLAN_TAG_LEN},
+    protocols,
+    stats::SkipReason,
+    tree::{self, ProtocolNode},
+};
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct FormatedPacket {
     pub number: u32,
+    /// Capture time in UTC, RFC 3339 with nanoseconds.
     pub time: String,
-    pub src_ip: IpAddr,
-    pub dst_ip: IpAddr,
+    /// Unset for frames that don't carry IP, or ARP, addresses.
+    pub src_ip: Option<IpAddr>,
+    pub dst_ip: Option<IpAddr>,
     pub protocol: String,
     pub lenght: usize,
     pub info: String,
     pub detailed_info: Option<DetailedInfo>,
-    #[serde(skip)]
+    /// The frame's VLAN tags, outermost first. Empty for untagged frames.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vlans: Vec<VlanTag>,
+    /// Capture time as seconds since the epoch. Use `frame` for the exact time.
+    #[serde(default)]
     pub timestamp: f64,
+    /// Seconds since the first packet of the capture, filled in by the store.
+    #[serde(default)]
+    pub relative: f64,
+    /// Seconds since the packet captured before this one, filled in by the store.
+    #[serde(default)]
+    pub delta: f64,
+    #[serde(default)]
+    pub frame: RawFrame,
+}
+
+/// What the packet table shows of a packet. The details, protocol tree and frame bytes are
+/// left out, to be fetched for the one packet that is selected.
+#[derive(Clone, Serialize, Debug)]
+pub struct PacketRow {
+    pub number: u32,
+    pub time: String,
+    pub src_ip: Option<IpAddr>,
+    pub dst_ip: Option<IpAddr>,
+    /// Shown in place of the IP addresses of frames without them, e.g. LLDP.
+    pub src_mac: Option<String>,
+    pub dst_mac: Option<String>,
+    pub protocol: String,
+    pub lenght: usize,
+    pub info: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub vlans: Vec<VlanTag>,
+    pub relative: f64,
+    pub delta: f64,
+    /// The exact capture time, as in the frame.
+    pub ts_sec: i64,
+    pub ts_nsec: u32,
+}
+
+impl From<&FormatedPacket> for PacketRow {
+    fn from(packet: &FormatedPacket) -> Self {
+        let details = packet.detailed_info.as_ref();
+        Self {
+            number: packet.number,
+            time: packet.time.clone(),
+            src_ip: packet.src_ip,
+            dst_ip: packet.dst_ip,
+            src_mac: details.map(|info| info.src_mac.clone()),
+            dst_mac: details.map(|info| info.dst_mac.clone()),
+            protocol: packet.protocol.clone(),
+            lenght: packet.lenght,
+            info: packet.info.clone(),
+            vlans: packet.vlans.clone(),
+            relative: packet.relative,
+            delta: packet.delta,
+            ts_sec: packet.frame.ts_sec,
+            ts_nsec: packet.frame.ts_nsec,
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -41,48 +109,241 @@ pub struct DetailedInfo {
     pub payload_length: usize,
     pub packet_length: usize,
     pub payload_data: String,
+    /// The layers of the frame and their fields, outermost first.
+    #[serde(default)]
+    pub tree: Vec<ProtocolNode>,
 }
 
 impl FormatedPacket {
     fn new(
         number: u32,
-        src_ip: IpAddr,
-        dst_ip: IpAddr,
+        src_ip: Option<IpAddr>,
+        dst_ip: Option<IpAddr>,
         protocol: String,
         lenght: usize,
         info: String,
     ) -> Self {
-        let now = Local::now();
-        let time = now.time().format("%H:%M:%S").to_string();
-        let timestamp = now.timestamp() as f64 + now.timestamp_subsec_nanos() as f64 / 1e9;
-
         Self {
             number,
-            time,
+            time: String::new(),
             src_ip,
             dst_ip,
             lenght,
             protocol,
             info,
             detailed_info: None,
-            timestamp,
+            vlans: Vec::new(),
+            timestamp: 0.0,
+            relative: 0.0,
+            delta: 0.0,
+            frame: RawFrame::default(),
         }
     }
+
+    /// Runs the current dissectors over the stored frame again, keeping the packet number.
+    pub fn redissect(&self) -> FormatedPacket {
+        let mut count_fp = self.number.saturating_sub(1);
+        process_frame(self.frame.clone(), &mut count_fp)
+    }
+}
+
+/// Dissects one frame into a [`FormatedPacket`]. Live capture and file import both go
+/// through here, so imported traffic looks exactly like traffic seen on the wire.
+pub fn process_frame(frame: RawFrame, count_fp: &mut u32) -> FormatedPacket {
+    dissect(frame, count_fp).0
+}
+
+/// Like [`process_frame`], but also says why a frame could only be partly dissected. Every
+/// frame becomes a packet, labelled as well as we can, so nothing on the wire goes unseen.
+pub fn dissect(frame: RawFrame, count_fp: &mut u32) -> (FormatedPacket, Option<SkipReason>) {
+    let mut layers = vec![tree::frame(&frame, *count_fp + 1)];
+    let Some(link) = link::decode(frame.link_type, &frame.data) else {
+        let (info, reason) = if link::is_supported(frame.link_type) {
+            (
+                "truncated link-layer header".to_string(),
+                SkipReason::Malformed,
+            )
+        } else {
+            (
+                format!("link type {}", frame.link_type),
+                SkipReason::UnsupportedLinkType,
+            )
+        };
+        let mut fp = undissected(
+            count_fp,
+            link::name(frame.link_type).to_string(),
+            None,
+            &frame.data,
+            info,
+        );
+        fp.detailed_info = Some(DetailedInfo {
+            src_mac: String::new(),
+            dst_mac: String::new(),
+            frame_type: link::name(frame.link_type).to_string(),
+            packet_length: frame.data.len(),
+            payload_length: frame.data.len(),
+            interface: frame.interface.clone(),
+            payload_data: get_payload_data(&frame.data),
+            tree: layers
+                .into_iter()
+                .chain(tree::data(&frame.data, 0))
+                .collect(),
+        });
+        return (stamp(fp, frame), Some(reason));
+    };
+    let header_len = frame.data.len() - link.payload.len();
+    let tags_at = header_len - link.vlans.len() * VLAN_TAG_LEN;
+    layers.extend(tree::link(frame.link_type, &frame.data[..tags_at], &link));
+    for (i, tag) in link.vlans.iter().enumerate() {
+        let next = link
+            .vlans
+            .get(i + 1)
+            .map_or(link.ethertype.0, |inner| inner.tpid);
+        layers.push(tree::vlan(tag, next, tags_at + i * VLAN_TAG_LEN));
+    }
+    let (mut fp, reason) = match link.ethertype {
+        EtherTypes::Arp | EtherTypes::Rarp => {
+            let name = if link.ethertype == EtherTypes::Arp {
+                "ARP"
+            } else {
+                "RARP"
+            };
+            match process_arp_packet(
+                link.payload,
+                count_fp,
+                name.to_string(),
+                header_len,
+                &mut layers,
+            ) {
+                Some(fp) => (fp, None),
+                None => {
+                    layers.extend(tree::data(link.payload, header_len));
+                    (
+                        truncated(count_fp, name, None, link.payload),
+                        Some(SkipReason::Malformed),
+                    )
+                }
+            }
+        }
+        EtherTypes::Ipv4 => {
+            match process_ipv4_packet(link.payload, count_fp, header_len, &mut layers) {
+                Some(dissected) => dissected,
+                None => {
+                    layers.extend(tree::data(link.payload, header_len));
+                    (
+                        truncated(count_fp, "IPv4", None, link.payload),
+                        Some(SkipReason::Malformed),
+                    )
+                }
+            }
+        }
+        EtherTypes::Ipv6 => {
+            match process_ipv6_packet(link.payload, count_fp, header_len, &mut layers) {
+                Some(dissected) => dissected,
+                None => {
+                    layers.extend(tree::data(link.payload, header_len));
+                    (
+                        truncated(count_fp, "IPv6", None, link.payload),
+                        Some(SkipReason::Malformed),
+                    )
+                }
+            }
+        }
+        ethertype => {
+            let application =
+                dissectors::registry().for_ethertype(ethertype.0, link.payload, header_len);
+            if let Some((protocol, info)) =
+                process_application(application, link.payload, header_len, &mut layers)
+            {
+                let fp = undissected(count_fp, protocol.to_string(), None, link.payload, info);
+                (fp, None)
+            } else {
+                let protocol = protocols::ether_type_name(ethertype.0)
+                    .map_or_else(|| format!("0x{:04x}", ethertype.0), str::to_string);
+                let info = format!(
+                    "ethertype 0x{:04x}, {} bytes",
+                    ethertype.0,
+                    link.payload.len()
+                );
+                (
+                    undissected(count_fp, protocol, None, link.payload, info),
+                    Some(SkipReason::UnhandledEtherType),
+                )
+            }
+        }
+    };
+
+    fp.vlans = link.vlans;
+    fp.detailed_info = Some(DetailedInfo {
+        src_mac: link.src.unwrap_or_default(),
+        dst_mac: link.dst.unwrap_or_default(),
+        frame_type: link.ethertype.to_string(),
+        packet_length: frame.data.len(),
+        payload_length: link.payload.len(),
+        interface: frame.interface.clone(),
+        payload_data: get_payload_data(link.payload),
+        tree: layers,
+    });
+    (stamp(fp, frame), reason)
+}
+
+/// Gives the packet the frame it came from, and its capture time.
+fn stamp(mut fp: FormatedPacket, frame: RawFrame) -> FormatedPacket {
+    if let Some(at) = DateTime::from_timestamp(frame.ts_sec, frame.ts_nsec) {
+        fp.time = at.to_rfc3339_opts(SecondsFormat::Nanos, true);
+    }
+    fp.timestamp = frame.timestamp();
+    fp.frame = frame;
+    fp
+}
+
+/// A packet for a protocol we only know the name of.
+fn undissected(
+    count_fp: &mut u32,
+    protocol: String,
+    addresses: Option<(IpAddr, IpAddr)>,
+    payload: &[u8],
+    info: String,
+) -> FormatedPacket {
+    *count_fp += 1;
+    FormatedPacket::new(
+        *count_fp,
+        addresses.map(|(src, _)| src),
+        addresses.map(|(_, dst)| dst),
+        protocol,
+        payload.len(),
+        info,
+    )
+}
+
+/// A packet whose `protocol` header is cut short.
+fn truncated(
+    count_fp: &mut u32,
+    protocol: &str,
+    addresses: Option<(IpAddr, IpAddr)>,
+    payload: &[u8],
+) -> FormatedPacket {
+    let info = format!("truncated {} header, {} bytes", protocol, payload.len());
+    undissected(count_fp, protocol.to_string(), addresses, payload, info)
 }
 
 pub fn get_payload_data(payload: &[u8]) -> String {
-    let mut result = String::new();
-    let mut offset = 0;
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
     let line_width = 16;
+    // this runs for every captured frame, so avoid a `format!` per byte
+    let mut result = String::with_capacity(payload.len().div_ceil(line_width) * 75);
+    let mut offset = 0;
 
     while offset < payload.len() {
         let end = std::cmp::min(offset + line_width, payload.len());
         let line = &payload[offset..end];
 
-        result.push_str(&format!("{:04x}  ", offset));
+        let _ = write!(result, "{:04x}  ", offset);
 
         for (i, byte) in line.iter().enumerate() {
-            result.push_str(&format!("{:02x} ", byte));
+            result.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+            result.push(HEX_DIGITS[(byte & 0x0f) as usize] as char);
+            result.push(' ');
             if i == 7 {
                 result.push_str(" ");
             }
@@ -110,13 +371,17 @@ pub fn get_payload_data(payload: &[u8]) -> String {
     result
 }
 
+/// Dissects an ARP packet `offset` bytes into the frame, adding its layer to `tree`.
 pub fn process_arp_packet(
-    ethernet: &EthernetPacket,
+    payload: &[u8],
     count_fp: &mut u32,
     t: String,
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
 ) -> Option<FormatedPacket> {
-    if let Some(arp) = ArpPacket::new(ethernet.payload()) {
+    if let Some(arp) = ArpPacket::new(payload) {
         *count_fp += 1;
+        tree.push(tree::arp(&arp, &t, offset));
         let src_mac = arp.get_sender_hw_addr();
         let dst_mac = arp.get_target_hw_addr();
         let operation = match arp.get_operation() {
@@ -130,8 +395,8 @@ pub fn process_arp_packet(
         );
         return Some(FormatedPacket::new(
             *count_fp,
-            IpAddr::V4(arp.get_sender_proto_addr()),
-            IpAddr::V4(arp.get_target_proto_addr()),
+            Some(IpAddr::V4(arp.get_sender_proto_addr())),
+            Some(IpAddr::V4(arp.get_target_proto_addr())),
             t,
             arp.packet().len(),
             info,
@@ -140,91 +405,431 @@ pub fn process_arp_packet(
     None
 }
 
+/// Dissects an IPv6 packet `offset` bytes into the frame, adding its layers to `tree`.
+/// `None` if the header itself is cut short; otherwise a packet, and why its payload
+/// couldn't be dissected if it couldn't.
 pub fn process_ipv6_packet(
-    ethernet: &EthernetPacket,
+    payload: &[u8],
     count_fp: &mut u32,
-) -> Option<FormatedPacket> {
-    if let Some(ipv6) = Ipv6Packet::new(ethernet.payload()) {
-        let payload = ipv6.payload();
-        let pr = process_ip_traffic(payload, ipv6.get_next_header());
-        if let Some(data) = pr.0 {
-            *count_fp += 1;
-            return Some(FormatedPacket::new(
-                *count_fp,
-                IpAddr::V6(ipv6.get_source()),
-                IpAddr::V6(ipv6.get_destination()),
-                pr.1,
-                payload.len(),
-                data,
-            ));
-        }
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> Option<(FormatedPacket, Option<SkipReason>)> {
+    let ipv6 = Ipv6Packet::new(payload)?;
+    let layer = tree::ipv6(&ipv6, offset);
+    let offset = offset + layer.length;
+    tree.push(layer);
+    let addresses = (
+        IpAddr::V6(ipv6.get_source()),
+        IpAddr::V6(ipv6.get_destination()),
+    );
+    let payload = ipv6_payload(&ipv6, payload);
+    let Some(mut extensions) = ipv6_extensions(payload, ipv6.get_next_header(), offset) else {
+        tree.extend(tree::data(payload, offset));
+        return Some((
+            truncated(count_fp, "IPv6", Some(addresses), payload),
+            Some(SkipReason::Malformed),
+        ));
+    };
+    let offset = offset + extensions.length;
+    tree.append(&mut extensions.layers);
+    if let Some(fragment) = ipv6_fragment(&ipv6, payload, &extensions) {
+        return Some(process_fragment(&fragment, count_fp, offset, tree));
     }
-    None
+    Some(process_ip_traffic(
+        &payload[extensions.length..],
+        extensions.next,
+        addresses,
+        count_fp,
+        offset,
+        tree,
+    ))
 }
 
+/// Dissects an IPv4 packet, like [`process_ipv6_packet`].
 pub fn process_ipv4_packet(
-    ethernet: &EthernetPacket,
+    payload: &[u8],
     count_fp: &mut u32,
-) -> Option<FormatedPacket> {
-    if let Some(ipv4) = Ipv4Packet::new(ethernet.payload()) {
-        let payload = ipv4.payload();
-        let pr = process_ip_traffic(payload, ipv4.get_next_level_protocol());
-        if let Some(data) = pr.0 {
-            *count_fp += 1;
-            return Some(FormatedPacket::new(
-                *count_fp,
-                IpAddr::V4(ipv4.get_source()),
-                IpAddr::V4(ipv4.get_destination()),
-                pr.1,
-                payload.len(),
-                data,
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> Option<(FormatedPacket, Option<SkipReason>)> {
+    let ipv4 = Ipv4Packet::new(payload)?;
+    let layer = tree::ipv4(&ipv4, offset);
+    let offset = offset + layer.length;
+    tree.push(layer);
+    if let Some(fragment) = ipv4_fragment(&ipv4, payload) {
+        return Some(process_fragment(&fragment, count_fp, offset, tree));
+    }
+    let addresses = (
+        IpAddr::V4(ipv4.get_source()),
+        IpAddr::V4(ipv4.get_destination()),
+    );
+    Some(process_ip_traffic(
+        ipv4.payload(),
+        ipv4.get_next_level_protocol(),
+        addresses,
+        count_fp,
+        offset,
+        tree,
+    ))
+}
+
+/// A piece of an IP datagram that was split on the way, and where it goes in the whole.
+pub struct Fragment<'a> {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    /// What the whole datagram carries.
+    pub protocol: IpNextHeaderProtocol,
+    pub id: u32,
+    /// Where `payload` goes in the datagram's payload, in bytes.
+    pub offset: usize,
+    /// Unset on the last fragment.
+    pub more: bool,
+    pub payload: &'a [u8],
+}
+
+/// The IP fragment `frame` carries, if it carries one, for reassembly. Whole datagrams,
+/// IPv6 atomic fragments included, are `None`.
+pub fn fragment(frame: &RawFrame) -> Option<Fragment<'_>> {
+    let link = link::decode(frame.link_type, &frame.data)?;
+    match link.ethertype {
+        EtherTypes::Ipv4 => ipv4_fragment(&Ipv4Packet::new(link.payload)?, link.payload),
+        EtherTypes::Ipv6 => {
+            let ipv6 = Ipv6Packet::new(link.payload)?;
+            let payload = ipv6_payload(&ipv6, link.payload);
+            let extensions = ipv6_extensions(payload, ipv6.get_next_header(), 0)?;
+            ipv6_fragment(&ipv6, payload, &extensions)
+        }
+        _ => None,
+    }
+}
+
+/// `packet` being the bytes `ipv4` was read from.
+fn ipv4_fragment<'a>(ipv4: &Ipv4Packet, packet: &'a [u8]) -> Option<Fragment<'a>> {
+    let offset = ipv4.get_fragment_offset() as usize * 8;
+    let more = ipv4.get_flags() & Ipv4Flags::MoreFragments != 0;
+    if offset == 0 && !more {
+        return None;
+    }
+    let start = ipv4.get_header_length() as usize * 4;
+    let end = (ipv4.get_total_length() as usize).min(packet.len());
+    Some(Fragment {
+        src: IpAddr::V4(ipv4.get_source()),
+        dst: IpAddr::V4(ipv4.get_destination()),
+        protocol: ipv4.get_next_level_protocol(),
+        id: ipv4.get_identification() as u32,
+        offset,
+        more,
+        payload: packet.get(start..end).unwrap_or_default(),
+    })
+}
+
+/// `payload` being what follows the fixed header, extension headers included.
+fn ipv6_fragment<'a>(
+    ipv6: &Ipv6Packet,
+    payload: &'a [u8],
+    extensions: &Ipv6Extensions,
+) -> Option<Fragment<'a>> {
+    let (id, offset, more) = extensions
+        .fragment
+        .filter(|&(_, offset, more)| offset != 0 || more)?;
+    Some(Fragment {
+        src: IpAddr::V6(ipv6.get_source()),
+        dst: IpAddr::V6(ipv6.get_destination()),
+        protocol: extensions.next,
+        id,
+        offset,
+        more,
+        payload: &payload[extensions.length..],
+    })
+}
+
+/// What follows the fixed IPv6 header, as far as its payload length says. Unlike
+/// [`Ipv6Packet::payload`], borrowed from the packet's bytes rather than from `ipv6`.
+fn ipv6_payload<'a>(ipv6: &Ipv6Packet, packet: &'a [u8]) -> &'a [u8] {
+    let start = Ipv6Packet::minimum_packet_size();
+    let end = (start + ipv6.get_payload_length() as usize).min(packet.len());
+    &packet[start..end]
+}
+
+/// The extension headers between an IPv6 header and the protocol it carries.
+struct Ipv6Extensions {
+    /// The protocol past them.
+    next: IpNextHeaderProtocol,
+    /// Their length; the protocol's bytes start here.
+    length: usize,
+    layers: Vec<ProtocolNode>,
+    /// The fragment header's identification, offset and more-fragments flag.
+    fragment: Option<(u32, usize, bool)>,
+}
+
+/// Walks the hop-by-hop options, routing, fragment and destination options headers at the
+/// start of `payload`, `offset` bytes into the frame. `None` if one is cut short.
+fn ipv6_extensions(
+    payload: &[u8],
+    next: IpNextHeaderProtocol,
+    offset: usize,
+) -> Option<Ipv6Extensions> {
+    let mut extensions = Ipv6Extensions {
+        next,
+        length: 0,
+        layers: Vec::new(),
+        fragment: None,
+    };
+    loop {
+        let at = extensions.length;
+        let header_len = match extensions.next {
+            IpNextHeaderProtocols::Hopopt
+            | IpNextHeaderProtocols::Ipv6Route
+            | IpNextHeaderProtocols::Ipv6Opts => (*payload.get(at + 1)? as usize + 1) * 8,
+            IpNextHeaderProtocols::Ipv6Frag => 8,
+            _ => return Some(extensions),
+        };
+        let header = payload.get(at..at + header_len)?;
+        extensions
+            .layers
+            .push(tree::ipv6_extension(extensions.next, header, offset + at));
+        if extensions.next == IpNextHeaderProtocols::Ipv6Frag {
+            let offset_and_more = u16::from_be_bytes([header[2], header[3]]);
+            extensions.fragment = Some((
+                u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+                (offset_and_more & !0b111) as usize,
+                offset_and_more & 1 != 0,
             ));
         }
+        extensions.next = IpNextHeaderProtocol(header[0]);
+        extensions.length += header_len;
+    }
+}
+
+/// A fragment is only data until the rest of its datagram turns up; see
+/// [`reassembly`](super::reassembly).
+fn process_fragment(
+    fragment: &Fragment,
+    count_fp: &mut u32,
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> (FormatedPacket, Option<SkipReason>) {
+    tree.extend(tree::data(fragment.payload, offset));
+    let protocol = protocols::ip_protocol_name(fragment.protocol.0).map_or_else(
+        || fragment.protocol.0.to_string(),
+        |name| format!("{} {}", name, fragment.protocol.0),
+    );
+    let info = format!(
+        "Fragmented IP protocol (proto={}, off={}, ID=0x{:x}{})",
+        protocol,
+        fragment.offset,
+        fragment.id,
+        if fragment.more { "" } else { ", last" }
+    );
+    let name = if fragment.src.is_ipv6() {
+        "IPv6"
+    } else {
+        "IPv4"
+    };
+    let packet = undissected(
+        count_fp,
+        name.to_string(),
+        Some((fragment.src, fragment.dst)),
+        fragment.payload,
+        info,
+    );
+    (packet, None)
+}
+
+/// Dissects the payload of a datagram reassembled from its fragments onto `packet`, the
+/// packet of the fragment that completed it. `pieces` are the frame number, offset and
+/// length of each fragment, in payload order.
+pub fn dissect_reassembled(
+    packet: &mut FormatedPacket,
+    protocol: IpNextHeaderProtocol,
+    payload: &[u8],
+    pieces: &[(u32, usize, usize)],
+) {
+    let (Some(src), Some(dst)) = (packet.src_ip, packet.dst_ip) else {
+        return;
+    };
+    let mut layers = vec![tree::fragments(src.is_ipv6(), payload.len(), pieces)];
+    let mut number = packet.number.saturating_sub(1);
+    let (reassembled, _) =
+        process_ip_traffic(payload, protocol, (src, dst), &mut number, 0, &mut layers);
+    // the datagram's bytes are spread over several frames, none of them this one's alone
+    layers.iter_mut().for_each(ProtocolNode::detach);
+    let numbers = pieces.iter().map(|(number, _, _)| *number);
+    let (first, last) = (
+        numbers.clone().min().unwrap_or(packet.number),
+        numbers.max().unwrap_or(packet.number),
+    );
+    packet.protocol = reassembled.protocol;
+    packet.lenght = reassembled.lenght;
+    packet.info = format!(
+        "{} [reassembled from frames {}..{}]",
+        reassembled.info, first, last
+    );
+    if let Some(detailed_info) = packet.detailed_info.as_mut() {
+        detailed_info.tree.extend(layers);
     }
-    None
 }
 
-fn process_ip_traffic(payload: &[u8], protocol: IpNextHeaderProtocol) -> (Option<String>, String) {
-    match protocol {
-        IpNextHeaderProtocols::Tcp => (process_tcp_packet(payload), "TCP".to_string()),
-        IpNextHeaderProtocols::Udp => (process_udp_packet(payload), "UDP".to_string()),
-        IpNextHeaderProtocols::Icmp => (process_icmp_packet(payload), "ICMP".to_string()),
-        IpNextHeaderProtocols::Icmpv6 => (process_icmpv6_packet(payload), "ICMPv6".to_string()),
-        _ => (None, "".to_string()),
+fn process_ip_traffic(
+    payload: &[u8],
+    protocol: IpNextHeaderProtocol,
+    addresses: (IpAddr, IpAddr),
+    count_fp: &mut u32,
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> (FormatedPacket, Option<SkipReason>) {
+    let (name, dissected) = match protocol {
+        IpNextHeaderProtocols::Tcp => ("TCP", process_tcp_packet(payload, offset, tree)),
+        IpNextHeaderProtocols::Udp => ("UDP", process_udp_packet(payload, offset, tree)),
+        IpNextHeaderProtocols::Icmp => (
+            "ICMP",
+            process_icmp_packet(payload, offset, tree).map(|info| ("ICMP", info)),
+        ),
+        IpNextHeaderProtocols::Icmpv6 => (
+            "ICMPv6",
+            process_icmpv6_packet(payload, offset, tree).map(|info| ("ICMPv6", info)),
+        ),
+        _ => {
+            tree.extend(tree::data(payload, offset));
+            let name = protocols::ip_protocol_name(protocol.0)
+                .map_or_else(|| format!("IP proto {}", protocol.0), str::to_string);
+            let info = format!("protocol {}, {} bytes", protocol.0, payload.len());
+            return (
+                undissected(count_fp, name, Some(addresses), payload, info),
+                Some(SkipReason::UnhandledIpProtocol),
+            );
+        }
+    };
+    match dissected {
+        Some((protocol, info)) => (
+            undissected(
+                count_fp,
+                protocol.to_string(),
+                Some(addresses),
+                payload,
+                info,
+            ),
+            None,
+        ),
+        None => {
+            tree.extend(tree::data(payload, offset));
+            (
+                truncated(count_fp, name, Some(addresses), payload),
+                Some(SkipReason::Malformed),
+            )
+        }
     }
 }
 
-fn process_tcp_packet(payload: &[u8]) -> Option<String> {
+/// The protocol and one-line summary of a TCP segment `offset` bytes into the frame: TCP's
+/// own, or those of the application protocol it carries. Its layers go into `tree`.
+fn process_tcp_packet(
+    payload: &[u8],
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> Option<(&'static str, String)> {
     if let Some(tcp) = TcpPacket::new(payload) {
+        let layer = tree::tcp(&tcp, offset);
+        let payload_offset = offset + layer.length;
+        tree.push(layer);
         let src_port = tcp.get_source();
         let dst_port = tcp.get_destination();
+        let application = dissectors::registry().for_ports(
+            Transport::Tcp,
+            (src_port, dst_port),
+            tcp.payload(),
+            payload_offset,
+        );
+        if let Some(dissected) =
+            process_application(application, tcp.payload(), payload_offset, tree)
+        {
+            return Some(dissected);
+        }
 
         let flags = get_tcp_flags(&tcp);
-        return Some(format!(
-            "src port {}, dst port {}, flags {}",
-            src_port, dst_port, flags
+        return Some((
+            "TCP",
+            format!(
+                "src port {}, dst port {}, flags {}",
+                src_port, dst_port, flags
+            ),
         ));
     }
     None
 }
 
-fn process_udp_packet(payload: &[u8]) -> Option<String> {
+fn process_udp_packet(
+    payload: &[u8],
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> Option<(&'static str, String)> {
     if let Some(udp) = UdpPacket::new(payload) {
+        let layer = tree::udp(&udp, offset);
+        let payload_offset = offset + layer.length;
+        tree.push(layer);
         let src_port = udp.get_source();
         let dst_port = udp.get_destination();
-        return Some(format!("src port {}, dst port {}", src_port, dst_port));
+        let application = dissectors::registry().for_ports(
+            Transport::Udp,
+            (src_port, dst_port),
+            udp.payload(),
+            payload_offset,
+        );
+        if let Some(dissected) =
+            process_application(application, udp.payload(), payload_offset, tree)
+        {
+            return Some(dissected);
+        }
+        return Some((
+            "UDP",
+            format!("src port {}, dst port {}", src_port, dst_port),
+        ));
     }
     None
 }
 
-fn process_icmp_packet(payload: &[u8]) -> Option<String> {
+/// Adds what a registered dissector made of `payload` to `tree`, and whatever it left as
+/// data. Returns its protocol and summary, or `None` when no dissector took the payload.
+fn process_application(
+    application: Option<(&'static str, Dissection)>,
+    payload: &[u8],
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> Option<(&'static str, String)> {
+    let Some((protocol, Dissection { info, layer })) = application else {
+        tree.extend(tree::data(payload, offset));
+        return None;
+    };
+    let end = layer.length.min(payload.len());
+    tree.push(layer);
+    tree.extend(tree::data(&payload[end..], offset + end));
+    Some((protocol, info))
+}
+
+fn process_icmp_packet(
+    payload: &[u8],
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> Option<String> {
     if let Some(icmp) = IcmpPacket::new(payload) {
+        let layer = tree::icmp(&icmp, offset);
+        let data = tree::data(&payload[layer.length..], offset + layer.length);
+        tree.push(layer);
+        tree.extend(data);
         return Some(format!("type {}", icmp.get_icmp_type().0));
     }
     None
 }
-fn process_icmpv6_packet(payload: &[u8]) -> Option<String> {
+fn process_icmpv6_packet(
+    payload: &[u8],
+    offset: usize,
+    tree: &mut Vec<ProtocolNode>,
+) -> Option<String> {
     if let Some(icmp) = Icmpv6Packet::new(payload) {
+        let layer = tree::icmpv6(&icmp, offset);
+        let data = tree::data(&payload[layer.length..], offset + layer.length);
+        tree.push(layer);
+        tree.extend(data);
         return Some(format!("type {}", icmp.get_icmpv6_type().0));
     }
     None
@@ -0,0 +1,499 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io, mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Mutex,
+    },
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
+
+use pnet::datalink::NetworkInterface;
+
+#[cfg(target_os = "linux")]
+use super::packet_socket::{CaptureMode, PacketSocket};
+use super::{
+    bpf::{BpfProgram, Framing},
+    frame::RawFrame,
+    link,
+    pcap::CaptureRecord,
+    reassembly::Reassembler,
+    show_packets::{dissect, FormatedPacket},
+    stats::{CaptureCounters, VlanCounts},
+    READ_TIMEOUT,
+};
+#[cfg(not(target_os = "linux"))]
+use super::{create_chanel, pcap::LINKTYPE_ETHERNET};
+
+/// Anything frames can be read from. Live capture, file replay and tests all feed the same
+/// dissection loop through this.
+pub trait PacketSource: Send {
+    /// Blocks until the next frame is available. `Ok(None)` means the source is exhausted.
+    fn next_frame(&mut self) -> io::Result<Option<RawFrame>>;
+
+    /// Frames the kernel dropped since the last call, for sources that can tell.
+    fn take_kernel_drops(&mut self) -> Option<u64> {
+        None
+    }
+}
+
+/// Reads from a network interface. On Linux this is a packet socket of our own, so "any" and
+/// links without Ethernet framing can be captured; elsewhere it's a pnet channel.
+pub struct LiveSource {
+    #[cfg(target_os = "linux")]
+    socket: PacketSocket,
+    #[cfg(not(target_os = "linux"))]
+    interface: String,
+    #[cfg(not(target_os = "linux"))]
+    rx: Box<dyn pnet::datalink::DataLinkReceiver>,
+    #[cfg(not(target_os = "linux"))]
+    snaplen: Option<usize>,
+}
+
+impl LiveSource {
+    /// How frames captured on `interface` are framed, which a capture filter must be
+    /// compiled for.
+    pub fn framing(interface: &NetworkInterface) -> Framing {
+        #[cfg(target_os = "linux")]
+        return CaptureMode::for_interface(interface).framing();
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = interface;
+            Framing::Ethernet
+        }
+    }
+
+    /// The `LINKTYPE_*` of frames captured on `interface`.
+    pub fn link_type(interface: &NetworkInterface) -> u32 {
+        #[cfg(target_os = "linux")]
+        return CaptureMode::for_interface(interface).link_type();
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = interface;
+            LINKTYPE_ETHERNET
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn new(
+        interface: &NetworkInterface,
+        snaplen: Option<usize>,
+        filter: Option<&BpfProgram>,
+    ) -> io::Result<Self> {
+        let mode = CaptureMode::for_interface(interface);
+        Ok(Self {
+            socket: PacketSocket::open(interface, mode, snaplen, filter)?,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(
+        interface: &NetworkInterface,
+        snaplen: Option<usize>,
+        filter: Option<&BpfProgram>,
+    ) -> io::Result<Self> {
+        if filter.is_some() {
+            return Err(io::Error::other(
+                "Capture filters are only supported on Linux",
+            ));
+        }
+        let (_, rx) = create_chanel(interface)?;
+        Ok(Self {
+            interface: interface.name.clone(),
+            rx,
+            snaplen,
+        })
+    }
+}
+
+impl PacketSource for LiveSource {
+    #[cfg(target_os = "linux")]
+    fn next_frame(&mut self) -> io::Result<Option<RawFrame>> {
+        self.socket.recv().map(Some)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn take_kernel_drops(&mut self) -> Option<u64> {
+        match self.socket.take_drops() {
+            Ok(drops) => Some(drops),
+            Err(e) => {
+                log::debug!("failed to read socket statistics: {}", e);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn next_frame(&mut self) -> io::Result<Option<RawFrame>> {
+        let data = self.rx.next()?;
+        Ok(Some(RawFrame::captured_now(
+            &self.interface,
+            data,
+            self.snaplen,
+        )))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    AsFastAsPossible,
+    /// Sleeps between frames so they come out with the gaps they were recorded with.
+    Original,
+}
+
+pub struct FileSource {
+    records: std::vec::IntoIter<CaptureRecord>,
+    speed: ReplaySpeed,
+    started: Option<(Instant, f64)>,
+    /// The next frame, read but not yet due.
+    pending: Option<RawFrame>,
+}
+
+impl FileSource {
+    pub fn new(records: Vec<CaptureRecord>, speed: ReplaySpeed) -> Self {
+        Self {
+            records: records.into_iter(),
+            speed,
+            started: None,
+            pending: None,
+        }
+    }
+
+    fn next_record(&mut self) -> Option<RawFrame> {
+        for record in self.records.by_ref() {
+            if !link::is_supported(record.link_type) {
+                log::warn!(
+                    "skipping frame with unsupported link type {}",
+                    record.link_type
+                );
+                continue;
+            }
+            return Some(RawFrame {
+                interface: record.interface.unwrap_or_else(|| "file".to_string()),
+                link_type: record.link_type,
+                ts_sec: record.ts_sec,
+                ts_nsec: record.ts_nsec,
+                orig_len: record.orig_len,
+                data: record.data,
+            });
+        }
+        None
+    }
+}
+
+impl PacketSource for FileSource {
+    fn next_frame(&mut self) -> io::Result<Option<RawFrame>> {
+        let Some(frame) = self.pending.take().or_else(|| self.next_record()) else {
+            return Ok(None);
+        };
+        if self.speed == ReplaySpeed::Original {
+            let (started, first_ts) = *self
+                .started
+                .get_or_insert_with(|| (Instant::now(), frame.timestamp()));
+            let offset = Duration::from_secs_f64((frame.timestamp() - first_ts).max(0.0));
+            if let Some(wait) = (started + offset).checked_duration_since(Instant::now()) {
+                // a long gap is waited out a read timeout at a time, like an idle link, so the
+                // replay can be stopped in the middle of it
+                sleep(wait.min(READ_TIMEOUT));
+                if wait > READ_TIMEOUT {
+                    self.pending = Some(frame);
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+            }
+        }
+        Ok(Some(frame))
+    }
+}
+
+/// Hands out frames produced in memory, e.g. fixtures or generated traffic. Only the
+/// pipeline's tests use it so far.
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct SyntheticSource {
+    generator: Box<dyn FnMut() -> Option<RawFrame> + Send>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl SyntheticSource {
+    pub fn new(generator: impl FnMut() -> Option<RawFrame> + Send + 'static) -> Self {
+        Self {
+            generator: Box::new(generator),
+        }
+    }
+
+    pub fn from_frames(frames: impl IntoIterator<Item = RawFrame>) -> Self {
+        let mut frames: VecDeque<RawFrame> = frames.into_iter().collect();
+        Self::new(move || frames.pop_front())
+    }
+}
+
+impl PacketSource for SyntheticSource {
+    fn next_frame(&mut self) -> io::Result<Option<RawFrame>> {
+        Ok((self.generator)())
+    }
+}
+
+/// Frames are handed from the reader to the dissectors, and packets from the dissectors to
+/// the store, in batches of up to this many, so locks are taken per batch rather than per packet.
+const BATCH_SIZE: usize = 1024;
+/// A partial batch is flushed after this long, so a quiet link still shows packets promptly.
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+/// Batches the reader may get ahead of the dissectors before it blocks and the kernel buffers.
+const QUEUED_BATCHES: usize = 64;
+/// Dissection is the expensive part of the pipeline, so it is spread over several threads.
+const MAX_DISSECTORS: usize = 8;
+
+/// Read errors after which a source never produces another frame, e.g. when its interface
+/// went down or was removed.
+fn is_fatal(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::PermissionDenied
+        || matches!(
+            e.raw_os_error(),
+            Some(libc::ENETDOWN | libc::ENODEV | libc::ENXIO | libc::EBADF)
+        )
+}
+
+/// Reads frames from `source` and dissects them until it's exhausted or `running` is cleared.
+/// A fatal read error ends the capture and is returned once everything read so far has been
+/// handed to `on_batch`.
+///
+/// One thread reads while a pool of threads dissects, so a slow dissection never holds up the
+/// socket. Batches are put back in capture order and numbered before being passed to `on_batch`.
+pub fn run_capture(
+    source: &mut dyn PacketSource,
+    running: &AtomicBool,
+    counters: &CaptureCounters,
+    mut on_batch: impl FnMut(Vec<FormatedPacket>) + Send,
+) -> io::Result<()> {
+    let started = Instant::now();
+    let (tx, rx) = mpsc::sync_channel::<(u64, Vec<RawFrame>)>(QUEUED_BATCHES);
+    let rx = Mutex::new(rx);
+    let (done_tx, done_rx) = mpsc::channel::<(u64, Vec<FormatedPacket>)>();
+    let mut fatal = None;
+    let dissectors = thread::available_parallelism()
+        .map(|n| n.get().saturating_sub(1))
+        .unwrap_or(1)
+        .clamp(1, MAX_DISSECTORS);
+
+    thread::scope(|scope| {
+        for _ in 0..dissectors {
+            let done_tx = done_tx.clone();
+            let rx = &rx;
+            scope.spawn(move || loop {
+                let next = rx.lock().unwrap().recv();
+                let Ok((seq, frames)) = next else { break };
+                // numbers are assigned once batches are back in order
+                let mut unnumbered = 0;
+                let mut vlans = VlanCounts::default();
+                let packets: Vec<FormatedPacket> = frames
+                    .into_iter()
+                    .map(|frame| {
+                        let (packet, gap) = dissect(frame, &mut unnumbered);
+                        if let Some(reason) = gap {
+                            counters.skip(reason);
+                        }
+                        vlans.count(&packet.vlans, packet.frame.orig_len as u64);
+                        packet
+                    })
+                    .collect();
+                counters.add_vlans(vlans);
+                counters
+                    .dissected
+                    .fetch_add(packets.len() as u64, Ordering::Relaxed);
+                if done_tx.send((seq, packets)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(done_tx);
+
+        scope.spawn(move || {
+            let mut count_fp = 0;
+            let mut next_seq = 0;
+            let mut pending = BTreeMap::new();
+            // fragments may be dissected on different threads, so they're reassembled here
+            let mut reassembler = Reassembler::default();
+            for (seq, packets) in done_rx {
+                pending.insert(seq, packets);
+                while let Some(mut packets) = pending.remove(&next_seq) {
+                    next_seq += 1;
+                    for fp in packets.iter_mut() {
+                        count_fp += 1;
+                        fp.number = count_fp;
+                        reassembler.add(fp);
+                    }
+                    if !packets.is_empty() {
+                        on_batch(packets);
+                    }
+                }
+            }
+        });
+
+        let mut seq = 0;
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut flushed_at = Instant::now();
+        let mut window = counters.window();
+        while running.load(Ordering::SeqCst) {
+            match source.next_frame() {
+                Ok(Some(frame)) => {
+                    counters.frames.fetch_add(1, Ordering::Relaxed);
+                    counters
+                        .bytes
+                        .fetch_add(frame.orig_len as u64, Ordering::Relaxed);
+                    batch.push(frame);
+                }
+                Ok(None) => break,
+                // the read timeout expiring on an idle link
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) => {}
+                Err(e) if is_fatal(&e) => {
+                    log::error!("capture failed: {}", e);
+                    fatal = Some(e);
+                    break;
+                }
+                Err(e) => {
+                    counters.read_errors.fetch_add(1, Ordering::Relaxed);
+                    log::debug!("failed to read frame: {}", e);
+                }
+            }
+
+            let due = !batch.is_empty() && flushed_at.elapsed() >= BATCH_INTERVAL;
+            if batch.len() >= BATCH_SIZE || due {
+                let full = mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                if tx.send((seq, full)).is_err() {
+                    break;
+                }
+                seq += 1;
+                flushed_at = Instant::now();
+            }
+            if counters.update_rates(&mut window) {
+                if let Some(drops) = source.take_kernel_drops() {
+                    counters.add_kernel_drops(drops);
+                }
+            }
+        }
+        if !batch.is_empty() {
+            let _ = tx.send((seq, batch));
+        }
+        drop(tx);
+    });
+
+    if let Some(drops) = source.take_kernel_drops() {
+        counters.add_kernel_drops(drops);
+    }
+    counters.clear_rates();
+    let frames = counters.frames.load(Ordering::Relaxed);
+    let secs = started.elapsed().as_secs_f64();
+    log::info!(
+        "capture finished: {} frames, {} dissected in {:.1}s ({:.0} frames/s)",
+        frames,
+        counters.dissected.load(Ordering::Relaxed),
+        secs,
+        if secs > 0.0 {
+            frames as f64 / secs
+        } else {
+            0.0
+        }
+    );
+    fatal.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Enough frames for several batches, so they're spread over the dissector pool.
+    const FRAMES: usize = BATCH_SIZE * 5 + 17;
+
+    /// Frame `i`: a UDP datagram, or every seventh an ethertype nothing dissects.
+    fn frame(i: usize) -> RawFrame {
+        let mut data = vec![2, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 1];
+        if i.is_multiple_of(7) {
+            data.extend([0x88, 0xb5]);
+            data.extend((i as u32).to_be_bytes());
+        } else {
+            data.extend([0x08, 0x00, 0x45, 0, 0, 32, 0, 0, 0, 0, 64, 17, 0, 0]);
+            data.extend([10, 0, 0, 1, 10, 0, 0, 2, 0x30, 0x39, 0, 9, 0, 12, 0, 0]);
+            data.extend((i as u32).to_be_bytes());
+        }
+        RawFrame::new("eth0", &data, i as i64, 0)
+    }
+
+    #[test]
+    fn keeps_capture_order_across_the_dissectors() {
+        let frames: Vec<RawFrame> = (0..FRAMES).map(frame).collect();
+        let bytes: u64 = frames.iter().map(|frame| frame.orig_len as u64).sum();
+        let mut source = SyntheticSource::from_frames(frames);
+        let counters = CaptureCounters::default();
+        let mut packets = Vec::new();
+        let mut batches = 0;
+        run_capture(&mut source, &AtomicBool::new(true), &counters, |batch| {
+            batches += 1;
+            packets.extend(batch);
+        })
+        .unwrap();
+
+        assert_eq!(packets.len(), FRAMES);
+        assert!(batches > 1);
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.number as usize, i + 1);
+            assert_eq!(packet.frame.ts_sec, i as i64);
+            let expected = if i.is_multiple_of(7) { "0x88b5" } else { "UDP" };
+            assert_eq!(packet.protocol, expected);
+        }
+        let stats = counters.snapshot();
+        assert_eq!(stats.frames_received, FRAMES as u64);
+        assert_eq!(stats.frames_dissected, FRAMES as u64);
+        assert_eq!(stats.bytes, bytes);
+        assert_eq!(
+            stats.skipped.unhandled_ether_type,
+            FRAMES.div_ceil(7) as u64
+        );
+        assert_eq!(stats.skipped.malformed, 0);
+    }
+
+    #[test]
+    fn stops_when_no_longer_running() {
+        let mut source = SyntheticSource::new(|| Some(frame(1)));
+        let counters = CaptureCounters::default();
+        run_capture(&mut source, &AtomicBool::new(false), &counters, |_| {}).unwrap();
+        assert_eq!(counters.snapshot().frames_received, 0);
+    }
+
+    #[test]
+    fn stops_a_replay_in_the_middle_of_a_gap() {
+        // the second frame was captured an hour after the first
+        let records = [0, 3600]
+            .into_iter()
+            .map(|ts_sec| {
+                let frame = frame(1);
+                CaptureRecord {
+                    link_type: frame.link_type,
+                    interface: None,
+                    ts_sec,
+                    ts_nsec: 0,
+                    orig_len: frame.orig_len,
+                    data: frame.data,
+                }
+            })
+            .collect();
+        let mut source = FileSource::new(records, ReplaySpeed::Original);
+        let running = AtomicBool::new(true);
+        let counters = CaptureCounters::default();
+        thread::scope(|scope| {
+            let capture =
+                scope.spawn(|| run_capture(&mut source, &running, &counters, |_| {}).unwrap());
+            while counters.snapshot().frames_received == 0 {
+                sleep(Duration::from_millis(10));
+            }
+            let stopped_at = Instant::now();
+            running.store(false, Ordering::SeqCst);
+            capture.join().unwrap();
+            assert!(stopped_at.elapsed() < READ_TIMEOUT * 5);
+        });
+        assert_eq!(counters.snapshot().frames_received, 1);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::link::VlanTag;
+
+/// Why a frame could only be partly dissected. Such frames are still kept as packets.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    UnsupportedLinkType,
+    UnhandledEtherType,
+    UnhandledIpProtocol,
+    /// Cut short or otherwise not a valid header for its protocol.
+    Malformed,
+}
+
+const SKIP_REASONS: usize = 4;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct SkippedFrames {
+    pub unsupported_link_type: u64,
+    pub unhandled_ether_type: u64,
+    pub unhandled_ip_protocol: u64,
+    pub malformed: u64,
+}
+
+/// How much of what reached the capture was read and dissected, and what wasn't.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct CaptureStats {
+    pub frames_received: u64,
+    pub frames_dissected: u64,
+    /// Frames labelled by protocol number only, or cut short, by reason.
+    pub skipped: SkippedFrames,
+    /// Bytes on the wire of every frame received.
+    pub bytes: u64,
+    /// Reads that failed without ending the capture.
+    pub read_errors: u64,
+    /// Frames the kernel dropped because the capture didn't keep up. `None` when the source
+    /// can't tell, e.g. files and captures outside Linux.
+    pub kernel_drops: Option<u64>,
+    /// Rates over the last second or so; zero once the capture has stopped.
+    pub packets_per_sec: f64,
+    pub bytes_per_sec: f64,
+    /// Traffic per VLAN ID, lowest ID first. QinQ frames count towards both of their VLANs.
+    #[serde(default)]
+    pub vlans: Vec<VlanStats>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct VlanStats {
+    pub id: u16,
+    pub packets: u64,
+    /// Bytes on the wire, tags included.
+    pub bytes: u64,
+}
+
+/// Counts shared by the reader and dissector threads of a capture.
+#[derive(Default)]
+pub struct CaptureCounters {
+    pub(super) frames: AtomicU64,
+    pub(super) bytes: AtomicU64,
+    pub(super) dissected: AtomicU64,
+    pub(super) read_errors: AtomicU64,
+    skipped: [AtomicU64; SKIP_REASONS],
+    kernel_drops: AtomicU64,
+    has_kernel_drops: AtomicBool,
+    /// Frames and bytes per second, as of the last full window.
+    rates: Mutex<(f64, f64)>,
+    /// Packets and bytes by VLAN ID, merged in a batch at a time.
+    vlans: Mutex<VlanCounts>,
+}
+
+/// Packets and bytes by VLAN ID. Dissector threads count a batch in one of their own and add
+/// it to the capture's in one go, as on a trunk every frame is tagged.
+#[derive(Default)]
+pub struct VlanCounts(BTreeMap<u16, (u64, u64)>);
+
+impl VlanCounts {
+    pub fn count(&mut self, tags: &[VlanTag], bytes: u64) {
+        for (i, tag) in tags.iter().enumerate() {
+            // a frame tagged twice with the same ID is still one frame on that VLAN
+            if tags[..i].iter().any(|outer| outer.id == tag.id) {
+                continue;
+            }
+            let (packets, total) = self.0.entry(tag.id).or_default();
+            *packets += 1;
+            *total += bytes;
+        }
+    }
+}
+
+/// The counter values at the start of the current one-second reporting window.
+pub(super) struct RateWindow {
+    started: Instant,
+    frames: u64,
+    bytes: u64,
+    dissected: u64,
+}
+
+impl CaptureCounters {
+    pub fn skip(&self, reason: SkipReason) {
+        self.skipped[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_vlans(&self, counts: VlanCounts) {
+        if counts.0.is_empty() {
+            return;
+        }
+        let mut vlans = self.vlans.lock().unwrap();
+        for (id, (packets, bytes)) in counts.0 {
+            let (total_packets, total_bytes) = vlans.0.entry(id).or_default();
+            *total_packets += packets;
+            *total_bytes += bytes;
+        }
+    }
+
+    pub fn add_kernel_drops(&self, drops: u64) {
+        self.kernel_drops.fetch_add(drops, Ordering::Relaxed);
+        self.has_kernel_drops.store(true, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> CaptureStats {
+        let skipped = |reason: SkipReason| self.skipped[reason as usize].load(Ordering::Relaxed);
+        let (packets_per_sec, bytes_per_sec) = *self.rates.lock().unwrap();
+        CaptureStats {
+            frames_received: self.frames.load(Ordering::Relaxed),
+            frames_dissected: self.dissected.load(Ordering::Relaxed),
+            skipped: SkippedFrames {
+                unsupported_link_type: skipped(SkipReason::UnsupportedLinkType),
+                unhandled_ether_type: skipped(SkipReason::UnhandledEtherType),
+                unhandled_ip_protocol: skipped(SkipReason::UnhandledIpProtocol),
+                malformed: skipped(SkipReason::Malformed),
+            },
+            bytes: self.bytes.load(Ordering::Relaxed),
+            read_errors: self.read_errors.load(Ordering::Relaxed),
+            kernel_drops: self
+                .has_kernel_drops
+                .load(Ordering::Relaxed)
+                .then(|| self.kernel_drops.load(Ordering::Relaxed)),
+            packets_per_sec,
+            bytes_per_sec,
+            vlans: self
+                .vlans
+                .lock()
+                .unwrap()
+                .0
+                .iter()
+                .map(|(&id, &(packets, bytes))| VlanStats { id, packets, bytes })
+                .collect(),
+        }
+    }
+
+    pub(super) fn window(&self) -> RateWindow {
+        RateWindow {
+            started: Instant::now(),
+            frames: self.frames.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            dissected: self.dissected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Closes the window once a second has passed, updating the rates and logging them.
+    /// Returns whether it did.
+    pub(super) fn update_rates(&self, window: &mut RateWindow) -> bool {
+        let secs = window.started.elapsed().as_secs_f64();
+        if secs < 1.0 {
+            return false;
+        }
+        let now = self.window();
+        let frames_per_sec = (now.frames - window.frames) as f64 / secs;
+        let bytes_per_sec = (now.bytes - window.bytes) as f64 / secs;
+        log::debug!(
+            "capture: {:.0} frames/s, {:.0} dissected/s, {:.2} Mbit/s",
+            frames_per_sec,
+            (now.dissected - window.dissected) as f64 / secs,
+            bytes_per_sec * 8.0 / 1e6,
+        );
+        *self.rates.lock().unwrap() = (frames_per_sec, bytes_per_sec);
+        *window = now;
+        true
+    }
+
+    /// The capture is over, so nothing is coming in any more.
+    pub(super) fn clear_rates(&self) {
+        *self.rates.lock().unwrap() = (0.0, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_up_vlan_counts_from_batches() {
+        let counters = CaptureCounters::default();
+        let tag = |id| VlanTag::new(0x8100, id);
+        let mut batch = VlanCounts::default();
+        batch.count(&[tag(10)], 100);
+        batch.count(&[tag(10), tag(20)], 200);
+        // tagged twice with the same ID
+        batch.count(&[tag(30), tag(30)], 50);
+        batch.count(&[], 1000);
+        counters.add_vlans(batch);
+        let mut batch = VlanCounts::default();
+        batch.count(&[tag(20)], 300);
+        counters.add_vlans(batch);
+
+        let vlan = |id, packets, bytes| VlanStats { id, packets, bytes };
+        assert_eq!(
+            counters.snapshot().vlans,
+            [vlan(10, 2, 300), vlan(20, 2, 500), vlan(30, 1, 50)]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant, TryFromFloatSecsError};
+
+use serde::{Deserialize, Serialize};
+
+use super::{show_packets::FormatedPacket, store::DisplayFilter};
+
+/// When a capture ends by itself. Whichever condition is met first stops it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct StopConditions {
+    pub max_packets: Option<u64>,
+    /// Bytes on the wire, so truncated frames count at their full length.
+    pub max_bytes: Option<u64>,
+    pub max_duration_secs: Option<f64>,
+    /// Stops right after the first packet matching this filter, which is kept.
+    pub until_match: Option<DisplayFilter>,
+}
+
+impl StopConditions {
+    /// `max_duration_secs` as a [`Duration`], an error if it's negative, not a number or too
+    /// large for one.
+    pub fn max_duration(&self) -> Result<Option<Duration>, TryFromFloatSecsError> {
+        self.max_duration_secs
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// `stop_watch` or closing the session.
+    User,
+    PacketCount,
+    ByteCount,
+    Duration,
+    Match,
+    /// A replayed file or other finite source ran out.
+    EndOfSource,
+    Error,
+}
+
+/// Counts what a capture has stored against its stop conditions.
+pub struct StopTracker {
+    conditions: StopConditions,
+    deadline: Option<Instant>,
+    packets: u64,
+    bytes: u64,
+    reason: Option<StopReason>,
+}
+
+impl StopTracker {
+    /// A duration [`StopConditions::max_duration`] rejects, or one reaching past what an
+    /// [`Instant`] can hold, sets no deadline.
+    pub fn new(conditions: StopConditions) -> Self {
+        let deadline = conditions
+            .max_duration()
+            .ok()
+            .flatten()
+            .and_then(|duration| Instant::now().checked_add(duration));
+        Self {
+            conditions,
+            deadline,
+            packets: 0,
+            bytes: 0,
+            reason: None,
+        }
+    }
+
+    /// Cuts `batch` down to the packets that came before a condition was met, the packet that
+    /// met it included. Returns the reason the first time a condition is met; from then on
+    /// every batch is emptied, as the capture is winding down.
+    pub fn admit(&mut self, batch: &mut Vec<FormatedPacket>) -> Option<StopReason> {
+        if self.reason.is_some() {
+            batch.clear();
+            return None;
+        }
+        let mut reason = None;
+        let mut keep = batch.len();
+        for (i, packet) in batch.iter().enumerate() {
+            self.packets += 1;
+            self.bytes += packet.frame.orig_len as u64;
+            reason = if self
+                .conditions
+                .max_packets
+                .is_some_and(|max| self.packets >= max)
+            {
+                Some(StopReason::PacketCount)
+            } else if self
+                .conditions
+                .max_bytes
+                .is_some_and(|max| self.bytes >= max)
+            {
+                Some(StopReason::ByteCount)
+            } else if self
+                .conditions
+                .until_match
+                .as_ref()
+                .is_some_and(|filter| filter.matches(packet))
+            {
+                Some(StopReason::Match)
+            } else {
+                None
+            };
+            if reason.is_some() {
+                keep = i + 1;
+                break;
+            }
+        }
+        batch.truncate(keep);
+        self.reason = reason;
+        reason
+    }
+
+    /// Checked on a timer, since a quiet link may deliver no batch at all once the time is up.
+    pub fn expired(&mut self) -> Option<StopReason> {
+        if self.reason.is_some() || self.deadline.is_none_or(|at| Instant::now() < at) {
+            return None;
+        }
+        self.reason = Some(StopReason::Duration);
+        self.reason
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lasting(secs: f64) -> StopConditions {
+        StopConditions {
+            max_duration_secs: Some(secs),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rejects_durations_that_arent_one() {
+        for secs in [-1.0, f64::NAN, f64::INFINITY, 1e300] {
+            assert!(lasting(secs).max_duration().is_err(), "{}", secs);
+        }
+        assert_eq!(
+            lasting(1.5).max_duration(),
+            Ok(Some(Duration::from_millis(1500)))
+        );
+        assert_eq!(StopConditions::default().max_duration(), Ok(None));
+    }
+
+    #[test]
+    fn never_expires_past_what_an_instant_holds() {
+        let mut tracker = StopTracker::new(lasting(u64::MAX as f64 / 2.0));
+        assert_eq!(tracker.expired(), None);
+        let mut tracker = StopTracker::new(lasting(0.0));
+        assert_eq!(tracker.expired(), Some(StopReason::Duration));
+    }
+}
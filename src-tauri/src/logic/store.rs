@@ -0,0 +1,555 @@
+use std::{
+    collections::{vec_deque, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    mem,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    frame::RawFrame,
+    link::VlanTag,
+    pcap::PcapngWriter,
+    show_packets::{DetailedInfo, FormatedPacket, PacketRow},
+    tree::ProtocolNode,
+};
+
+/// Memory the store may use when nothing else is configured.
+pub const DEFAULT_MAX_BYTES: usize = 512 * 1024 * 1024;
+/// Share of a limit above which the store reports that packets are about to be evicted.
+const NEAR_LIMIT: f64 = 0.9;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StoreLimits {
+    pub max_packets: Option<usize>,
+    pub max_bytes: Option<usize>,
+    /// Packets older than this, counted back from the newest packet, are evicted.
+    pub max_age_secs: Option<f64>,
+    /// Evicted packets are appended to this pcapng file instead of being thrown away.
+    pub spill_path: Option<PathBuf>,
+}
+
+impl Default for StoreLimits {
+    fn default() -> Self {
+        Self {
+            max_packets: None,
+            max_bytes: Some(DEFAULT_MAX_BYTES),
+            max_age_secs: None,
+            spill_path: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct StoreUsage {
+    pub packets: usize,
+    pub bytes: usize,
+    /// Packets dropped from memory since the store was last cleared, spilled ones included.
+    pub evicted: u64,
+    pub spilled: u64,
+    /// How close the store is to its tightest packet or byte limit, from 0 to 1.
+    pub fill: f64,
+    pub near_limit: bool,
+    pub limits: StoreLimits,
+    /// Set when writing to the spill file failed; eviction carries on without spilling.
+    pub spill_error: Option<String>,
+}
+
+/// The packet list filter: a protocol name, or "all", a prefix of the source or
+/// destination address, and a VLAN ID the frame must be tagged with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DisplayFilter {
+    pub protocol: String,
+    pub ip: String,
+    #[serde(default)]
+    pub vlan: Option<u16>,
+    /// `ip` parsed on first use.
+    #[serde(skip)]
+    ipv4_prefix: OnceLock<Option<Ipv4Prefix>>,
+}
+
+impl PartialEq for DisplayFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.protocol == other.protocol && self.ip == other.ip && self.vlan == other.vlan
+    }
+}
+
+impl DisplayFilter {
+    pub fn new(protocol: String, ip: String, vlan: Option<u16>) -> Self {
+        Self {
+            protocol,
+            ip,
+            vlan,
+            ipv4_prefix: OnceLock::new(),
+        }
+    }
+
+    pub fn matches(&self, packet: &FormatedPacket) -> bool {
+        (self.protocol == "all" || self.protocol == packet.protocol)
+            && (self.ip.is_empty()
+                || [packet.src_ip, packet.dst_ip]
+                    .iter()
+                    .flatten()
+                    .any(|ip| self.matches_ip(ip)))
+            && self
+                .vlan
+                .is_none_or(|id| packet.vlans.iter().any(|tag| tag.id == id))
+    }
+
+    /// Whether `ip` written out starts with `self.ip`. IPv4 addresses, by far the most common,
+    /// are compared against `self.ip` parsed once instead of being written out.
+    fn matches_ip(&self, ip: &IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(ip) => self
+                .ipv4_prefix
+                .get_or_init(|| Ipv4Prefix::parse(&self.ip))
+                .as_ref()
+                .is_some_and(|prefix| prefix.matches(ip)),
+            IpAddr::V6(_) => {
+                // long enough for any IPv6 address, so this never allocates
+                let mut text = [0; 48];
+                let mut cursor = io::Cursor::new(&mut text[..]);
+                write!(cursor, "{}", ip).is_ok() && {
+                    let len = cursor.position() as usize;
+                    text[..len].starts_with(self.ip.as_bytes())
+                }
+            }
+        }
+    }
+}
+
+/// A prefix of a dotted IPv4 address: whole `octets`, then the leading digits of the next one.
+#[derive(Clone, Debug)]
+struct Ipv4Prefix {
+    octets: Vec<u8>,
+    /// The digits' value and how many there are, none after a trailing dot.
+    partial: Option<(u16, u32)>,
+}
+
+impl Ipv4Prefix {
+    /// `None` when no IPv4 address starts with `text`.
+    fn parse(text: &str) -> Option<Self> {
+        let mut groups: Vec<&str> = text.split('.').collect();
+        let last = groups.pop()?;
+        if groups.len() > 3 || last.len() > 3 || !last.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let octets = groups
+            .into_iter()
+            .map(|group| {
+                // written out, octets never have leading zeros
+                group
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|octet| octet.to_string() == group)
+            })
+            .collect::<Option<Vec<u8>>>()?;
+        let partial = (!last.is_empty()).then(|| (last.parse().unwrap(), last.len() as u32));
+        Some(Self { octets, partial })
+    }
+
+    fn matches(&self, ip: &Ipv4Addr) -> bool {
+        let octets = ip.octets();
+        octets.starts_with(&self.octets)
+            && self.partial.is_none_or(|(value, digits)| {
+                let next = octets[self.octets.len()] as u16;
+                let written = match next {
+                    100.. => 3,
+                    10.. => 2,
+                    _ => 1,
+                };
+                written >= digits && next / 10u16.pow(written - digits) == value
+            })
+    }
+}
+
+/// One page of the stored packets that matched a filter.
+#[derive(Clone, Debug, Serialize, Default)]
+pub struct PacketPage {
+    pub packets: Vec<PacketRow>,
+    /// Packets in the whole store that match the filter, whatever page was asked for.
+    pub total: usize,
+    /// Pass as `after_number` to get the next page; `None` on the last one.
+    pub next_after: Option<u32>,
+}
+
+/// Captured packets held in memory, oldest first. Once a limit is reached the oldest packets
+/// are evicted ring-buffer style, and spilled to disk if a spill file is configured.
+#[derive(Default)]
+pub struct PacketStore {
+    packets: VecDeque<FormatedPacket>,
+    bytes: usize,
+    limits: StoreLimits,
+    evicted: u64,
+    spilled: u64,
+    spill: Option<PcapngWriter<BufWriter<File>>>,
+    spill_error: Option<String>,
+    /// Capture times of the first and the latest packet, kept through eviction so relative
+    /// and delta times don't shift as old packets go.
+    first_at: Option<(i64, u32)>,
+    last_at: Option<(i64, u32)>,
+}
+
+impl PacketStore {
+    pub fn limits(&self) -> &StoreLimits {
+        &self.limits
+    }
+
+    /// Applies new limits, evicting straight away if the store is already over them.
+    pub fn set_limits(&mut self, limits: StoreLimits) -> io::Result<()> {
+        if limits.spill_path != self.limits.spill_path || self.spill_error.is_some() {
+            self.spill = match &limits.spill_path {
+                Some(path) => Some(open_spill(path)?),
+                None => None,
+            };
+            self.spill_error = None;
+        }
+        self.limits = limits;
+        self.evict();
+        Ok(())
+    }
+
+    pub fn extend(&mut self, packets: impl IntoIterator<Item = FormatedPacket>) {
+        for packet in packets {
+            self.bytes += footprint(&packet);
+            self.packets.push_back(packet);
+        }
+        self.evict();
+    }
+
+    /// Fills in the relative and delta times of packets about to be added, so they can be
+    /// sent out with them before they reach the store.
+    pub fn stamp(&mut self, packets: &mut [FormatedPacket]) {
+        for packet in packets {
+            // packets imported from old JSON exports keep the times they were exported with
+            if packet.frame.data.is_empty() {
+                continue;
+            }
+            let at = (packet.frame.ts_sec, packet.frame.ts_nsec);
+            packet.relative = packet.frame.seconds_since(*self.first_at.get_or_insert(at));
+            packet.delta = packet.frame.seconds_since(self.last_at.unwrap_or(at));
+            self.last_at = Some(at);
+        }
+    }
+
+    /// Swaps the whole content, e.g. for an imported file. Limits still apply.
+    pub fn replace(&mut self, packets: impl IntoIterator<Item = FormatedPacket>) {
+        self.clear();
+        let mut packets: Vec<FormatedPacket> = packets.into_iter().collect();
+        self.stamp(&mut packets);
+        self.extend(packets);
+    }
+
+    /// Empties the store and resets the eviction counters. The spill file is kept, so packets
+    /// spilled by an earlier capture aren't lost.
+    pub fn clear(&mut self) {
+        self.packets.clear();
+        self.bytes = 0;
+        self.evicted = 0;
+        self.spilled = 0;
+        self.first_at = None;
+        self.last_at = None;
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, FormatedPacket> {
+        self.packets.iter()
+    }
+
+    /// The packet numbered `number`, if it's still in memory.
+    pub fn get(&self, number: u32) -> Option<&FormatedPacket> {
+        let index = self
+            .packets
+            .partition_point(|packet| packet.number < number);
+        self.packets
+            .get(index)
+            .filter(|packet| packet.number == number)
+    }
+
+    /// Returns up to `limit` packets matching `filter`, skipping `offset` matches after the
+    /// packet numbered `after_number`. Packets are stored in number order, so the cursor is a
+    /// binary search rather than a scan.
+    pub fn page(
+        &self,
+        filter: impl Fn(&FormatedPacket) -> bool,
+        after_number: Option<u32>,
+        offset: usize,
+        limit: usize,
+    ) -> PacketPage {
+        let start = after_number.map_or(0, |after| {
+            self.packets
+                .partition_point(|packet| packet.number <= after)
+        });
+        // one pass over the store: the matches before the cursor only count towards the total
+        let mut total = self
+            .packets
+            .range(..start)
+            .filter(|packet| filter(packet))
+            .count();
+        let mut skipped = 0;
+        let mut packets = Vec::new();
+        let mut more = false;
+        for packet in self.packets.range(start..).filter(|packet| filter(packet)) {
+            total += 1;
+            if skipped < offset {
+                skipped += 1;
+            } else if packets.len() < limit {
+                packets.push(PacketRow::from(packet));
+            } else {
+                more = true;
+            }
+        }
+        PacketPage {
+            // a page with no room still has a cursor when more packets match
+            next_after: more.then(|| {
+                packets
+                    .last()
+                    .map_or(after_number.unwrap_or(0), |packet| packet.number)
+            }),
+            packets,
+            total,
+        }
+    }
+
+    /// Replaces every packet with `f(packet)`, keeping the memory accounting right.
+    pub fn update(&mut self, mut f: impl FnMut(&FormatedPacket) -> Option<FormatedPacket>) {
+        for packet in self.packets.iter_mut() {
+            if let Some(mut updated) = f(packet) {
+                updated.relative = packet.relative;
+                updated.delta = packet.delta;
+                self.bytes = self.bytes - footprint(packet) + footprint(&updated);
+                *packet = updated;
+            }
+        }
+        self.evict();
+    }
+
+    pub fn usage(&self) -> StoreUsage {
+        let fill = [
+            self.limits
+                .max_packets
+                .map(|max| self.packets.len() as f64 / max.max(1) as f64),
+            self.limits
+                .max_bytes
+                .map(|max| self.bytes as f64 / max.max(1) as f64),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(0.0, f64::max)
+        .min(1.0);
+        StoreUsage {
+            packets: self.packets.len(),
+            bytes: self.bytes,
+            evicted: self.evicted,
+            spilled: self.spilled,
+            fill,
+            near_limit: fill >= NEAR_LIMIT,
+            limits: self.limits.clone(),
+            spill_error: self.spill_error.clone(),
+        }
+    }
+
+    fn over_limit(&self) -> bool {
+        let Some(oldest) = self.packets.front() else {
+            return false;
+        };
+        if self
+            .limits
+            .max_packets
+            .is_some_and(|max| self.packets.len() > max)
+            || self.limits.max_bytes.is_some_and(|max| self.bytes > max)
+        {
+            return true;
+        }
+        match (self.limits.max_age_secs, self.packets.back()) {
+            (Some(max_age), Some(newest)) => {
+                newest.frame.timestamp() - oldest.frame.timestamp() > max_age
+            }
+            _ => false,
+        }
+    }
+
+    fn evict(&mut self) {
+        let spilled_before = self.spilled;
+        while self.over_limit() {
+            let Some(packet) = self.packets.pop_front() else {
+                break;
+            };
+            self.bytes -= footprint(&packet);
+            self.evicted += 1;
+            self.spill(&packet);
+        }
+        if self.spilled != spilled_before {
+            if let Some(Err(e)) = self.spill.as_mut().map(PcapngWriter::flush) {
+                self.spill_failed(e);
+            }
+        }
+    }
+
+    fn spill(&mut self, packet: &FormatedPacket) {
+        // packets imported from old JSON exports have no frame to write
+        if packet.frame.data.is_empty() {
+            return;
+        }
+        if let Some(spill) = self.spill.as_mut() {
+            match spill.write_record(&packet.frame.to_record()) {
+                Ok(()) => self.spilled += 1,
+                Err(e) => self.spill_failed(e),
+            }
+        }
+    }
+
+    fn spill_failed(&mut self, e: io::Error) {
+        log::error!(
+            "failed to spill packets to disk, evicted packets are dropped: {}",
+            e
+        );
+        self.spill = None;
+        self.spill_error = Some(e.to_string());
+    }
+}
+
+/// Opens `path` for appending. Every open starts a new pcapng section, which readers
+/// handle like a concatenated capture.
+fn open_spill(path: &Path) -> io::Result<PcapngWriter<BufWriter<File>>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    PcapngWriter::new(BufWriter::new(file))
+}
+
+/// Roughly how much memory a frame takes on its own, e.g. in a pre-trigger window.
+pub fn frame_footprint(frame: &RawFrame) -> usize {
+    mem::size_of::<RawFrame>() + frame.interface.capacity() + frame.data.capacity()
+}
+
+/// Roughly how much memory a stored packet takes, heap allocations included.
+fn footprint(packet: &FormatedPacket) -> usize {
+    let detailed = packet.detailed_info.as_ref().map_or(0, |info| {
+        mem::size_of::<DetailedInfo>()
+            + info.interface.capacity()
+            + info.src_mac.capacity()
+            + info.dst_mac.capacity()
+            + info.frame_type.capacity()
+            + info.payload_data.capacity()
+            + info.tree.iter().map(ProtocolNode::footprint).sum::<usize>()
+    });
+    mem::size_of::<FormatedPacket>()
+        + packet.vlans.capacity() * mem::size_of::<VlanTag>()
+        + packet.time.capacity()
+        + packet.protocol.capacity()
+        + packet.info.capacity()
+        + packet.frame.interface.capacity()
+        + packet.frame.data.capacity()
+        + detailed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::show_packets::dissect;
+
+    fn store(count: usize) -> PacketStore {
+        let mut count_fp = 0;
+        let mut store = PacketStore::default();
+        store.extend((0..count).map(|i| {
+            let (mut packet, _) =
+                dissect(RawFrame::new("eth0", &[0; 64], i as i64, 0), &mut count_fp);
+            packet.number = i as u32 + 1;
+            packet
+        }));
+        store
+    }
+
+    fn numbers(page: &PacketPage) -> Vec<u32> {
+        page.packets.iter().map(|packet| packet.number).collect()
+    }
+
+    #[test]
+    fn pages_through_the_matches() {
+        let store = store(20);
+        let even = |packet: &FormatedPacket| packet.number.is_multiple_of(2);
+
+        let page = store.page(even, None, 0, 3);
+        assert_eq!(numbers(&page), [2, 4, 6]);
+        assert_eq!((page.total, page.next_after), (10, Some(6)));
+
+        let page = store.page(even, Some(6), 1, 3);
+        assert_eq!(numbers(&page), [10, 12, 14]);
+        assert_eq!((page.total, page.next_after), (10, Some(14)));
+
+        let page = store.page(even, Some(14), 0, 3);
+        assert_eq!(numbers(&page), [16, 18, 20]);
+        assert_eq!((page.total, page.next_after), (10, None));
+    }
+
+    #[test]
+    fn keeps_the_cursor_on_empty_pages() {
+        let store = store(20);
+        let page = store.page(|_| true, Some(5), 0, 0);
+        assert!(page.packets.is_empty());
+        assert_eq!((page.total, page.next_after), (20, Some(5)));
+        assert_eq!(store.page(|_| true, None, 0, 0).next_after, Some(0));
+        assert_eq!(store.page(|_| true, Some(20), 0, 0).next_after, None);
+    }
+
+    #[test]
+    fn matches_address_prefixes_as_written() {
+        let prefixes = [
+            "1",
+            "10",
+            "10.",
+            "10.0",
+            "10.0.0.1",
+            "10.0.0.10",
+            "10.0.0.100",
+            "192.168.1.",
+            "192.168.1.2",
+            "0",
+            "0.",
+            "00",
+            "01.2",
+            "1.2.3.4.",
+            "255.255.255.255",
+            "256",
+            "a",
+            "::",
+            "2001:db8",
+            "fe80::1",
+            ".",
+        ];
+        let addresses: Vec<IpAddr> = [
+            "1.2.3.4",
+            "10.0.0.1",
+            "10.0.0.10",
+            "10.0.0.100",
+            "10.0.0.2",
+            "100.0.0.1",
+            "192.168.1.2",
+            "192.168.1.25",
+            "192.168.10.2",
+            "0.0.0.0",
+            "0.1.2.3",
+            "255.255.255.255",
+            "2001:db8::1",
+            "fe80::1",
+            "::1",
+        ]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+        for prefix in prefixes {
+            let filter = DisplayFilter::new("all".into(), prefix.into(), None);
+            for address in &addresses {
+                assert_eq!(
+                    filter.matches_ip(address),
+                    address.to_string().starts_with(prefix),
+                    "{} against {}",
+                    prefix,
+                    address
+                );
+            }
+        }
+    }
+}
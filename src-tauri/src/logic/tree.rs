@@ -0,0 +1,1080 @@
+use std::{fmt::Display, mem};
+
+use chrono::{DateTime, SecondsFormat};
+use pnet::packet::{
+    arp::{ArpOperations, ArpPacket},
+    ethernet::{EtherType, EthernetPacket},
+    icmp::IcmpPacket,
+    icmpv6::Icmpv6Packet,
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv4::Ipv4Packet,
+    ipv6::Ipv6Packet,
+    tcp::{TcpFlags, TcpPacket},
+    udp::UdpPacket,
+    Packet,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    frame::RawFrame,
+    link::{self, LinkHeader, VlanTag},
+    pcap::{
+        LINKTYPE_ETHERNET, LINKTYPE_LINUX_SLL, LINKTYPE_LINUX_SLL2, LINKTYPE_LOOP, LINKTYPE_NULL,
+    },
+    protocols,
+};
+
+/// One line of a packet's protocol tree: a layer, or a field of one, and the bytes it was
+/// read from.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ProtocolNode {
+    /// Filter-style name, e.g. `ip.ttl` for a field or `ip` for its layer.
+    pub name: String,
+    /// The value on its own, e.g. `64`. Empty for layers.
+    pub value: String,
+    /// The line shown in the tree, e.g. `Time to live: 64`.
+    pub display: String,
+    /// Where it is in the frame, in bytes. Fields narrower than a byte cover the bytes they're
+    /// in, and capture metadata has no bytes at all.
+    pub offset: usize,
+    pub length: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ProtocolNode>,
+}
+
+impl ProtocolNode {
+    /// A layer `length` bytes long, `offset` bytes into the frame, with its fields to be added.
+    pub fn layer(name: &str, display: String, offset: usize, length: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            display,
+            offset,
+            length,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a field `at` bytes into this node, shown as `label: value`.
+    pub fn field(
+        &mut self,
+        name: &str,
+        label: &str,
+        value: impl Display,
+        at: usize,
+        length: usize,
+    ) -> &mut ProtocolNode {
+        let value = value.to_string();
+        self.children.push(ProtocolNode {
+            name: name.to_string(),
+            display: format!("{}: {}", label, value),
+            value,
+            offset: self.offset + at,
+            length,
+            children: Vec::new(),
+        });
+        self.children.last_mut().expect("just pushed")
+    }
+
+    /// Like [`field`](Self::field), with the text shown differing from the bare value, e.g.
+    /// a protocol number shown with its name.
+    pub fn described(
+        &mut self,
+        name: &str,
+        label: &str,
+        value: impl Display,
+        description: String,
+        at: usize,
+        length: usize,
+    ) -> &mut ProtocolNode {
+        let node = self.field(name, label, value, at, length);
+        node.display = format!("{}: {}", label, description);
+        node
+    }
+
+    /// Unties the node and everything under it from the frame's bytes, for layers read from
+    /// elsewhere, such as a datagram reassembled from several frames.
+    pub fn detach(&mut self) {
+        self.offset = 0;
+        self.length = 0;
+        self.children.iter_mut().for_each(Self::detach);
+    }
+
+    /// Memory held by the node and everything under it, for the store's accounting.
+    pub fn footprint(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.name.capacity()
+            + self.value.capacity()
+            + self.display.capacity()
+            + self.children.iter().map(Self::footprint).sum::<usize>()
+    }
+}
+
+/// What was captured, rather than what's in it.
+pub fn frame(frame: &RawFrame, number: u32) -> ProtocolNode {
+    let captured = frame.data.len();
+    let mut node = ProtocolNode::layer(
+        "frame",
+        format!(
+            "Frame {}: {} bytes on wire, {} bytes captured on {}",
+            number, frame.orig_len, captured, frame.interface
+        ),
+        0,
+        captured,
+    );
+    node.field("frame.number", "Frame number", number, 0, 0);
+    node.field("frame.interface", "Interface", &frame.interface, 0, 0);
+    if let Some(at) = DateTime::from_timestamp(frame.ts_sec, frame.ts_nsec) {
+        node.field(
+            "frame.time",
+            "Arrival time",
+            at.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            0,
+            0,
+        );
+    }
+    node.field("frame.len", "Frame length", frame.orig_len, 0, 0);
+    node.field("frame.cap_len", "Capture length", captured, 0, 0);
+    node.described(
+        "frame.link_type",
+        "Link type",
+        frame.link_type,
+        format!("{} ({})", link::name(frame.link_type), frame.link_type),
+        0,
+        0,
+    );
+    node
+}
+
+/// The link-layer header, the first `header.len()` bytes of the frame. `None` for links that
+/// have none, like raw IP.
+pub fn link(link_type: u32, header: &[u8], decoded: &LinkHeader) -> Option<ProtocolNode> {
+    // the link header names the outermost VLAN tag, if there is one, rather than the payload
+    let outer = decoded
+        .vlans
+        .first()
+        .map_or(decoded.ethertype, |tag| EtherType::new(tag.tpid));
+    let ethertype = ether_type(outer);
+    let source = decoded.src.clone().unwrap_or_default();
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let ethernet = EthernetPacket::new(header)?;
+            let mut node = ProtocolNode::layer(
+                "eth",
+                format!(
+                    "Ethernet II, Src: {}, Dst: {}",
+                    ethernet.get_source(),
+                    ethernet.get_destination()
+                ),
+                0,
+                header.len(),
+            );
+            node.field("eth.dst", "Destination", ethernet.get_destination(), 0, 6);
+            node.field("eth.src", "Source", ethernet.get_source(), 6, 6);
+            node.described("eth.type", "Type", outer.0, ethertype, 12, 2);
+            Some(node)
+        }
+        LINKTYPE_LINUX_SLL => {
+            let mut node = ProtocolNode::layer(
+                "sll",
+                format!("Linux cooked capture v1, Src: {}", source),
+                0,
+                header.len(),
+            );
+            node.field("sll.pkttype", "Packet type", be16(header, 0), 0, 2);
+            node.field(
+                "sll.hatype",
+                "Link-layer address type",
+                be16(header, 2),
+                2,
+                2,
+            );
+            node.field(
+                "sll.halen",
+                "Link-layer address length",
+                be16(header, 4),
+                4,
+                2,
+            );
+            node.field("sll.src", "Source", &source, 6, 8);
+            node.described("sll.etype", "Protocol", outer.0, ethertype, 14, 2);
+            Some(node)
+        }
+        LINKTYPE_LINUX_SLL2 => {
+            let mut node = ProtocolNode::layer(
+                "sll",
+                format!("Linux cooked capture v2, Src: {}", source),
+                0,
+                header.len(),
+            );
+            node.described("sll.etype", "Protocol", outer.0, ethertype, 0, 2);
+            let ifindex = u32::from_be_bytes(header[4..8].try_into().ok()?);
+            node.field("sll.ifindex", "Interface index", ifindex, 4, 4);
+            node.field(
+                "sll.hatype",
+                "Link-layer address type",
+                be16(header, 8),
+                8,
+                2,
+            );
+            node.field("sll.pkttype", "Packet type", header[10], 10, 1);
+            node.field("sll.halen", "Link-layer address length", header[11], 11, 1);
+            node.field("sll.src", "Source", &source, 12, 8);
+            Some(node)
+        }
+        LINKTYPE_NULL | LINKTYPE_LOOP => {
+            let mut node =
+                ProtocolNode::layer("null", "Null/Loopback".to_string(), 0, header.len());
+            let family = null_family(link_type, header)?;
+            node.described(
+                "null.family",
+                "Family",
+                family,
+                named(family, address_family_name(family)),
+                0,
+                4,
+            );
+            Some(node)
+        }
+        _ => None,
+    }
+}
+
+/// A VLAN tag, followed by what `next` says.
+pub fn vlan(tag: &VlanTag, next: u16, offset: usize) -> ProtocolNode {
+    let mut node = ProtocolNode::layer(
+        "vlan",
+        format!(
+            "{}, PRI: {}, DEI: {}, ID: {}",
+            if tag.tpid == 0x8100 {
+                "802.1Q Virtual LAN"
+            } else {
+                "802.1ad Service VLAN"
+            },
+            tag.pcp,
+            tag.dei as u8,
+            tag.id
+        ),
+        offset,
+        4,
+    );
+    node.field("vlan.priority", "Priority", tag.pcp, 0, 1);
+    node.field("vlan.dei", "Drop eligible", tag.dei, 0, 1);
+    node.field("vlan.id", "ID", tag.id, 0, 2);
+    node.described(
+        "vlan.etype",
+        "Type",
+        next,
+        ether_type(EtherType::new(next)),
+        2,
+        2,
+    );
+    node
+}
+
+pub fn arp(arp: &ArpPacket, name: &str, offset: usize) -> ProtocolNode {
+    let operation = match arp.get_operation() {
+        ArpOperations::Request => "request",
+        ArpOperations::Reply => "reply",
+        _ => "unknown",
+    };
+    let mut node = ProtocolNode::layer(
+        "arp",
+        format!("{} ({})", name, operation),
+        offset,
+        ArpPacket::minimum_packet_size(),
+    );
+    node.field(
+        "arp.hw.type",
+        "Hardware type",
+        arp.get_hardware_type().0,
+        0,
+        2,
+    );
+    node.described(
+        "arp.proto.type",
+        "Protocol type",
+        arp.get_protocol_type().0,
+        ether_type(arp.get_protocol_type()),
+        2,
+        2,
+    );
+    node.field("arp.hw.size", "Hardware size", arp.get_hw_addr_len(), 4, 1);
+    node.field(
+        "arp.proto.size",
+        "Protocol size",
+        arp.get_proto_addr_len(),
+        5,
+        1,
+    );
+    node.described(
+        "arp.opcode",
+        "Opcode",
+        arp.get_operation().0,
+        format!("{} ({})", operation, arp.get_operation().0),
+        6,
+        2,
+    );
+    node.field(
+        "arp.src.hw_mac",
+        "Sender MAC address",
+        arp.get_sender_hw_addr(),
+        8,
+        6,
+    );
+    node.field(
+        "arp.src.proto_ipv4",
+        "Sender IP address",
+        arp.get_sender_proto_addr(),
+        14,
+        4,
+    );
+    node.field(
+        "arp.dst.hw_mac",
+        "Target MAC address",
+        arp.get_target_hw_addr(),
+        18,
+        6,
+    );
+    node.field(
+        "arp.dst.proto_ipv4",
+        "Target IP address",
+        arp.get_target_proto_addr(),
+        24,
+        4,
+    );
+    node
+}
+
+/// The IPv4 header; its payload is for the next layer.
+pub fn ipv4(ip: &Ipv4Packet, offset: usize) -> ProtocolNode {
+    let header_len = (ip.get_header_length() as usize * 4).clamp(20, ip.packet().len());
+    let mut node = ProtocolNode::layer(
+        "ip",
+        format!(
+            "Internet Protocol Version 4, Src: {}, Dst: {}",
+            ip.get_source(),
+            ip.get_destination()
+        ),
+        offset,
+        header_len,
+    );
+    node.field("ip.version", "Version", ip.get_version(), 0, 1);
+    node.described(
+        "ip.hdr_len",
+        "Header length",
+        ip.get_header_length() as usize * 4,
+        format!(
+            "{} bytes ({})",
+            ip.get_header_length() as usize * 4,
+            ip.get_header_length()
+        ),
+        0,
+        1,
+    );
+    node.described(
+        "ip.dsfield",
+        "Differentiated services",
+        ip.get_dscp() << 2 | ip.get_ecn(),
+        format!("DSCP {}, ECN {}", ip.get_dscp(), ip.get_ecn()),
+        1,
+        1,
+    );
+    node.field("ip.len", "Total length", ip.get_total_length(), 2, 2);
+    node.described(
+        "ip.id",
+        "Identification",
+        ip.get_identification(),
+        format!(
+            "0x{:04x} ({})",
+            ip.get_identification(),
+            ip.get_identification()
+        ),
+        4,
+        2,
+    );
+    let flags = ip.get_flags();
+    let flag_names: Vec<&str> = [(0b010, "Don't fragment"), (0b001, "More fragments")]
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    let flags_node = node.described(
+        "ip.flags",
+        "Flags",
+        flags,
+        format!("0x{:x} {}", flags, flag_names.join(", "))
+            .trim_end()
+            .to_string(),
+        6,
+        1,
+    );
+    flags_node.field("ip.flags.df", "Don't fragment", flags & 0b010 != 0, 0, 1);
+    flags_node.field("ip.flags.mf", "More fragments", flags & 0b001 != 0, 0, 1);
+    node.field(
+        "ip.frag_offset",
+        "Fragment offset",
+        ip.get_fragment_offset() as u32 * 8,
+        6,
+        2,
+    );
+    node.field("ip.ttl", "Time to live", ip.get_ttl(), 8, 1);
+    node.described(
+        "ip.proto",
+        "Protocol",
+        ip.get_next_level_protocol().0,
+        ip_protocol(ip.get_next_level_protocol()),
+        9,
+        1,
+    );
+    node.field(
+        "ip.checksum",
+        "Header checksum",
+        hex16(ip.get_checksum()),
+        10,
+        2,
+    );
+    node.field("ip.src", "Source address", ip.get_source(), 12, 4);
+    node.field("ip.dst", "Destination address", ip.get_destination(), 16, 4);
+    if header_len > 20 {
+        node.field(
+            "ip.options",
+            "Options",
+            format!("{} bytes", header_len - 20),
+            20,
+            header_len - 20,
+        );
+    }
+    node
+}
+
+pub fn ipv6(ip: &Ipv6Packet, offset: usize) -> ProtocolNode {
+    let mut node = ProtocolNode::layer(
+        "ipv6",
+        format!(
+            "Internet Protocol Version 6, Src: {}, Dst: {}",
+            ip.get_source(),
+            ip.get_destination()
+        ),
+        offset,
+        Ipv6Packet::minimum_packet_size(),
+    );
+    node.field("ipv6.version", "Version", ip.get_version(), 0, 1);
+    node.field(
+        "ipv6.tclass",
+        "Traffic class",
+        format!("0x{:02x}", ip.get_traffic_class()),
+        0,
+        2,
+    );
+    node.field(
+        "ipv6.flow",
+        "Flow label",
+        format!("0x{:05x}", ip.get_flow_label()),
+        1,
+        3,
+    );
+    node.field("ipv6.plen", "Payload length", ip.get_payload_length(), 4, 2);
+    node.described(
+        "ipv6.nxt",
+        "Next header",
+        ip.get_next_header().0,
+        ip_protocol(ip.get_next_header()),
+        6,
+        1,
+    );
+    node.field("ipv6.hlim", "Hop limit", ip.get_hop_limit(), 7, 1);
+    node.field("ipv6.src", "Source address", ip.get_source(), 8, 16);
+    node.field(
+        "ipv6.dst",
+        "Destination address",
+        ip.get_destination(),
+        24,
+        16,
+    );
+    node
+}
+
+/// An IPv6 extension header, `header` being all of its bytes.
+pub fn ipv6_extension(kind: IpNextHeaderProtocol, header: &[u8], offset: usize) -> ProtocolNode {
+    let (name, label) = match kind {
+        IpNextHeaderProtocols::Hopopt => ("ipv6.hopopts", "Hop-by-Hop Options"),
+        IpNextHeaderProtocols::Ipv6Route => ("ipv6.routing", "Routing Header"),
+        IpNextHeaderProtocols::Ipv6Frag => ("ipv6.fraghdr", "Fragment Header"),
+        _ => ("ipv6.dstopts", "Destination Options"),
+    };
+    let mut node = ProtocolNode::layer(name, label.to_string(), offset, header.len());
+    let next = IpNextHeaderProtocol(header[0]);
+    node.described(
+        &format!("{}.nxt", name),
+        "Next header",
+        next.0,
+        ip_protocol(next),
+        0,
+        1,
+    );
+    match kind {
+        IpNextHeaderProtocols::Ipv6Frag => {
+            let offset_and_more = be16(header, 2);
+            node.field(
+                "ipv6.fragment.offset",
+                "Offset",
+                (offset_and_more & !0b111) as u32,
+                2,
+                2,
+            );
+            node.field(
+                "ipv6.fragment.more",
+                "More fragments",
+                offset_and_more & 1 != 0,
+                3,
+                1,
+            );
+            let id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+            node.described(
+                "ipv6.fragment.id",
+                "Identification",
+                id,
+                format!("0x{:08x} ({})", id, id),
+                4,
+                4,
+            );
+        }
+        _ => {
+            node.described(
+                &format!("{}.len", name),
+                "Length",
+                header[1],
+                format!("{} ({} bytes)", header[1], header.len()),
+                1,
+                1,
+            );
+            if kind == IpNextHeaderProtocols::Ipv6Route {
+                node.field("ipv6.routing.type", "Type", header[2], 2, 1);
+                node.field("ipv6.routing.segleft", "Segments left", header[3], 3, 1);
+            } else {
+                node.field(
+                    &format!("{}.options", name),
+                    "Options",
+                    format!("{} bytes", header.len() - 2),
+                    2,
+                    header.len() - 2,
+                );
+            }
+        }
+    }
+    node
+}
+
+/// The fragments an IP datagram was reassembled from: the number of each one's frame, and
+/// where its bytes went in the payload. Not tied to the bytes of any one frame.
+pub fn fragments(ipv6: bool, length: usize, pieces: &[(u32, usize, usize)]) -> ProtocolNode {
+    let (prefix, version) = if ipv6 {
+        ("ipv6", "IPv6")
+    } else {
+        ("ip", "IPv4")
+    };
+    let mut node = ProtocolNode::layer(
+        &format!("{}.fragments", prefix),
+        format!(
+            "{} {} fragments ({} bytes): {}",
+            pieces.len(),
+            version,
+            length,
+            pieces
+                .iter()
+                .map(|(number, _, len)| format!("#{}({})", number, len))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        0,
+        0,
+    );
+    for (number, at, len) in pieces {
+        node.described(
+            &format!("{}.fragment", prefix),
+            "Frame",
+            number,
+            format!("{}, {} bytes at {}", number, len, at),
+            0,
+            0,
+        );
+    }
+    node.field(
+        &format!("{}.reassembled.length", prefix),
+        "Reassembled length",
+        length,
+        0,
+        0,
+    );
+    node
+}
+
+pub fn tcp(tcp: &TcpPacket, offset: usize) -> ProtocolNode {
+    let header_len = tcp.packet().len() - tcp.payload().len();
+    let mut node = ProtocolNode::layer(
+        "tcp",
+        format!(
+            "Transmission Control Protocol, Src Port: {}, Dst Port: {}, Seq: {}, Len: {}",
+            tcp.get_source(),
+            tcp.get_destination(),
+            tcp.get_sequence(),
+            tcp.payload().len()
+        ),
+        offset,
+        header_len,
+    );
+    node.field("tcp.srcport", "Source port", tcp.get_source(), 0, 2);
+    node.field(
+        "tcp.dstport",
+        "Destination port",
+        tcp.get_destination(),
+        2,
+        2,
+    );
+    node.field("tcp.seq", "Sequence number", tcp.get_sequence(), 4, 4);
+    node.field(
+        "tcp.ack",
+        "Acknowledgment number",
+        tcp.get_acknowledgement(),
+        8,
+        4,
+    );
+    node.described(
+        "tcp.hdr_len",
+        "Header length",
+        tcp.get_data_offset() as usize * 4,
+        format!(
+            "{} bytes ({})",
+            tcp.get_data_offset() as usize * 4,
+            tcp.get_data_offset()
+        ),
+        12,
+        1,
+    );
+    let flags = tcp.get_flags();
+    let flag_names = [
+        (TcpFlags::CWR, "CWR", "tcp.flags.cwr"),
+        (TcpFlags::ECE, "ECE", "tcp.flags.ece"),
+        (TcpFlags::URG, "URG", "tcp.flags.urg"),
+        (TcpFlags::ACK, "ACK", "tcp.flags.ack"),
+        (TcpFlags::PSH, "PSH", "tcp.flags.push"),
+        (TcpFlags::RST, "RST", "tcp.flags.reset"),
+        (TcpFlags::SYN, "SYN", "tcp.flags.syn"),
+        (TcpFlags::FIN, "FIN", "tcp.flags.fin"),
+    ];
+    let set: Vec<&str> = flag_names
+        .iter()
+        .filter(|(flag, _, _)| flags & flag != 0)
+        .map(|(_, label, _)| *label)
+        .collect();
+    let flags_node = node.described(
+        "tcp.flags",
+        "Flags",
+        format!("0x{:02x}", flags),
+        format!("0x{:02x} ({})", flags, set.join(", ")),
+        13,
+        1,
+    );
+    for (flag, label, name) in flag_names {
+        flags_node.field(name, label, flags & flag != 0, 0, 1);
+    }
+    node.field("tcp.window_size", "Window", tcp.get_window(), 14, 2);
+    node.field("tcp.checksum", "Checksum", hex16(tcp.get_checksum()), 16, 2);
+    node.field(
+        "tcp.urgent_pointer",
+        "Urgent pointer",
+        tcp.get_urgent_ptr(),
+        18,
+        2,
+    );
+    if header_len > 20 {
+        node.field(
+            "tcp.options",
+            "Options",
+            format!("{} bytes", header_len - 20),
+            20,
+            header_len - 20,
+        );
+    }
+    node
+}
+
+pub fn udp(udp: &UdpPacket, offset: usize) -> ProtocolNode {
+    let mut node = ProtocolNode::layer(
+        "udp",
+        format!(
+            "User Datagram Protocol, Src Port: {}, Dst Port: {}",
+            udp.get_source(),
+            udp.get_destination()
+        ),
+        offset,
+        UdpPacket::minimum_packet_size(),
+    );
+    node.field("udp.srcport", "Source port", udp.get_source(), 0, 2);
+    node.field(
+        "udp.dstport",
+        "Destination port",
+        udp.get_destination(),
+        2,
+        2,
+    );
+    node.field("udp.length", "Length", udp.get_length(), 4, 2);
+    node.field("udp.checksum", "Checksum", hex16(udp.get_checksum()), 6, 2);
+    node
+}
+
+pub fn icmp(icmp: &IcmpPacket, offset: usize) -> ProtocolNode {
+    let icmp_type = icmp.get_icmp_type().0;
+    // echo request and reply carry an identifier and sequence number
+    let echo = matches!(icmp_type, 0 | 8) && icmp.packet().len() >= 8;
+    let mut node = ProtocolNode::layer(
+        "icmp",
+        "Internet Control Message Protocol".to_string(),
+        offset,
+        if echo {
+            8
+        } else {
+            IcmpPacket::minimum_packet_size()
+        },
+    );
+    node.described(
+        "icmp.type",
+        "Type",
+        icmp_type,
+        named(icmp_type, icmp_type_name(icmp_type)),
+        0,
+        1,
+    );
+    node.field("icmp.code", "Code", icmp.get_icmp_code().0, 1, 1);
+    node.field(
+        "icmp.checksum",
+        "Checksum",
+        hex16(icmp.get_checksum()),
+        2,
+        2,
+    );
+    if echo {
+        let packet = icmp.packet();
+        node.field("icmp.ident", "Identifier", be16(packet, 4), 4, 2);
+        node.field("icmp.seq", "Sequence number", be16(packet, 6), 6, 2);
+    }
+    node
+}
+
+pub fn icmpv6(icmp: &Icmpv6Packet, offset: usize) -> ProtocolNode {
+    let icmp_type = icmp.get_icmpv6_type().0;
+    let echo = matches!(icmp_type, 128 | 129) && icmp.packet().len() >= 8;
+    let mut node = ProtocolNode::layer(
+        "icmpv6",
+        "Internet Control Message Protocol v6".to_string(),
+        offset,
+        if echo {
+            8
+        } else {
+            Icmpv6Packet::minimum_packet_size()
+        },
+    );
+    node.described(
+        "icmpv6.type",
+        "Type",
+        icmp_type,
+        named(icmp_type, icmpv6_type_name(icmp_type)),
+        0,
+        1,
+    );
+    node.field("icmpv6.code", "Code", icmp.get_icmpv6_code().0, 1, 1);
+    node.field(
+        "icmpv6.checksum",
+        "Checksum",
+        hex16(icmp.get_checksum()),
+        2,
+        2,
+    );
+    if echo {
+        let packet = icmp.packet();
+        node.field(
+            "icmpv6.echo.identifier",
+            "Identifier",
+            be16(packet, 4),
+            4,
+            2,
+        );
+        node.field(
+            "icmpv6.echo.sequence_number",
+            "Sequence number",
+            be16(packet, 6),
+            6,
+            2,
+        );
+    }
+    node
+}
+
+/// Bytes no dissector went into. `None` when there are none.
+pub fn data(payload: &[u8], offset: usize) -> Option<ProtocolNode> {
+    (!payload.is_empty()).then(|| {
+        ProtocolNode::layer(
+            "data",
+            format!(
+                "Data ({} byte{})",
+                payload.len(),
+                if payload.len() == 1 { "" } else { "s" }
+            ),
+            offset,
+            payload.len(),
+        )
+    })
+}
+
+fn ether_type(ethertype: EtherType) -> String {
+    named(
+        format!("0x{:04x}", ethertype.0),
+        protocols::ether_type_name(ethertype.0),
+    )
+}
+
+fn ip_protocol(protocol: IpNextHeaderProtocol) -> String {
+    named(protocol.0, protocols::ip_protocol_name(protocol.0))
+}
+
+/// A number with its name, as `name (number)`, or just the number.
+fn named(number: impl Display, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{} ({})", name, number),
+        None => number.to_string(),
+    }
+}
+
+/// The address family in a null or loopback header. Loopback headers are big-endian, null
+/// ones are in the byte order of the host that captured them, which a family, being a small
+/// number, gives away.
+fn null_family(link_type: u32, header: &[u8]) -> Option<u32> {
+    let bytes: [u8; 4] = header.get(..4)?.try_into().ok()?;
+    let family = match link_type {
+        LINKTYPE_LOOP => u32::from_be_bytes(bytes),
+        _ => match u32::from_le_bytes(bytes) {
+            family @ ..=0xffff => family,
+            _ => u32::from_be_bytes(bytes),
+        },
+    };
+    Some(family)
+}
+
+/// IPv6 has a different number on Linux, the BSDs and macOS.
+fn address_family_name(family: u32) -> Option<&'static str> {
+    match family {
+        2 => Some("AF_INET"),
+        10 | 24 | 28 | 30 => Some("AF_INET6"),
+        _ => None,
+    }
+}
+
+fn icmp_type_name(icmp_type: u8) -> Option<&'static str> {
+    Some(match icmp_type {
+        0 => "Echo reply",
+        3 => "Destination unreachable",
+        4 => "Source quench",
+        5 => "Redirect",
+        8 => "Echo request",
+        9 => "Router advertisement",
+        10 => "Router solicitation",
+        11 => "Time exceeded",
+        12 => "Parameter problem",
+        13 => "Timestamp",
+        14 => "Timestamp reply",
+        _ => return None,
+    })
+}
+
+fn icmpv6_type_name(icmp_type: u8) -> Option<&'static str> {
+    Some(match icmp_type {
+        1 => "Destination unreachable",
+        2 => "Packet too big",
+        3 => "Time exceeded",
+        4 => "Parameter problem",
+        128 => "Echo request",
+        129 => "Echo reply",
+        130 => "Multicast listener query",
+        131 => "Multicast listener report",
+        133 => "Router solicitation",
+        134 => "Router advertisement",
+        135 => "Neighbor solicitation",
+        136 => "Neighbor advertisement",
+        137 => "Redirect",
+        143 => "Multicast listener report v2",
+        _ => return None,
+    })
+}
+
+fn be16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn hex16(value: u16) -> String {
+    format!("0x{:04x}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::show_packets::process_frame;
+
+    /// An IPv4 header, then a TCP segment carrying `ping`, from 10.0.0.1:40000 to
+    /// 10.0.0.2:5001.
+    fn ipv4_tcp() -> Vec<u8> {
+        // version and header length, DSCP, total length, id, DF, TTL, TCP, checksum
+        let mut packet = vec![
+            0x45, 0x00, 0x00, 0x2c, 0x12, 0x34, 0x40, 0x00, 0x40, 0x06, 0xbe, 0xef,
+        ];
+        packet.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        // ports, sequence and acknowledgment numbers
+        packet.extend_from_slice(&[0x9c, 0x40, 0x13, 0x89, 0, 0, 0, 1, 0, 0, 0, 2]);
+        // header length, SYN and ACK, window, checksum, urgent pointer
+        packet.extend_from_slice(&[0x50, 0x12, 0xfa, 0xf0, 0xab, 0xcd, 0x00, 0x00]);
+        packet.extend_from_slice(b"ping");
+        packet
+    }
+
+    /// Ethernet from 02:00:00:00:00:01 to 02:00:00:00:00:02, up to the ethertype.
+    fn ethernet(ethertype: u16) -> Vec<u8> {
+        let mut frame = vec![2, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 1];
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame
+    }
+
+    fn dissect(link_type: u32, data: &[u8]) -> Vec<ProtocolNode> {
+        let mut frame = RawFrame::new("eth0", data, 0, 0);
+        frame.link_type = link_type;
+        process_frame(frame, &mut 0)
+            .detailed_info
+            .expect("every frame has details")
+            .tree
+    }
+
+    fn find<'a>(nodes: &'a [ProtocolNode], name: &str) -> &'a ProtocolNode {
+        fn search<'a>(nodes: &'a [ProtocolNode], name: &str) -> Option<&'a ProtocolNode> {
+            nodes.iter().find_map(|node| {
+                (node.name == name)
+                    .then_some(node)
+                    .or_else(|| search(&node.children, name))
+            })
+        }
+        search(nodes, name).unwrap_or_else(|| panic!("no {} in the tree", name))
+    }
+
+    /// The bytes `name` says it was read from.
+    fn bytes<'a>(data: &'a [u8], tree: &[ProtocolNode], name: &str) -> &'a [u8] {
+        let node = find(tree, name);
+        &data[node.offset..node.offset + node.length]
+    }
+
+    fn assert_within(data: &[u8], nodes: &[ProtocolNode]) {
+        for node in nodes {
+            assert!(
+                node.offset + node.length <= data.len(),
+                "{} runs past the frame",
+                node.name
+            );
+            assert_within(data, &node.children);
+        }
+    }
+
+    #[test]
+    fn places_the_fields_of_an_ethernet_ipv4_tcp_frame() {
+        let mut data = ethernet(0x0800);
+        data.extend(ipv4_tcp());
+        let tree = dissect(LINKTYPE_ETHERNET, &data);
+        assert_within(&data, &tree);
+
+        let layers: Vec<(&str, usize, usize)> = tree
+            .iter()
+            .map(|node| (node.name.as_str(), node.offset, node.length))
+            .collect();
+        assert_eq!(
+            layers,
+            [
+                ("frame", 0, 58),
+                ("eth", 0, 14),
+                ("ip", 14, 20),
+                ("tcp", 34, 20),
+                ("data", 54, 4)
+            ]
+        );
+        let expected: [(&str, &[u8]); 23] = [
+            ("eth.dst", &[2, 0, 0, 0, 0, 2]),
+            ("eth.src", &[2, 0, 0, 0, 0, 1]),
+            ("eth.type", &[0x08, 0x00]),
+            ("ip.version", &[0x45]),
+            ("ip.hdr_len", &[0x45]),
+            ("ip.dsfield", &[0x00]),
+            ("ip.len", &[0x00, 0x2c]),
+            ("ip.id", &[0x12, 0x34]),
+            ("ip.flags.df", &[0x40]),
+            ("ip.frag_offset", &[0x40, 0x00]),
+            ("ip.ttl", &[0x40]),
+            ("ip.proto", &[0x06]),
+            ("ip.checksum", &[0xbe, 0xef]),
+            ("ip.src", &[10, 0, 0, 1]),
+            ("ip.dst", &[10, 0, 0, 2]),
+            ("tcp.srcport", &[0x9c, 0x40]),
+            ("tcp.dstport", &[0x13, 0x89]),
+            ("tcp.seq", &[0, 0, 0, 1]),
+            ("tcp.ack", &[0, 0, 0, 2]),
+            ("tcp.flags.syn", &[0x12]),
+            ("tcp.window_size", &[0xfa, 0xf0]),
+            ("tcp.checksum", &[0xab, 0xcd]),
+            ("tcp.urgent_pointer", &[0x00, 0x00]),
+        ];
+        for (name, expected) in expected {
+            assert_eq!(bytes(&data, &tree, name), expected, "{}", name);
+        }
+        assert_eq!(find(&tree, "tcp.srcport").value, "40000");
+        assert_eq!(find(&tree, "ip.ttl").value, "64");
+        assert_eq!(bytes(&data, &tree, "data"), b"ping");
+    }
+
+    #[test]
+    fn places_the_fields_of_a_vlan_tag() {
+        let mut data = ethernet(0x8100);
+        // priority 5, ID 100, then what the tag carries
+        data.extend_from_slice(&[0xa0, 0x64, 0x08, 0x00]);
+        data.extend(ipv4_tcp());
+        let tree = dissect(LINKTYPE_ETHERNET, &data);
+        assert_within(&data, &tree);
+
+        let eth = find(&tree, "eth");
+        assert_eq!((eth.offset, eth.length), (0, 14));
+        assert_eq!(bytes(&data, &tree, "eth.type"), [0x81, 0x00]);
+        let vlan = find(&tree, "vlan");
+        assert_eq!((vlan.offset, vlan.length), (14, 4));
+        assert_eq!(bytes(&data, &tree, "vlan.id"), [0xa0, 0x64]);
+        assert_eq!(find(&tree, "vlan.id").value, "100");
+        assert_eq!(bytes(&data, &tree, "vlan.priority"), [0xa0]);
+        assert_eq!(find(&tree, "vlan.priority").value, "5");
+        assert_eq!(bytes(&data, &tree, "vlan.etype"), [0x08, 0x00]);
+        let ip = find(&tree, "ip");
+        assert_eq!((ip.offset, ip.length), (18, 20));
+        assert_eq!(bytes(&data, &tree, "ip.src"), [10, 0, 0, 1]);
+        assert_eq!(bytes(&data, &tree, "tcp.dstport"), [0x13, 0x89]);
+    }
+
+    #[test]
+    fn shows_the_address_family_of_null_and_loopback_headers() {
+        let cases: [(u32, [u8; 4], &str); 4] = [
+            // little-endian and big-endian capturing hosts
+            (LINKTYPE_NULL, [2, 0, 0, 0], "Family: AF_INET (2)"),
+            (LINKTYPE_NULL, [0, 0, 0, 2], "Family: AF_INET (2)"),
+            (LINKTYPE_NULL, [30, 0, 0, 0], "Family: AF_INET6 (30)"),
+            (LINKTYPE_LOOP, [0, 0, 0, 24], "Family: AF_INET6 (24)"),
+        ];
+        for (link_type, header, display) in cases {
+            let mut data = header.to_vec();
+            data.extend(ipv4_tcp());
+            let tree = dissect(link_type, &data);
+            let family = find(&tree, "null.family");
+            assert_eq!(family.display, display);
+            assert_eq!((family.offset, family.length), (0, 4));
+            assert_eq!(find(&tree, "ip").offset, 4);
+        }
+    }
+}
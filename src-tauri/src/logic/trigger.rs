@@ -0,0 +1,269 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    frame::RawFrame,
+    pcap::PcapngWriter,
+    show_packets::FormatedPacket,
+    store::{self, DisplayFilter},
+};
+
+/// Memory the pre-trigger window may use when nothing else is configured.
+pub const DEFAULT_WINDOW_BYTES: usize = 64 * 1024 * 1024;
+
+/// A capture mode that keeps the last `pre_secs` of traffic in memory, so that when a trigger
+/// fires, what led up to it can be saved along with what follows.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TriggerOptions {
+    /// Evidence files are written here.
+    pub directory: PathBuf,
+    pub pre_secs: f64,
+    pub post_secs: f64,
+    /// Memory the pre-trigger window may use, [`DEFAULT_WINDOW_BYTES`] when not given. Past it
+    /// the oldest frames are dropped, even if they're still within `pre_secs`.
+    pub max_bytes: Option<usize>,
+    /// Fires a trigger on every packet that matches, on top of the ones fired by alerts.
+    pub on_match: Option<DisplayFilter>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TriggerInfo {
+    pub id: u32,
+    /// What fired it, e.g. the alert or the matching packet.
+    pub label: String,
+    pub fired_at: f64,
+    pub path: PathBuf,
+    /// Frames written to the evidence file so far.
+    pub packets: usize,
+    /// Set once the post-trigger window is over and the file is final.
+    pub complete: bool,
+}
+
+struct ActiveTrigger {
+    info: TriggerInfo,
+    writer: PcapngWriter<BufWriter<File>>,
+}
+
+/// The rolling pre-trigger window of one capture and the evidence files being written from it.
+pub struct TriggerBuffer {
+    options: TriggerOptions,
+    /// Goes into every file name, so evidence from different captures never collides.
+    name: String,
+    window: VecDeque<RawFrame>,
+    /// Footprint of the frames in `window`.
+    window_bytes: usize,
+    active: Vec<ActiveTrigger>,
+    finished: Vec<TriggerInfo>,
+    next_id: u32,
+}
+
+impl TriggerBuffer {
+    pub fn new(options: TriggerOptions, name: &str) -> io::Result<Self> {
+        fs::create_dir_all(&options.directory)?;
+        Ok(Self {
+            options,
+            name: name.replace(|c: char| !c.is_ascii_alphanumeric(), "-"),
+            window: VecDeque::new(),
+            window_bytes: 0,
+            active: Vec::new(),
+            finished: Vec::new(),
+            next_id: 1,
+        })
+    }
+
+    /// Feeds newly captured packets through the window, firing match triggers and extending
+    /// the evidence files still inside their post-trigger window.
+    pub fn push(&mut self, packets: &[FormatedPacket]) -> io::Result<()> {
+        for packet in packets {
+            // imported JSON without frame bytes has nothing to write
+            if packet.frame.data.is_empty() {
+                continue;
+            }
+            let at = packet.frame.timestamp();
+            self.finish_before(at)?;
+            for trigger in self.active.iter_mut() {
+                trigger.writer.write_record(&packet.frame.to_record())?;
+                trigger.info.packets += 1;
+            }
+            self.window_bytes += store::frame_footprint(&packet.frame);
+            self.window.push_back(packet.frame.clone());
+            let max_bytes = self.options.max_bytes.unwrap_or(DEFAULT_WINDOW_BYTES);
+            // the newest frame always stays, so a match can still be saved
+            while self.window.len() > 1
+                && self.window.front().is_some_and(|oldest| {
+                    oldest.timestamp() < at - self.options.pre_secs || self.window_bytes > max_bytes
+                })
+            {
+                if let Some(oldest) = self.window.pop_front() {
+                    self.window_bytes -= store::frame_footprint(&oldest);
+                }
+            }
+
+            // matches inside a window that's still being written don't open another file
+            let matched = self.active.is_empty()
+                && self
+                    .options
+                    .on_match
+                    .as_ref()
+                    .is_some_and(|filter| filter.matches(packet));
+            if matched {
+                // the packet itself is already in the window, so it's written as pre-trigger
+                self.fire(format!("match on packet {}", packet.number), at)?;
+            }
+        }
+        for trigger in self.active.iter_mut() {
+            trigger.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Starts an evidence file holding the window before `at`; packets keep being added to it
+    /// for `post_secs` after.
+    pub fn fire(&mut self, label: String, at: f64) -> io::Result<TriggerInfo> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let stamp = DateTime::from_timestamp(at as i64, 0)
+            .map(|time| {
+                time.with_timezone(&Local)
+                    .format("%Y%m%d%H%M%S")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let path = self.options.directory.join(format!(
+            "netwatch_{}_trigger_{:03}_{}.pcapng",
+            self.name, id, stamp
+        ));
+        let mut writer = PcapngWriter::new(BufWriter::new(File::create(&path)?))?;
+        let mut packets = 0;
+        for frame in self
+            .window
+            .iter()
+            .filter(|frame| frame.timestamp() >= at - self.options.pre_secs)
+        {
+            writer.write_record(&frame.to_record())?;
+            packets += 1;
+        }
+        writer.flush()?;
+        log::info!("trigger {} ({}): writing {}", id, label, path.display());
+
+        let info = TriggerInfo {
+            id,
+            label,
+            fired_at: at,
+            path,
+            packets,
+            complete: false,
+        };
+        self.active.push(ActiveTrigger {
+            info: info.clone(),
+            writer,
+        });
+        Ok(info)
+    }
+
+    /// Closes the evidence files whose post-trigger window ended before `now`. Called on a
+    /// timer too, as a quiet link may not deliver the packet that would close them.
+    pub fn finish_before(&mut self, now: f64) -> io::Result<()> {
+        let post_secs = self.options.post_secs;
+        let (done, active) = self
+            .active
+            .drain(..)
+            .partition(|trigger| trigger.info.fired_at + post_secs < now);
+        self.active = active;
+        for trigger in done {
+            self.close(trigger)?;
+        }
+        Ok(())
+    }
+
+    /// Closes every evidence file, e.g. when the capture ends before their windows do.
+    pub fn finish(&mut self) -> io::Result<()> {
+        for trigger in std::mem::take(&mut self.active) {
+            self.close(trigger)?;
+        }
+        Ok(())
+    }
+
+    pub fn triggers(&self) -> Vec<TriggerInfo> {
+        let mut triggers: Vec<TriggerInfo> = self
+            .finished
+            .iter()
+            .cloned()
+            .chain(self.active.iter().map(|trigger| trigger.info.clone()))
+            .collect();
+        triggers.sort_by_key(|trigger| trigger.id);
+        triggers
+    }
+
+    fn close(&mut self, mut trigger: ActiveTrigger) -> io::Result<()> {
+        trigger.writer.flush()?;
+        trigger.info.complete = true;
+        log::info!(
+            "trigger {}: {} packets written",
+            trigger.info.id,
+            trigger.info.packets
+        );
+        self.finished.push(trigger.info);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::show_packets::dissect;
+
+    fn window(pre_secs: f64, max_bytes: Option<usize>) -> TriggerBuffer {
+        let options = TriggerOptions {
+            directory: std::env::temp_dir().join("netwatch-trigger-window"),
+            pre_secs,
+            post_secs: 1.0,
+            max_bytes,
+            on_match: None,
+        };
+        TriggerBuffer::new(options, "eth0").unwrap()
+    }
+
+    fn packets(count: usize, len: usize) -> Vec<FormatedPacket> {
+        let mut count_fp = 0;
+        (0..count)
+            .map(|i| {
+                dissect(
+                    RawFrame::new("eth0", &vec![0; len], i as i64, 0),
+                    &mut count_fp,
+                )
+                .0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drops_frames_older_than_the_window() {
+        let mut buffer = window(10.0, None);
+        buffer.push(&packets(100, 64)).unwrap();
+        assert_eq!(buffer.window.len(), 11);
+        assert_eq!(buffer.window.front().unwrap().ts_sec, 89);
+    }
+
+    #[test]
+    fn drops_the_oldest_frames_past_the_byte_cap() {
+        let frame = store::frame_footprint(&packets(1, 1500)[0].frame);
+        let mut buffer = window(3600.0, Some(frame * 5 + frame / 2));
+        buffer.push(&packets(100, 1500)).unwrap();
+        assert_eq!(buffer.window.len(), 5);
+        assert_eq!(buffer.window.front().unwrap().ts_sec, 95);
+        assert_eq!(buffer.window_bytes, frame * 5);
+
+        // even a cap below one frame keeps the newest
+        let mut buffer = window(3600.0, Some(1));
+        buffer.push(&packets(3, 1500)).unwrap();
+        assert_eq!(buffer.window.len(), 1);
+    }
+}
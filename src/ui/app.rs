@@ -1,7 +1,11 @@
-use std::collections::HashMap;
+use std::{cell::Cell, collections::HashMap, rc::Rc};
 
 use crate::errors::ApiError;
 
+use super::components::capture_options::{CaptureOptions, CaptureOptionsForm};
+use super::components::hex_view::HexView;
+use super::components::packet_details::PacketDetails;
+use super::components::session_bar::SessionBar;
 use super::components::sidenavbar::SideNavBar;
 use super::components::AppContext;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -9,18 +13,106 @@ use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
 use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::{platform::spawn_local, prelude::*};
-use yew_hooks::{use_async, use_interval, use_state_ptr_eq};
+use yew_hooks::{use_async, use_state_ptr_eq};
 
+/// A line of the packet table. The rest of the packet is fetched when the line is selected.
 #[derive(Deserialize, Clone, PartialEq)]
-pub struct FormatedPacket {
+pub struct PacketRow {
     pub number: u32,
     pub time: String,
-    pub src_ip: String,
-    pub dst_ip: String,
+    pub src_ip: Option<String>,
+    pub dst_ip: Option<String>,
+    pub src_mac: Option<String>,
+    pub dst_mac: Option<String>,
     pub protocol: String,
     pub lenght: usize,
     pub info: String,
+    /// The 802.1Q tags the frame carried, outermost first.
+    #[serde(default)]
+    pub vlans: Vec<VlanTag>,
+    pub relative: f64,
+    pub delta: f64,
+    pub ts_sec: i64,
+    pub ts_nsec: u32,
+}
+
+/// The selected packet as `get_packet` returns it, of which only the details are read.
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct FormatedPacket {
     pub detailed_info: Option<DetailedInfo>,
+    #[serde(default)]
+    pub frame: Frame,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct VlanTag {
+    pub tpid: u16,
+    pub id: u16,
+    pub pcp: u8,
+    pub dei: bool,
+}
+
+/// The frame the packet was dissected from, its bytes as hex.
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct Frame {
+    #[serde(default)]
+    pub data: String,
+}
+
+impl Frame {
+    pub fn bytes(&self) -> Vec<u8> {
+        (0..self.data.len() / 2)
+            .filter_map(|i| u8::from_str_radix(self.data.get(i * 2..i * 2 + 2)?, 16).ok())
+            .collect()
+    }
+}
+
+/// How the time column is shown.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    Utc,
+    Local,
+    /// Seconds since the first packet of the capture.
+    Relative,
+    /// Seconds since the previous packet.
+    Delta,
+}
+
+impl TimeFormat {
+    fn parse(value: &str) -> Self {
+        match value {
+            "local" => TimeFormat::Local,
+            "relative" => TimeFormat::Relative,
+            "delta" => TimeFormat::Delta,
+            _ => TimeFormat::Utc,
+        }
+    }
+
+    pub fn format(self, packet: &PacketRow) -> String {
+        let PacketRow {
+            ts_sec, ts_nsec, ..
+        } = *packet;
+        match self {
+            // `time` is already UTC with nanoseconds; old JSON exports hold a bare local time
+            TimeFormat::Utc => packet.time.replacen('T', " ", 1).replace('Z', " UTC"),
+            TimeFormat::Local if ts_sec == 0 && ts_nsec == 0 => packet.time.clone(),
+            TimeFormat::Local => {
+                let at = js_sys::Date::new(&JsValue::from_f64(ts_sec as f64 * 1000.0));
+                format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09}",
+                    at.get_full_year(),
+                    at.get_month() + 1,
+                    at.get_date(),
+                    at.get_hours(),
+                    at.get_minutes(),
+                    at.get_seconds(),
+                    ts_nsec
+                )
+            }
+            TimeFormat::Relative => format!("{:.9}", packet.relative),
+            TimeFormat::Delta => format!("{:.9}", packet.delta),
+        }
+    }
 }
 
 #[derive(Clone, Deserialize, PartialEq)]
@@ -32,6 +124,20 @@ pub struct DetailedInfo {
     pub payload_length: usize,
     pub packet_length: usize,
     pub payload_data: String,
+    #[serde(default)]
+    pub tree: Vec<ProtocolNode>,
+}
+
+/// A layer of the packet, or one of its fields, with the bytes it was read from.
+#[derive(Clone, Deserialize, PartialEq)]
+pub struct ProtocolNode {
+    pub name: String,
+    pub value: String,
+    pub display: String,
+    pub offset: usize,
+    pub length: usize,
+    #[serde(default)]
+    pub children: Vec<ProtocolNode>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -42,22 +148,319 @@ struct Detect {
     avg_dst_ip_freq: f64,
     avg_packet_rate: f64,
     avg_packet_length: f64,
+    #[serde(default)]
+    evidence: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct DiscoveredHost {
+    ip: String,
+    mac: String,
+    probe: String,
+    response_ms: f64,
+    packets: usize,
+    first_packet: Option<u32>,
+    last_packet: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct StoreLimits {
+    pub max_packets: Option<usize>,
+    pub max_bytes: Option<usize>,
+    pub max_age_secs: Option<f64>,
+    pub spill_path: Option<String>,
+}
+
+/// When a capture ends by itself; unset fields don't apply.
+#[derive(Serialize, Clone, PartialEq, Default)]
+pub struct StopConditions {
+    pub max_packets: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub max_duration_secs: Option<f64>,
+    pub until_match: Option<DisplayFilter>,
+}
+
+/// Writes the capture to a rotating set of files in `directory` as it runs.
+#[derive(Serialize, Clone, PartialEq)]
+pub struct RingBufferOptions {
+    pub directory: String,
+    pub max_file_bytes: Option<u64>,
+    pub max_file_secs: Option<f64>,
+    pub max_files: Option<usize>,
+}
+
+/// Keeps the last `pre_secs` of traffic so a trigger can save what led up to it.
+#[derive(Serialize, Clone, PartialEq)]
+pub struct TriggerOptions {
+    pub directory: String,
+    pub pre_secs: f64,
+    pub post_secs: f64,
+    pub max_bytes: Option<usize>,
+    pub on_match: Option<DisplayFilter>,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct TriggerInfo {
+    pub id: u32,
+    pub label: String,
+    pub path: String,
+    pub packets: usize,
+}
+
+#[derive(Serialize, Clone, PartialEq)]
+pub struct DisplayFilter {
+    pub protocol: String,
+    pub ip: String,
+    pub vlan: Option<u16>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct StoreUsage {
+    pub packets: usize,
+    pub bytes: usize,
+    pub evicted: u64,
+    pub spilled: u64,
+    pub fill: f64,
+    pub near_limit: bool,
+    pub limits: StoreLimits,
+    pub spill_error: Option<String>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct SkippedFrames {
+    pub unsupported_link_type: u64,
+    pub unhandled_ether_type: u64,
+    pub unhandled_ip_protocol: u64,
+    pub malformed: u64,
+}
+
+/// How complete a capture is: what was received, dissected, skipped or dropped.
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct CaptureStats {
+    pub frames_received: u64,
+    pub frames_dissected: u64,
+    pub skipped: SkippedFrames,
+    pub bytes: u64,
+    pub read_errors: u64,
+    pub kernel_drops: Option<u64>,
+    pub packets_per_sec: f64,
+    pub bytes_per_sec: f64,
+    #[serde(default)]
+    pub vlans: Vec<VlanStats>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct VlanStats {
+    pub id: u16,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl SkippedFrames {
+    fn total(&self) -> u64 {
+        self.unsupported_link_type
+            + self.unhandled_ether_type
+            + self.unhandled_ip_protocol
+            + self.malformed
+    }
+}
+
+pub const MIB: f64 = 1024.0 * 1024.0;
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub id: u32,
+    pub interface: String,
+    pub filter: Option<String>,
+    pub running: bool,
+    #[serde(default)]
+    pub stop_reason: Option<String>,
+    /// Why the capture stopped on its own, if it failed.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Packets pushed by the backend while a capture runs.
+#[derive(Deserialize)]
+struct PacketsEvent {
+    session_id: u32,
+    packets: Vec<PacketRow>,
+    usage: StoreUsage,
+    stats: CaptureStats,
+}
+
+#[derive(Deserialize)]
+struct TauriEvent<T> {
+    payload: T,
+}
+
+#[derive(Deserialize)]
+struct PacketPage {
+    packets: Vec<PacketRow>,
+    total: usize,
+}
+
+/// Packets fetched per page when browsing the store.
+const PAGE_SIZE: usize = 1_000;
+/// While following a live capture the table keeps at most this many of the newest packets.
+const MAX_SHOWN_PACKETS: usize = 5_000;
+
+/// The slice of the filtered store shown in the table.
+#[derive(Clone, Default, PartialEq)]
+struct PacketList {
+    packets: Vec<PacketRow>,
+    /// Position of the first shown packet among all matching packets.
+    offset: usize,
+    total: usize,
+}
+
+enum PacketListAction {
+    Replace(PacketList),
+    Append(Vec<PacketRow>),
+}
+
+impl Reducible for PacketList {
+    type Action = PacketListAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        match action {
+            PacketListAction::Replace(list) => Rc::new(list),
+            PacketListAction::Append(new) => {
+                if new.is_empty() {
+                    return self;
+                }
+                let mut list = Rc::unwrap_or_clone(self);
+                list.total += new.len();
+                list.packets.extend(new);
+                let excess = list.packets.len().saturating_sub(MAX_SHOWN_PACKETS);
+                list.packets.drain(..excess);
+                list.offset += excess;
+                Rc::new(list)
+            }
+        }
+    }
+}
+
+/// Same matching as the backend's `get_packets`, for packets that arrive by event.
+fn matches_filter(packet: &PacketRow, protocol: &str, ip: &str, vlan: Option<u16>) -> bool {
+    (protocol == "all" || protocol == packet.protocol)
+        && (ip.is_empty()
+            || [&packet.src_ip, &packet.dst_ip]
+                .into_iter()
+                .flatten()
+                .any(|address| address.starts_with(ip)))
+        && vlan.is_none_or(|id| packet.vlans.iter().any(|tag| tag.id == id))
+}
+
+impl PacketRow {
+    /// Frames without IP addresses, e.g. LLDP, are shown by their MAC addresses instead.
+    fn source(&self) -> String {
+        self.src_ip
+            .clone()
+            .or_else(|| self.src_mac.clone())
+            .unwrap_or_default()
+    }
+
+    fn destination(&self) -> String {
+        self.dst_ip
+            .clone()
+            .or_else(|| self.dst_mac.clone())
+            .unwrap_or_default()
+    }
 }
 
 mod start_args {
-    use super::{Deserialize, Serialize};
-    #[derive(Serialize, Deserialize)]
+    use super::Serialize;
+    #[derive(Serialize)]
     pub struct Args<'a> {
         pub interface: &'a str,
+        pub filter: &'a str,
+        pub stop: super::StopConditions,
+        pub ring: Option<super::RingBufferOptions>,
+        pub trigger: Option<super::TriggerOptions>,
     }
 }
 
-mod get_args {
+mod trigger_args {
+    use super::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Args {
+        pub session_id: u32,
+        pub label: String,
+    }
+}
+
+mod session_args {
+    use super::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Args {
+        pub session_id: u32,
+    }
+}
+
+mod packet_args {
+    use super::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Args {
+        pub session_id: u32,
+        pub number: u32,
+    }
+}
+
+mod discover_args {
     use super::{Deserialize, Serialize};
     #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
     pub struct Args {
+        pub interface: String,
+        pub session_id: Option<u32>,
+    }
+}
+
+mod map_port_args {
+    use super::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Args {
+        pub transport: String,
+        pub port: u16,
+        pub protocol: Option<String>,
+    }
+}
+
+mod reparse_args {
+    use super::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Args {
+        pub session_id: u32,
+    }
+}
+
+mod limits_args {
+    use super::{Serialize, StoreLimits};
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Args {
+        pub session_id: u32,
+        pub limits: StoreLimits,
+    }
+}
+
+mod get_args {
+    use super::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Clone)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Args {
+        pub session_id: u32,
         pub ip: String,
         pub protocol: String,
+        pub vlan: Option<u16>,
+        pub offset: Option<usize>,
+        pub limit: Option<usize>,
     }
 }
 
@@ -65,74 +468,440 @@ mod get_args {
 extern "C" {
     #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"])]
     async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], js_name = invoke, catch)]
+    async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
+    async fn listen(event: &str, handler: &Closure<dyn FnMut(JsValue)>) -> JsValue;
 }
 
 #[function_component(Watch)]
 pub fn watch() -> Html {
+    let current_row = use_state(|| None::<PacketRow>);
     let current_packet = use_state(|| None::<FormatedPacket>);
+    let selected_field = use_state(|| None::<ProtocolNode>);
     let app_context: AppContext = use_context::<AppContext>().expect("No AppContext found!");
-    let packets = use_state_eq(|| Vec::<FormatedPacket>::new());
+    let packets = use_reducer(PacketList::default);
     let is_running = use_state(|| false);
     let filter_protocol = use_state(|| "all".to_string());
+    let time_format = use_state(|| TimeFormat::Utc);
     let filter_ip = use_state(|| String::new());
+    let filter_vlan = use_state(|| None::<u16>);
+    let capture_filter_input = use_node_ref();
+    // read when a capture starts, so changing a field doesn't render the view again
+    let capture_options = use_mut_ref(CaptureOptions::default);
+    let capture_error = use_state(|| None::<String>);
+    let store_usage = use_state_eq(StoreUsage::default);
+    let capture_stats = use_state_eq(CaptureStats::default);
+    let store_error = use_state(|| None::<String>);
+
+    // `None` follows the newest packets, `Some` is a page the user browsed to
+    let page_offset = use_state_eq(|| None::<usize>);
+    // the capture shown in the table; others keep running in the background
+    let session_id = use_state_eq(|| None::<u32>);
+    let sessions = use_state_eq(Vec::<SessionInfo>::new);
+
+    let get_sessions = use_async({
+        let sessions = sessions.clone();
+        async move {
+            if let Ok(list) =
+                from_value::<Vec<SessionInfo>>(invoke("get_sessions", JsValue::NULL).await)
+            {
+                sessions.set(list);
+            }
+            Ok::<(), ()>(())
+        }
+    });
 
     let get_packets = use_async({
+        let current_session = *session_id;
         let filters = get_args::Args {
+            session_id: current_session.unwrap_or_default(),
             ip: (*filter_ip).clone(),
             protocol: (*filter_protocol).clone(),
+            vlan: *filter_vlan,
+            offset: None,
+            limit: Some(PAGE_SIZE),
         };
-        let packets = packets.clone();
+        let page_offset = *page_offset;
+        let packets = packets.dispatcher();
+        let store_usage = store_usage.clone();
         async move {
-            let result = invoke("get_packets", to_value(&filters).unwrap()).await;
-            if let Ok(pkts) = from_value::<Vec<FormatedPacket>>(result) {
-                packets.set(pkts);
+            let Some(current_session) = current_session else {
+                packets.dispatch(PacketListAction::Replace(PacketList::default()));
+                store_usage.set(StoreUsage::default());
+                return Ok(());
+            };
+            let fetch = |offset: usize, limit: usize| {
+                let filters = get_args::Args {
+                    offset: Some(offset),
+                    limit: Some(limit),
+                    ..filters.clone()
+                };
+                async move {
+                    from_value::<PacketPage>(
+                        invoke("get_packets", to_value(&filters).unwrap()).await,
+                    )
+                }
+            };
+            let offset = match page_offset {
+                Some(offset) => Some(offset),
+                // ask for the count first, to know where the newest page starts
+                None => fetch(0, 0)
+                    .await
+                    .ok()
+                    .map(|page| page.total.saturating_sub(PAGE_SIZE)),
+            };
+            if let Some(offset) = offset {
+                if let Ok(page) = fetch(offset, PAGE_SIZE).await {
+                    packets.dispatch(PacketListAction::Replace(PacketList {
+                        packets: page.packets,
+                        offset,
+                        total: page.total,
+                    }));
+                }
+            }
+            let usage = invoke(
+                "get_store_usage",
+                to_value(&session_args::Args {
+                    session_id: current_session,
+                })
+                .unwrap(),
+            )
+            .await;
+            if let Ok(usage) = from_value::<StoreUsage>(usage) {
+                store_usage.set(usage);
             }
             Ok::<(), ()>(())
         }
     });
 
+    let get_capture_stats = use_async({
+        let session_id = session_id.clone();
+        let capture_stats = capture_stats.clone();
+        async move {
+            let Some(current_session) = *session_id else {
+                capture_stats.set(CaptureStats::default());
+                return Ok(());
+            };
+            let stats = invoke(
+                "get_capture_stats",
+                to_value(&session_args::Args {
+                    session_id: current_session,
+                })
+                .unwrap(),
+            )
+            .await;
+            if let Ok(stats) = from_value::<CaptureStats>(stats) {
+                capture_stats.set(stats);
+            }
+            Ok::<(), ()>(())
+        }
+    });
+    {
+        let get_capture_stats = get_capture_stats.clone();
+        // live counters come with the packets events; this covers switching sessions
+        use_effect_with((*session_id, *is_running), move |_| {
+            get_capture_stats.run();
+            || ()
+        });
+    }
+
+    // the whole list is only fetched when the filter changes; while capturing, packets are pushed
+    {
+        let get_packets = get_packets.clone();
+        use_effect_with(
+            (
+                *session_id,
+                (*filter_protocol).clone(),
+                (*filter_ip).clone(),
+                *filter_vlan,
+                *page_offset,
+            ),
+            move |_| {
+                get_packets.run();
+                || ()
+            },
+        );
+    }
+
+    // the event handler outlives this render, so it reads the filter through a shared cell
+    let live_filter = use_mut_ref(|| (None, "all".to_string(), String::new(), None, true));
+    *live_filter.borrow_mut() = (
+        *session_id,
+        (*filter_protocol).clone(),
+        (*filter_ip).clone(),
+        *filter_vlan,
+        page_offset.is_none(),
+    );
+
+    {
+        let packets = packets.dispatcher();
+        let store_usage = store_usage.clone();
+        let capture_stats = capture_stats.clone();
+        let live_filter = live_filter.clone();
+        let get_sessions = get_sessions.clone();
+        use_effect_with((), move |_| {
+            // sessions outlive a reload of the window
+            get_sessions.run();
+            let handler = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+                let Ok(event) = from_value::<TauriEvent<PacketsEvent>>(event) else {
+                    return;
+                };
+                let (shown_session, protocol, ip, vlan, following) = live_filter.borrow().clone();
+                if shown_session != Some(event.payload.session_id) {
+                    return;
+                }
+                store_usage.set(event.payload.usage);
+                capture_stats.set(event.payload.stats);
+                // an older page the user is reading stays put
+                if !following {
+                    return;
+                }
+                let matching = event
+                    .payload
+                    .packets
+                    .into_iter()
+                    .filter(|packet| matches_filter(packet, &protocol, &ip, vlan))
+                    .collect();
+                packets.dispatch(PacketListAction::Append(matching));
+            });
+            spawn_local(async move {
+                listen("packets", &handler).await;
+                // the watch view lives as long as the window, so the listener is never removed
+                handler.forget();
+            });
+            || ()
+        });
+    }
+
+    {
+        let is_running = is_running.clone();
+        let capture_error = capture_error.clone();
+        let live_filter = live_filter.clone();
+        let get_sessions = get_sessions.clone();
+        use_effect_with((), move |_| {
+            // a capture that ends on its own, e.g. because its interface went away
+            let handler = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+                let Ok(event) = from_value::<TauriEvent<SessionInfo>>(event) else {
+                    return;
+                };
+                get_sessions.run();
+                if live_filter.borrow().0 != Some(event.payload.id) {
+                    return;
+                }
+                is_running.set(false);
+                capture_error.set(event.payload.error);
+            });
+            spawn_local(async move {
+                listen("capture-status", &handler).await;
+                handler.forget();
+            });
+            || ()
+        });
+    }
+
     let watch = use_async({
         let is_running = is_running.clone();
         let choosed_interface = app_context.interface.clone();
+        let capture_filter_input = capture_filter_input.clone();
+        let capture_options = capture_options.clone();
+        let display_filter = DisplayFilter {
+            protocol: (*filter_protocol).clone(),
+            ip: (*filter_ip).clone(),
+            vlan: *filter_vlan,
+        };
+        let capture_error = capture_error.clone();
+        let packets = packets.dispatcher();
+        let page_offset = page_offset.clone();
+        let session_id = session_id.clone();
+        let get_sessions = get_sessions.clone();
         async move {
             if *is_running {
                 is_running.set(false);
-                let _ = invoke("stop_watch", JsValue::NULL).await;
+                if let Some(current_session) = *session_id {
+                    let _ = invoke(
+                        "stop_watch",
+                        to_value(&session_args::Args {
+                            session_id: current_session,
+                        })
+                        .unwrap(),
+                    )
+                    .await;
+                }
+                get_sessions.run();
             } else {
-                let _ = invoke(
+                let capture_filter = capture_filter_input
+                    .cast::<HtmlInputElement>()
+                    .map(|input| input.value())
+                    .unwrap_or_default();
+                let CaptureOptions {
+                    max_packets,
+                    max_duration_secs,
+                    until_match,
+                    ring,
+                    trigger,
+                } = capture_options.borrow().clone();
+                let stop = StopConditions {
+                    max_packets,
+                    max_bytes: None,
+                    max_duration_secs,
+                    until_match: until_match.then_some(display_filter),
+                };
+                let result = try_invoke(
                     "start_watch",
                     serde_wasm_bindgen::to_value(&start_args::Args {
                         interface: &choosed_interface,
+                        filter: &capture_filter,
+                        stop,
+                        ring,
+                        trigger,
                     })
                     .unwrap(),
                 )
                 .await;
-                is_running.set(true);
+                match result.map(from_value::<u32>) {
+                    Ok(Ok(new_session)) => {
+                        capture_error.set(None);
+                        packets.dispatch(PacketListAction::Replace(PacketList::default()));
+                        page_offset.set(None);
+                        session_id.set(Some(new_session));
+                        is_running.set(true);
+                        get_sessions.run();
+                    }
+                    Ok(Err(e)) => capture_error.set(Some(e.to_string())),
+                    Err(e) => capture_error.set(Some(
+                        ApiError::AppError(e.as_string().unwrap_or_default()).to_string(),
+                    )),
+                }
             }
             Ok::<(), ()>(())
         }
     });
 
-    {
-        let is_running = is_running.clone();
-        let get_packets = get_packets.clone();
+    let toggle_loop = {
+        let watch = watch.clone();
+        Callback::from(move |_| watch.run())
+    };
 
-        use_interval(
-            {
-                let is_running = is_running.clone();
-                move || {
-                    if *is_running {
-                        get_packets.run();
-                    }
+    let last_trigger = use_state(|| None::<TriggerInfo>);
+    let save_evidence = use_async({
+        let session_id = session_id.clone();
+        let capture_error = capture_error.clone();
+        let last_trigger = last_trigger.clone();
+        async move {
+            let Some(current_session) = *session_id else {
+                return Ok(());
+            };
+            let result = try_invoke(
+                "fire_trigger",
+                to_value(&trigger_args::Args {
+                    session_id: current_session,
+                    label: "manual".to_string(),
+                })
+                .unwrap(),
+            )
+            .await;
+            match result.map(from_value::<TriggerInfo>) {
+                Ok(Ok(trigger)) => last_trigger.set(Some(trigger)),
+                Ok(Err(e)) => capture_error.set(Some(e.to_string())),
+                Err(e) => capture_error.set(Some(
+                    ApiError::AppError(e.as_string().unwrap_or_default()).to_string(),
+                )),
+            }
+            Ok::<(), ()>(())
+        }
+    });
+    let on_save_evidence = Callback::from(move |_| save_evidence.run());
+
+    let resent = use_state(|| None::<u32>);
+    let resend = use_async({
+        let session_id = session_id.clone();
+        let current_row = current_row.clone();
+        let capture_error = capture_error.clone();
+        let resent = resent.clone();
+        async move {
+            let (Some(current_session), Some(packet)) = (*session_id, (*current_row).as_ref())
+            else {
+                return Ok(());
+            };
+            let result = try_invoke(
+                "resend_packet",
+                to_value(&packet_args::Args {
+                    session_id: current_session,
+                    number: packet.number,
+                })
+                .unwrap(),
+            )
+            .await;
+            match result {
+                Ok(_) => resent.set(Some(packet.number)),
+                Err(e) => capture_error.set(Some(
+                    ApiError::AppError(e.as_string().unwrap_or_default()).to_string(),
+                )),
+            }
+            Ok::<(), ()>(())
+        }
+    });
+    let on_resend = Callback::from(move |_| resend.run());
+
+    // a row belongs to the session it was picked in
+    {
+        let current_row = current_row.clone();
+        use_effect_with(*session_id, move |_| {
+            current_row.set(None);
+            || ()
+        });
+    }
+    // the table only has rows, so the rest of the selected packet is fetched when it's picked
+    {
+        let current_packet = current_packet.clone();
+        use_effect_with(
+            (*session_id, (*current_row).as_ref().map(|row| row.number)),
+            move |&(session_id, number)| {
+                current_packet.set(None);
+                // a reply for a row that's no longer selected is dropped
+                let cancelled = Rc::new(Cell::new(false));
+                if let (Some(session_id), Some(number)) = (session_id, number) {
+                    let cancelled = cancelled.clone();
+                    spawn_local(async move {
+                        let packet = try_invoke(
+                            "get_packet",
+                            to_value(&packet_args::Args { session_id, number }).unwrap(),
+                        )
+                        .await;
+                        if cancelled.get() {
+                            return;
+                        }
+                        if let Ok(Ok(packet)) = packet.map(from_value::<FormatedPacket>) {
+                            current_packet.set(Some(packet));
+                        }
+                    });
                 }
+                move || cancelled.set(true)
             },
-            if *is_running { 500 } else { 0 },
         );
     }
+    let on_capture_options = {
+        let capture_options = capture_options.clone();
+        Callback::from(move |options: CaptureOptions| *capture_options.borrow_mut() = options)
+    };
+    let on_select_field = {
+        let selected_field = selected_field.clone();
+        Callback::from(move |node: ProtocolNode| selected_field.set(Some(node)))
+    };
 
-    let toggle_loop = {
-        let watch = watch.clone();
-        Callback::from(move |_| watch.run())
+    let choosed_time_format = use_node_ref();
+    let set_time_format = {
+        let time_format = time_format.clone();
+        let choosed_time_format = choosed_time_format.clone();
+
+        Callback::from(move |_| {
+            if let Some(format) = choosed_time_format.cast::<HtmlSelectElement>() {
+                time_format.set(TimeFormat::parse(&format.value()));
+            }
+        })
     };
 
     let choosed_protocol = use_node_ref();
@@ -140,52 +909,283 @@ pub fn watch() -> Html {
 
     let set_protocol = {
         let filter_protocol = filter_protocol.clone();
-        let is_running = is_running.clone();
         let choosed_protocol = choosed_protocol.clone();
-        let get_packets = get_packets.clone();
 
         Callback::from(move |_| {
             if let Some(protocol) = choosed_protocol.cast::<HtmlSelectElement>() {
                 filter_protocol.set(protocol.value());
-                if !*is_running {
-                    get_packets.run();
-                }
             }
         })
     };
 
     let on_search = {
         let filter_ip = filter_ip.clone();
-        let is_running = is_running.clone();
         let search_input = search_input.clone();
-        let get_packets = get_packets.clone();
 
         Callback::from(move |e: SubmitEvent| {
             e.prevent_default();
 
             if let Some(search_ip) = search_input.cast::<HtmlInputElement>() {
                 filter_ip.set(search_ip.value());
-                if !*is_running {
+            }
+        })
+    };
+
+    let vlan_input = use_node_ref();
+    // an empty or invalid ID shows every VLAN, untagged traffic included
+    let on_vlan = {
+        let filter_vlan = filter_vlan.clone();
+        let vlan_input = vlan_input.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            if let Some(input) = vlan_input.cast::<HtmlInputElement>() {
+                filter_vlan.set(input.value().trim().parse().ok());
+            }
+        })
+    };
+
+    let select_session = {
+        let session_id = session_id.clone();
+        let page_offset = page_offset.clone();
+        let is_running = is_running.clone();
+        let capture_error = capture_error.clone();
+        let sessions = sessions.clone();
+        Callback::from(move |selected: Option<u32>| {
+            let session = sessions.iter().find(|session| Some(session.id) == selected);
+            session_id.set(selected);
+            page_offset.set(None);
+            is_running.set(session.is_some_and(|session| session.running));
+            capture_error.set(session.and_then(|session| session.error.clone()));
+        })
+    };
+
+    let close_session = use_async({
+        let session_id = session_id.clone();
+        let is_running = is_running.clone();
+        let get_sessions = get_sessions.clone();
+        async move {
+            if let Some(current_session) = *session_id {
+                let _ = invoke(
+                    "close_session",
+                    to_value(&session_args::Args {
+                        session_id: current_session,
+                    })
+                    .unwrap(),
+                )
+                .await;
+                session_id.set(None);
+                is_running.set(false);
+                get_sessions.run();
+            }
+            Ok::<(), ()>(())
+        }
+    });
+
+    let on_close_session = {
+        let close_session = close_session.clone();
+        Callback::from(move |_| close_session.run())
+    };
+
+    let max_packets_input = use_node_ref();
+    let max_mib_input = use_node_ref();
+    let max_age_input = use_node_ref();
+    let spill_path_input = use_node_ref();
+
+    let apply_limits = use_async({
+        let get_packets = get_packets.clone();
+        let store_error = store_error.clone();
+        let current_session = *session_id;
+        let max_packets_input = max_packets_input.clone();
+        let max_mib_input = max_mib_input.clone();
+        let max_age_input = max_age_input.clone();
+        let spill_path_input = spill_path_input.clone();
+        async move {
+            let value = |input: &NodeRef| {
+                input
+                    .cast::<HtmlInputElement>()
+                    .map(|input| input.value().trim().to_string())
+                    .filter(|value| !value.is_empty())
+            };
+            // an empty or zero field means no limit
+            let number = |input: &NodeRef| {
+                value(input)
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .filter(|value| *value > 0.0)
+            };
+            let limits = StoreLimits {
+                max_packets: number(&max_packets_input).map(|value| value as usize),
+                max_bytes: number(&max_mib_input).map(|value| (value * MIB) as usize),
+                max_age_secs: number(&max_age_input),
+                spill_path: value(&spill_path_input),
+            };
+            let Some(current_session) = current_session else {
+                store_error.set(Some("Start or import a capture first".to_string()));
+                return Ok(());
+            };
+            let result = try_invoke(
+                "set_store_limits",
+                to_value(&limits_args::Args {
+                    session_id: current_session,
+                    limits,
+                })
+                .unwrap(),
+            )
+            .await;
+            match result {
+                Ok(_) => {
+                    store_error.set(None);
                     get_packets.run();
                 }
+                Err(e) => store_error.set(Some(
+                    ApiError::AppError(e.as_string().unwrap_or_default()).to_string(),
+                )),
+            }
+            Ok::<(), ()>(())
+        }
+    });
+
+    let get_dissectors = use_async(async move {
+        Ok::<Vec<String>, ()>(
+            from_value::<Vec<String>>(invoke("get_dissectors", JsValue::NULL).await)
+                .unwrap_or_default(),
+        )
+    });
+    {
+        let get_dissectors = get_dissectors.clone();
+        use_effect_with((), move |_| {
+            get_dissectors.run();
+            || ()
+        });
+    }
+    let dissectors = get_dissectors.data.clone().unwrap_or_default();
+
+    let decode_transport_select = use_node_ref();
+    let decode_port_input = use_node_ref();
+    let decode_protocol_select = use_node_ref();
+    let decode_as = use_async({
+        let get_packets = get_packets.clone();
+        let store_error = store_error.clone();
+        let current_session = *session_id;
+        let decode_transport_select = decode_transport_select.clone();
+        let decode_port_input = decode_port_input.clone();
+        let decode_protocol_select = decode_protocol_select.clone();
+        async move {
+            let select = |select: &NodeRef| {
+                select
+                    .cast::<HtmlSelectElement>()
+                    .map(|select| select.value())
+                    .unwrap_or_default()
+            };
+            let Some(port) = decode_port_input
+                .cast::<HtmlInputElement>()
+                .and_then(|input| input.value().trim().parse::<u16>().ok())
+            else {
+                store_error.set(Some("Enter a port from 0 to 65535".to_string()));
+                return Ok(());
+            };
+            // no protocol leaves the port to the heuristics
+            let protocol = Some(select(&decode_protocol_select)).filter(|value| !value.is_empty());
+            let result = try_invoke(
+                "map_port",
+                to_value(&map_port_args::Args {
+                    transport: select(&decode_transport_select),
+                    port,
+                    protocol,
+                })
+                .unwrap(),
+            )
+            .await;
+            if let Err(e) = result {
+                store_error.set(Some(
+                    ApiError::AppError(e.as_string().unwrap_or_default()).to_string(),
+                ));
+                return Ok(());
             }
+            store_error.set(None);
+            // packets already stored are shown the new way too
+            if let Some(current_session) = current_session {
+                invoke(
+                    "reparse_packets",
+                    to_value(&reparse_args::Args {
+                        session_id: current_session,
+                    })
+                    .unwrap(),
+                )
+                .await;
+                get_packets.run();
+            }
+            Ok::<(), ()>(())
+        }
+    });
+    let on_decode_as = {
+        let decode_as = decode_as.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            decode_as.run();
+        })
+    };
+
+    let on_apply_limits = {
+        let apply_limits = apply_limits.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            apply_limits.run();
+        })
+    };
+
+    let on_older = {
+        let page_offset = page_offset.clone();
+        let offset = packets.offset;
+        Callback::from(move |_| page_offset.set(Some(offset.saturating_sub(PAGE_SIZE))))
+    };
+
+    let on_newer = {
+        let page_offset = page_offset.clone();
+        let (offset, total) = (packets.offset, packets.total);
+        Callback::from(move |_| {
+            let next = offset + PAGE_SIZE;
+            page_offset.set((next + PAGE_SIZE < total).then_some(next));
         })
     };
 
+    let on_latest = {
+        let page_offset = page_offset.clone();
+        Callback::from(move |_| page_offset.set(None))
+    };
+
+    let usage = (*store_usage).clone();
+    let limit_value = |limit: Option<f64>| limit.map(|limit| limit.to_string()).unwrap_or_default();
+
     //imprt_export
     let import_data = use_async({
-        let get_packets = get_packets.clone();
+        let session_id = session_id.clone();
+        let page_offset = page_offset.clone();
+        let is_running = is_running.clone();
+        let get_sessions = get_sessions.clone();
         async move {
-            if let Ok(_) = from_value::<()>(invoke("import_packets", JsValue::NULL).await) {
-                get_packets.run()
+            if let Ok(imported) = from_value::<u32>(invoke("import_packets", JsValue::NULL).await) {
+                session_id.set(Some(imported));
+                page_offset.set(None);
+                is_running.set(false);
+                get_sessions.run();
             }
             Ok::<(), ApiError>(())
         }
     });
 
     let export_data = use_async({
+        let current_session = *session_id;
         async move {
-            invoke("export_packets", JsValue::NULL).await;
+            if let Some(current_session) = current_session {
+                invoke(
+                    "export_packets",
+                    to_value(&session_args::Args {
+                        session_id: current_session,
+                    })
+                    .unwrap(),
+                )
+                .await;
+            }
             Ok::<(), ApiError>(())
         }
     });
@@ -210,8 +1210,16 @@ pub fn watch() -> Html {
     let detect_with_ai = use_async({
         let detected_dos = detected_dos.clone();
         let is_show_model = is_show_model.clone();
+        let current_session = (*session_id).unwrap_or_default();
         async move {
-            let value = invoke("dos_detect", JsValue::NULL).await;
+            let value = invoke(
+                "dos_detect",
+                to_value(&session_args::Args {
+                    session_id: current_session,
+                })
+                .unwrap(),
+            )
+            .await;
             let detected: Vec<Detect> = from_value(value).unwrap();
             detected_dos.set(detected);
             is_show_model.set(true);
@@ -221,6 +1229,57 @@ pub fn watch() -> Html {
     });
     let on_detect = Callback::from(move |_| detect_with_ai.run());
 
+    let discovered_hosts = use_state(Vec::<DiscoveredHost>::new);
+    let is_show_hosts = use_state(|| false);
+    let toggle_hosts = {
+        let is_show_hosts = is_show_hosts.clone();
+        Callback::from(move |_| is_show_hosts.set(!*is_show_hosts))
+    };
+    let discover_hosts = use_async({
+        let discovered_hosts = discovered_hosts.clone();
+        let is_show_hosts = is_show_hosts.clone();
+        let capture_error = capture_error.clone();
+        let interface = (*app_context.interface).clone();
+        let current_session = *session_id;
+        async move {
+            let result = try_invoke(
+                "discover_hosts",
+                to_value(&discover_args::Args {
+                    interface,
+                    session_id: current_session,
+                })
+                .unwrap(),
+            )
+            .await;
+            match result.map(from_value::<Vec<DiscoveredHost>>) {
+                Ok(Ok(hosts)) => {
+                    discovered_hosts.set(hosts);
+                    is_show_hosts.set(true);
+                }
+                Ok(Err(e)) => capture_error.set(Some(e.to_string())),
+                Err(e) => capture_error.set(Some(
+                    ApiError::AppError(e.as_string().unwrap_or_default()).to_string(),
+                )),
+            }
+            Ok::<(), ()>(())
+        }
+    });
+    let is_discovering = discover_hosts.loading;
+    let on_discover = Callback::from(move |_| discover_hosts.run());
+    // shows only the packets a discovered host sent
+    let show_host_packets = {
+        let filter_ip = filter_ip.clone();
+        let search_input = search_input.clone();
+        let is_show_hosts = is_show_hosts.clone();
+        Callback::from(move |ip: String| {
+            if let Some(input) = search_input.cast::<HtmlInputElement>() {
+                input.set_value(&ip);
+            }
+            filter_ip.set(ip);
+            is_show_hosts.set(false);
+        })
+    };
+
     html! {
                                                  <>
                                                  <SideNavBar />
@@ -238,6 +1297,10 @@ pub fn watch() -> Html {
                                                                                      <input ref={search_input} type="text" name="email" id="products-search" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-full p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white dark:focus:ring-blue-500 dark:focus:border-blue-500" placeholder="Search by ip" />
                                                                                  </div>
                                                                              </form>
+                                                                             <form onsubmit={on_vlan} class="sm:pr-3">
+                                                                                 <label for="vlan-search" class="sr-only">{"VLAN"}</label>
+                                                                                 <input ref={vlan_input} type="number" min="0" max="4095" id="vlan-search" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-24 mt-1 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white dark:focus:ring-blue-500 dark:focus:border-blue-500" placeholder="VLAN" />
+                                                                             </form>
                                                  <form class="mx-auto px-2">
                                                    <label for="protocols" class="text-sm font-medium text-gray-900 dark:text-white">{"protocol"}</label>
                                                    <select ref={choosed_protocol} onchange={set_protocol} id="protocols" class="bg-gray-50 border border-gray-300 text-gray-900 text-sm rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-full dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white dark:focus:ring-blue-500 dark:focus:border-blue-500">
@@ -247,8 +1310,19 @@ pub fn watch() -> Html {
                                                      <option >{"ICMP"}</option>
                                                      <option >{"ICMPv6"}</option>
                                                      <option >{"ARP"}</option>
+                                                     { for dissectors.iter().map(|name| html! { <option>{name}</option> }) }
                                                    </select>
                                                  </form>
+                                                 <form class="mx-auto px-2">
+                                                   <label for="time-format" class="text-sm font-medium text-gray-900 dark:text-white">{"time"}</label>
+                                                   <select ref={choosed_time_format} onchange={set_time_format} id="time-format" class="bg-gray-50 border border-gray-300 text-gray-900 text-sm rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-full dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white dark:focus:ring-blue-500 dark:focus:border-blue-500">
+                                                     <option value="utc" selected=true>{"UTC"}</option>
+                                                     <option value="local">{"Local"}</option>
+                                                     <option value="relative">{"Since first packet"}</option>
+                                                     <option value="delta">{"Since previous packet"}</option>
+                                                   </select>
+                                                 </form>
+                                                 <SessionBar sessions={(*sessions).clone()} session_id={*session_id} on_select={select_session} on_close={on_close_session} />
                             <div class="flex pl-0 mt-3 space-x-1 sm:pl-2 sm:mt-0">
                                         <button onclick={on_click_import} class="inline-flex justify-center p-1 text-gray-500 rounded cursor-pointer hover:text-gray-900 hover:bg-gray-100 dark:text-gray-400 dark:hover:bg-gray-700 dark:hover:text-white">
                                         <svg class="w-6 h-6" aria-hidden="true" xmlns="http://www.w3.org/2000/svg" width="24" height="24" fill="none" viewBox="0 0 24 24">
@@ -268,12 +1342,30 @@ pub fn watch() -> Html {
                                                                              </div>
                                                                          </div>
                    <div class="flex items-center ml-auto space-x-2 sm:space-x-3">
+                                <div class="relative">
+                                    <input ref={capture_filter_input} disabled={*is_running} type="text" id="capture-filter" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-48 xl:w-72 p-2.5 font-mono dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white dark:focus:ring-blue-500 dark:focus:border-blue-500" placeholder="Capture filter, e.g. tcp port 443" />
+                                    { if let Some(error) = (*capture_error).clone() {
+                                        html! { <p class="absolute mt-1 text-xs text-red-600 dark:text-red-500 whitespace-nowrap">{error}</p> }
+                                    } else {
+                                        html! {}
+                                    }}
+                                </div>
+                                <CaptureOptionsForm disabled={*is_running} on_change={on_capture_options} />
+                                <button onclick={on_save_evidence} disabled={!*is_running} type="button" class="text-sm font-medium text-gray-900 bg-white border border-gray-300 rounded-lg px-3 py-2 whitespace-nowrap hover:bg-gray-100 disabled:opacity-50 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:bg-gray-700" title={(*last_trigger).as_ref().map(|trigger| trigger.path.clone()).unwrap_or_default()}>
+                                    {match (*last_trigger).as_ref() {
+                                        Some(trigger) => format!("Saved #{} ({} pkts)", trigger.id, trigger.packets),
+                                        None => "Save evidence".to_string(),
+                                    }}
+                                </button>
                                 <button onclick={on_detect} class="inline-flex items-center justify-center w-1/2 px-3 py-2 text-sm font-medium text-center text-gray-900 bg-white border border-gray-300 rounded-lg hover:bg-gray-100 focus:ring-4 focus:ring-primary-300 sm:w-auto dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:text-white dark:hover:bg-gray-700 dark:focus:ring-gray-700">
                                     <svg class="w-6 h-6 text-gray-800 dark:text-white" aria-hidden="true" xmlns="http://www.w3.org/2000/svg" width="24" height="24" fill="none" viewBox="0 0 24 24">
                               <path stroke="currentColor" stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 18.5A2.493 2.493 0 0 1 7.51 20H7.5a2.468 2.468 0 0 1-2.4-3.154 2.98 2.98 0 0 1-.85-5.274 2.468 2.468 0 0 1 .92-3.182 2.477 2.477 0 0 1 1.876-3.344 2.5 2.5 0 0 1 3.41-1.856A2.5 2.5 0 0 1 12 5.5m0 13v-13m0 13a2.493 2.493 0 0 0 4.49 1.5h.01a2.468 2.468 0 0 0 2.403-3.154 2.98 2.98 0 0 0 .847-5.274 2.468 2.468 0 0 0-.921-3.182 2.477 2.477 0 0 0-1.875-3.344A2.5 2.5 0 0 0 14.5 3 2.5 2.5 0 0 0 12 5.5m-8 5a2.5 2.5 0 0 1 3.48-2.3m-.28 8.551a3 3 0 0 1-2.953-5.185M20 10.5a2.5 2.5 0 0 0-3.481-2.3m.28 8.551a3 3 0 0 0 2.954-5.185"/>
                             </svg>
                             {" detect with ai "}
                                         </button>
+                                <button onclick={on_discover} disabled={is_discovering} type="button" class="text-sm font-medium text-gray-900 bg-white border border-gray-300 rounded-lg px-3 py-2 whitespace-nowrap hover:bg-gray-100 disabled:opacity-50 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:bg-gray-700" title="Find live hosts on the interface's subnets with ARP and ICMPv6">
+                                    {if is_discovering { "Discovering..." } else { "Discover hosts" }}
+                                </button>
 
                                                                          <button onclick={toggle_loop} id="createProductButton" class="text-white bg-blue-700 hover:bg-blue-800 focus:ring-4 focus:ring-blue-300 font-medium rounded-lg text-sm px-5 py-2.5 dark:bg-blue-600 dark:hover:bg-blue-700 focus:outline-none dark:focus:ring-blue-800" type="button" data-drawer-target="drawer-create-product-default" data-drawer-show="drawer-create-product-default" aria-controls="drawer-create-product-default" data-drawer-placement="right">
                                                                      {format!("{} watch", if *is_running { "Stop" } else { "Start" })}
@@ -313,13 +1405,15 @@ pub fn watch() -> Html {
                                                                                  </tr>
                                                                              </thead>
                                                                              <tbody class="bg-white divide-y divide-gray-200 dark:bg-gray-800 dark:divide-gray-700">
-                                                                     {for (*packets).iter().map(|packet| html!{
-                                                                             <tr onclick={
+                                                                     {for packets.packets.iter().map(|packet| html!{
+                                                                             <tr key={packet.number} onclick={
                                                                      Callback::from({
-                                                                     let current_packet = current_packet.clone();
+                                                                     let current_row = current_row.clone();
+                                                                     let selected_field = selected_field.clone();
                                                                      let packet = packet.clone();
                                                                      move |_| {
-                                                                         current_packet.set(Some(packet.clone()))
+                                                                         selected_field.set(None);
+                                                                         current_row.set(Some(packet.clone()))
                                                                      }})
 
                                                                      } class="cursor-pointer hover:bg-gray-100 dark:hover:bg-gray-700">
@@ -327,10 +1421,10 @@ pub fn watch() -> Html {
                                                                                      {packet.number}
                                                                                  </td>
                                                                                  <td class="w-1 px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">
-                                                                                     {&packet.time}
+                                                                                     {time_format.format(packet)}
                                                                                  </td>
-                                                                                 <td class="w-1 px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">{packet.src_ip.to_string()}</td>
-                                                                                 <td class="w-1 px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">{packet.dst_ip.to_string()}</td>
+                                                                                 <td class="w-1 px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">{packet.source()}</td>
+                                                                                 <td class="w-1 px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">{packet.destination()}</td>
                                                                                  <td class="w-1 px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">{&packet.protocol}</td>
                                                                                  <td class="w-1 px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">{packet.lenght}</td>
                                                                                  <td class="px-2 py-1 text-base font-medium text-gray-800 whitespace-nowrap dark:text-gray-300">{&packet.info}</td>
@@ -344,98 +1438,94 @@ pub fn watch() -> Html {
                                                              </div>
                                                          </div>
                                                          <div class="sticky bottom-0 right-0 items-center w-full p-4 bg-gray-100 border-t border-gray-200 sm:flex sm:justify-between dark:bg-gray-800 dark:border-gray-700">
-                                                      <div class="flex items-center mb-4 sm:mb-0">
+                                                      <div class="flex flex-col mb-4 sm:mb-0 text-sm">
+                                                        <span class="text-gray-700 dark:text-gray-300">
+                                                            {format!("{} packets, {:.1} MiB in memory", usage.packets, usage.bytes as f64 / MIB)}
+                                                            { if usage.evicted > 0 {
+                                                                format!(", {} oldest evicted ({} spilled to disk)", usage.evicted, usage.spilled)
+                                                            } else {
+                                                                String::new()
+                                                            }}
+                                                        </span>
+                                                        { if session_id.is_some() {
+                                                            let stats = &*capture_stats;
+                                                            let dropped = stats.kernel_drops.unwrap_or_default();
+                                                            html! {
+                                                                <span class={if dropped > 0 { "text-red-600 dark:text-red-500" } else { "text-gray-500 dark:text-gray-400" }} title={format!("Partly dissected: {} unsupported link type, {} unhandled ethertype, {} unhandled IP protocol, {} malformed; {} read errors", stats.skipped.unsupported_link_type, stats.skipped.unhandled_ether_type, stats.skipped.unhandled_ip_protocol, stats.skipped.malformed, stats.read_errors)}>
+                                                                    {format!("{} frames received, {} dissected, {} partly", stats.frames_received, stats.frames_dissected, stats.skipped.total())}
+                                                                    {match stats.kernel_drops {
+                                                                        Some(drops) => format!(", {} dropped by the kernel", drops),
+                                                                        None => String::new(),
+                                                                    }}
+                                                                    { if *is_running { format!(" · {:.0} pkts/s", stats.packets_per_sec) } else { String::new() }}
+                                                                    { if stats.vlans.is_empty() {
+                                                                        String::new()
+                                                                    } else {
+                                                                        format!(" · {}", stats.vlans.iter().map(|vlan| format!("VLAN {}: {} pkts, {:.1} MiB", vlan.id, vlan.packets, vlan.bytes as f64 / MIB)).collect::<Vec<_>>().join(", "))
+                                                                    }}
+                                                                </span>
+                                                            }
+                                                        } else {
+                                                            html! {}
+                                                        }}
+                                                        { if usage.near_limit {
+                                                            html! { <span class="text-yellow-600 dark:text-yellow-400">{format!("Packet store is {:.0}% full, the oldest packets {}", usage.fill * 100.0, if usage.limits.spill_path.is_some() { "are spilled to disk" } else { "will be dropped" })}</span> }
+                                                        } else {
+                                                            html! {}
+                                                        }}
+                                                        { if let Some(error) = usage.spill_error.clone().or_else(|| (*store_error).clone()) {
+                                                            html! { <span class="text-red-600 dark:text-red-500">{error}</span> }
+                                                        } else {
+                                                            html! {}
+                                                        }}
+                                                     </div>
+                                                     <div class="flex items-center space-x-2 text-sm text-gray-700 dark:text-gray-300">
+                                                        <span>
+                                                            { if packets.packets.is_empty() {
+                                                                format!("0 of {}", packets.total)
+                                                            } else {
+                                                                format!("{}-{} of {}", packets.offset + 1, packets.offset + packets.packets.len(), packets.total)
+                                                            }}
+                                                        </span>
+                                                        <button onclick={on_older} disabled={packets.offset == 0} class="px-3 py-1.5 font-medium text-gray-900 bg-white border border-gray-300 rounded-lg hover:bg-gray-100 disabled:opacity-50 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:text-white dark:hover:bg-gray-700">{"Older"}</button>
+                                                        <button onclick={on_newer} disabled={page_offset.is_none()} class="px-3 py-1.5 font-medium text-gray-900 bg-white border border-gray-300 rounded-lg hover:bg-gray-100 disabled:opacity-50 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:text-white dark:hover:bg-gray-700">{"Newer"}</button>
+                                                        <button onclick={on_latest} disabled={page_offset.is_none()} class="px-3 py-1.5 font-medium text-gray-900 bg-white border border-gray-300 rounded-lg hover:bg-gray-100 disabled:opacity-50 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:text-white dark:hover:bg-gray-700">{"Latest"}</button>
                                                      </div>
+                                                     <form onsubmit={on_apply_limits} class="flex items-center space-x-2 text-sm">
+                                                        <input ref={max_packets_input} type="number" min="0" value={limit_value(usage.limits.max_packets.map(|limit| limit as f64))} class="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg block w-28 p-1.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Max packets" />
+                                                        <input ref={max_mib_input} type="number" min="0" value={limit_value(usage.limits.max_bytes.map(|limit| limit as f64 / MIB))} class="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg block w-24 p-1.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Max MiB" />
+                                                        <input ref={max_age_input} type="number" min="0" value={limit_value(usage.limits.max_age_secs)} class="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg block w-24 p-1.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Max age, s" />
+                                                        <input ref={spill_path_input} type="text" value={usage.limits.spill_path.clone().unwrap_or_default()} class="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg block w-48 p-1.5 font-mono dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Spill to .pcapng file" />
+                                                        <button type="submit" class="px-3 py-1.5 font-medium text-gray-900 bg-white border border-gray-300 rounded-lg hover:bg-gray-100 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:text-white dark:hover:bg-gray-700">{"Apply limits"}</button>
+                                                     </form>
+                                                     <form onsubmit={on_decode_as} class="flex items-center space-x-2 text-sm">
+                                                        <select ref={decode_transport_select} class="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg block p-1.5 dark:bg-gray-700 dark:border-gray-600 dark:text-white">
+                                                            <option value="tcp" selected=true>{"TCP"}</option>
+                                                            <option value="udp">{"UDP"}</option>
+                                                        </select>
+                                                        <input ref={decode_port_input} type="number" min="0" max="65535" class="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg block w-24 p-1.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Port" />
+                                                        <select ref={decode_protocol_select} class="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg block p-1.5 dark:bg-gray-700 dark:border-gray-600 dark:text-white">
+                                                            <option value="" selected=true>{"by heuristics"}</option>
+                                                            { for dissectors.iter().map(|name| html! { <option value={name.clone()}>{name}</option> }) }
+                                                        </select>
+                                                        <button type="submit" class="px-3 py-1.5 font-medium text-gray-900 bg-white border border-gray-300 rounded-lg hover:bg-gray-100 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:text-white dark:hover:bg-gray-700" title="Dissect traffic on this port as the chosen protocol">{"Decode as"}</button>
+                                                     </form>
                                                      </div>
                                     <div class="grid grid-cols-1 md:grid-cols-12 gap-2 p-3 h-[450px]">
-                                    <div class="w-full md:col-span-6 p-2 border-4 dark:border-gray-700 bg-white dark:bg-gray-800 overflow-auto max-h-[450px]">
-                                        <h3 class="text-gray-700 text-lg sm:text-xl font-normal dark:text-gray-300 text-center mb-4">
-                                            {"Detailed info"}
-                                        </h3>
-
-                                        { if let Some(pkg) = (*current_packet).clone() {
-                                            if let Some(detailed_info) = pkg.detailed_info {
-                                                html! {
-                                                    <table class="w-full text-left border-collapse dark:bg-gray-800  text-white text-sm sm:text-base">
-                                                        <tbody>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Frame number"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{}", pkg.number)}</td>
-                                                            </tr>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Interface name"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{}", detailed_info.interface)}</td>
-                                                            </tr>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Arrival time"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{}", pkg.time)}</td>
-                                                            </tr>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Source Mac Address"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{}", detailed_info.src_mac)}</td>
-                                                            </tr>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Destination Mac Address"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{}", detailed_info.dst_mac)}</td>
-                                                            </tr>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Frame protocol name"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{}", detailed_info.frame_type)}</td>
-                                                            </tr>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Payload length"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{} bytes, ({}) bits", detailed_info.payload_length, detailed_info.payload_length * 8)}</td>
-                                                            </tr>
-                                                            <tr class="border-b border-gray-200 dark:border-gray-700">
-                                                                <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{"Packet length"}</td>
-                                                                <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{format!("{} bytes, ({}) bits", detailed_info.packet_length, detailed_info.packet_length * 8)}</td>
-                                                            </tr>
-                                                        </tbody>
-                                                    </table>
-                                                }
-                                            } else {
-                                                html! {
-                                                    <a class="text-sm sm:text-base font-medium text-gray-400 text-center block py-4">{"No additional info"}</a>
-                                                }
-                                            }
-                                        } else {
-                                            html! {
-                                                <a class="text-sm sm:text-base font-medium text-gray-400 text-center block py-4">{"Click on table element to show more info about"}</a>
-                                            }
-                                        }}
-                                    </div>
-                                <div class="w-full md:col-span-6 p-2 border-4 dark:border-gray-700 bg-white dark:bg-gray-800 overflow-auto max-h-[450px]">
-                                        { if let Some(current_packet) = (*current_packet).clone() {
-                                            if let Some(detailed_info) = current_packet.detailed_info {
-                                        html!{
-                                        {(*detailed_info.payload_data).lines().map(|line| {
-                                            if line.trim().is_empty() {
-                                                return html! { <div></div> };
-                                            }
-
-                                            let offset = &line[0..4];
-                                            let hex = &line[4..55].trim_end();
-                                            let ascii = &line[55..].trim_end();
-
-                                            html! {
-                                                <div class="flex items-center text-sm font-mono">
-                                                    <span class="w-12 text-blue-700">{offset}</span>
-                                                    <span class="w-[400px] text-gray-900">{hex}</span>
-                                                    <span class="text-green-700">{ascii}</span>
-                                                </div>
-                                            }
-                                        }).collect::<Html>()}
-                                        }
-                                        }else{
-                                        html!{}
-
-                                    }
-                                }else{
-                                html!{}
-
-                                }
-                                        }
-                                    </div>
+                                    <PacketDetails
+                                        row={(*current_row).clone()}
+                                        packet={(*current_packet).clone()}
+                                        selected={(*selected_field).clone()}
+                                        on_select={on_select_field.clone()}
+                                        can_resend={session_id.is_some()}
+                                        resent={(*current_row).as_ref().is_some_and(|row| *resent == Some(row.number))}
+                                        {on_resend}
+                                    />
+                                    <HexView
+                                        packet={(*current_packet).clone()}
+                                        selected={(*selected_field).clone()}
+                                        on_select={on_select_field}
+                                    />
                                 </div>
                   <div id="popup-modal" tabindex="-1" class={format!("{}  fixed flex bg-black bg-opacity-30 shadow left-0 right-0 z-50 items-center justify-center overflow-x-hidden  overflow-y-auto inset-0 h-full", if *is_show_model{ ""} else{"hidden"})}>
                             <div class="relative p-4 w-full max-w-4xl max-h-full">
@@ -505,6 +1595,11 @@ pub fn watch() -> Html {
             </tbody>
         </table>
     </div>
+    { if let Some(evidence) = (*detected_dos).iter().find_map(|value| value.evidence.clone()) {
+        html! { <p class="mt-4 text-sm text-gray-500 dark:text-gray-400">{format!("Traffic around the alert is saved to {}", evidence)}</p> }
+    } else {
+        html! {}
+    }}
 
     <div class="pt-10">
 
@@ -518,6 +1613,60 @@ pub fn watch() -> Html {
                             </div>
                         </div>
 
+                  <div id="hosts-modal" tabindex="-1" class={format!("{}  fixed flex bg-black bg-opacity-30 shadow left-0 right-0 z-50 items-center justify-center overflow-x-hidden  overflow-y-auto inset-0 h-full", if *is_show_hosts { "" } else { "hidden" })}>
+                            <div class="relative p-4 w-full max-w-4xl max-h-full">
+                                <div class="relative bg-white rounded-lg shadow-sm dark:bg-gray-700">
+                                    <button onclick={toggle_hosts.clone()} type="button" class="absolute top-3 end-2.5 text-gray-400 bg-transparent hover:bg-gray-200 hover:text-gray-900 rounded-lg text-sm w-8 h-8 ms-auto inline-flex justify-center items-center dark:hover:bg-gray-600 dark:hover:text-white">
+                                        <svg class="w-3 h-3" aria-hidden="true" xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 14 14">
+                                            <path stroke="currentColor" stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="m1 1 6 6m0 0 6 6M7 7l6-6M7 7l-6 6"/>
+                                        </svg>
+                                        <span class="sr-only">{"Close modal"}</span>
+                                    </button>
+                                    <div class="p-4 md:p-5 text-center">
+            <h3 class="text-xl font-semibold text-gray-900 dark:text-white mb-4">
+            {format!("{} hosts on {}", discovered_hosts.len(), *app_context.interface)}
+          </h3>
+            <div class="relative overflow-x-auto max-h-96 shadow-md sm:rounded-lg">
+        <table class="w-full text-sm text-left rtl:text-right text-gray-500 dark:text-gray-400">
+            <thead class="text-xs text-gray-700 uppercase dark:text-gray-400">
+                <tr>
+                    <th scope="col" class="px-6 py-3 bg-gray-50 dark:bg-gray-800">{"IP"}</th>
+                    <th scope="col" class="px-6 py-3">{"MAC"}</th>
+                    <th scope="col" class="px-6 py-3 bg-gray-50 dark:bg-gray-800">{"Found by"}</th>
+                    <th scope="col" class="px-6 py-3">{"Response"}</th>
+                    <th scope="col" class="px-6 py-3 bg-gray-50 dark:bg-gray-800">{"Packets"}</th>
+                </tr>
+            </thead>
+            <tbody>
+                { for (*discovered_hosts).iter().map(|host| {
+                    let ip = host.ip.clone();
+                    let show_host_packets = show_host_packets.clone();
+                    html! {
+                        <tr onclick={move |_| show_host_packets.emit(ip.clone())} class="border-b border-gray-200 cursor-pointer hover:bg-gray-100 dark:border-gray-700 dark:hover:bg-gray-600" title="Show the packets this host sent">
+                            <th scope="row" class="px-6 py-4 font-medium text-gray-900 whitespace-nowrap bg-gray-50 dark:text-white dark:bg-gray-800">{&host.ip}</th>
+                            <td class="px-6 py-4 font-mono">{&host.mac}</td>
+                            <td class="px-6 py-4 bg-gray-50 dark:bg-gray-800">{host.probe.replace('_', " ")}</td>
+                            <td class="px-6 py-4">{format!("{:.1} ms", host.response_ms)}</td>
+                            <td class="px-6 py-4 bg-gray-50 dark:bg-gray-800">
+                                {match (host.first_packet, host.last_packet) {
+                                    (Some(first), Some(last)) => format!("{} (#{}-#{})", host.packets, first, last),
+                                    _ => host.packets.to_string(),
+                                }}
+                            </td>
+                        </tr>
+                    }
+                })}
+            </tbody>
+        </table>
+    </div>
+    <div class="pt-6">
+                                        <button onclick={toggle_hosts} type="button" class="py-2.5 px-5 text-sm font-medium text-gray-900 focus:outline-none bg-white rounded-lg border border-gray-200 hover:bg-gray-100 hover:text-blue-700 focus:z-10 focus:ring-4 focus:ring-gray-100 dark:focus:ring-gray-700 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:text-white dark:hover:bg-gray-700">{"Close"}</button>
+    </div>
+                                    </div>
+                                </div>
+                            </div>
+                        </div>
+
                                                  </>
                                                                  }
 }
@@ -0,0 +1,112 @@
+use crate::ui::app::{RingBufferOptions, TriggerOptions, MIB};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+/// Seconds kept before and after a trigger when the fields are left empty.
+const DEFAULT_TRIGGER_SECS: f64 = 30.0;
+
+/// What the stop, ring buffer and trigger fields hold, for the next capture to start with.
+#[derive(Clone, PartialEq, Default)]
+pub struct CaptureOptions {
+    pub max_packets: Option<u64>,
+    pub max_duration_secs: Option<f64>,
+    /// Stop at the first packet matching the display filter.
+    pub until_match: bool,
+    pub ring: Option<RingBufferOptions>,
+    pub trigger: Option<TriggerOptions>,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CaptureOptionsFormProps {
+    /// Set while capturing, as the options only apply when a capture starts.
+    pub disabled: bool,
+    pub on_change: Callback<CaptureOptions>,
+}
+
+/// The fields that decide when a capture stops by itself and what it writes to disk.
+#[function_component(CaptureOptionsForm)]
+pub fn capture_options_form(props: &CaptureOptionsFormProps) -> Html {
+    let stop_packets_input = use_node_ref();
+    let stop_secs_input = use_node_ref();
+    let stop_on_match_input = use_node_ref();
+    let ring_dir_input = use_node_ref();
+    let ring_mib_input = use_node_ref();
+    let ring_secs_input = use_node_ref();
+    let ring_files_input = use_node_ref();
+    let evidence_dir_input = use_node_ref();
+    let pre_secs_input = use_node_ref();
+    let post_secs_input = use_node_ref();
+    let pre_mib_input = use_node_ref();
+
+    let on_input = {
+        let stop_packets_input = stop_packets_input.clone();
+        let stop_secs_input = stop_secs_input.clone();
+        let stop_on_match_input = stop_on_match_input.clone();
+        let ring_dir_input = ring_dir_input.clone();
+        let ring_mib_input = ring_mib_input.clone();
+        let ring_secs_input = ring_secs_input.clone();
+        let ring_files_input = ring_files_input.clone();
+        let evidence_dir_input = evidence_dir_input.clone();
+        let pre_secs_input = pre_secs_input.clone();
+        let post_secs_input = post_secs_input.clone();
+        let pre_mib_input = pre_mib_input.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |_: Event| {
+            // an empty or zero field means no condition
+            let number = |input: &NodeRef| {
+                input
+                    .cast::<HtmlInputElement>()
+                    .and_then(|input| input.value().trim().parse::<f64>().ok())
+                    .filter(|value| *value > 0.0)
+            };
+            let directory = |input: &NodeRef| {
+                input
+                    .cast::<HtmlInputElement>()
+                    .map(|input| input.value().trim().to_string())
+                    .filter(|directory| !directory.is_empty())
+            };
+            on_change.emit(CaptureOptions {
+                max_packets: number(&stop_packets_input).map(|value| value as u64),
+                max_duration_secs: number(&stop_secs_input),
+                until_match: stop_on_match_input
+                    .cast::<HtmlInputElement>()
+                    .is_some_and(|input| input.checked()),
+                // files are only written when a directory is given
+                ring: directory(&ring_dir_input).map(|directory| RingBufferOptions {
+                    directory,
+                    max_file_bytes: number(&ring_mib_input).map(|value| (value * MIB) as u64),
+                    max_file_secs: number(&ring_secs_input),
+                    max_files: number(&ring_files_input).map(|value| value as usize),
+                }),
+                // pre-trigger mode is on when an evidence directory is given
+                trigger: directory(&evidence_dir_input).map(|directory| TriggerOptions {
+                    directory,
+                    pre_secs: number(&pre_secs_input).unwrap_or(DEFAULT_TRIGGER_SECS),
+                    post_secs: number(&post_secs_input).unwrap_or(DEFAULT_TRIGGER_SECS),
+                    max_bytes: number(&pre_mib_input).map(|value| (value * MIB) as usize),
+                    on_match: None,
+                }),
+            });
+        })
+    };
+    let disabled = props.disabled;
+
+    html! {
+        <>
+        <input ref={stop_packets_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-28 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Stop after pkts" />
+        <input ref={stop_secs_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-28 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Stop after s" />
+        <input ref={ring_dir_input} onchange={on_input.clone()} {disabled} type="text" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-40 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Save to directory" title="Write the capture to rotating pcapng files in this directory" />
+        <input ref={ring_mib_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-24 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="File MiB" />
+        <input ref={ring_secs_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-24 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="File s" />
+        <input ref={ring_files_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-20 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Files" />
+        <input ref={evidence_dir_input} onchange={on_input.clone()} {disabled} type="text" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-40 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Evidence directory" title="Keep recent traffic in memory and save it around alerts to this directory" />
+        <input ref={pre_secs_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-20 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Pre s" />
+        <input ref={post_secs_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-20 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Post s" />
+        <input ref={pre_mib_input} onchange={on_input.clone()} {disabled} type="number" min="0" class="bg-gray-50 border border-gray-300 text-gray-900 sm:text-sm rounded-lg block w-24 p-2.5 dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white" placeholder="Pre MiB" title="Memory the recent traffic may use" />
+        <label class="flex items-center text-sm text-gray-500 whitespace-nowrap dark:text-gray-400" title="Stop at the first packet matching the protocol and IP filter">
+            <input ref={stop_on_match_input} onchange={on_input} {disabled} type="checkbox" class="mr-1" />
+            {"Until match"}
+        </label>
+        </>
+    }
+}
@@ -0,0 +1,127 @@
+use crate::ui::app::{FormatedPacket, ProtocolNode};
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct HexViewProps {
+    pub packet: Option<FormatedPacket>,
+    /// The field picked in the tree or the hex view.
+    pub selected: Option<ProtocolNode>,
+    pub on_select: Callback<ProtocolNode>,
+}
+
+impl ProtocolNode {
+    fn covers(&self, offset: usize) -> bool {
+        (self.offset..self.offset + self.length).contains(&offset)
+    }
+}
+
+/// The narrowest field that covers the byte at `offset`, the first one if several do.
+fn field_at(tree: &[ProtocolNode], offset: usize) -> Option<&ProtocolNode> {
+    fn visit<'a>(nodes: &'a [ProtocolNode], offset: usize, best: &mut Option<&'a ProtocolNode>) {
+        for node in nodes.iter().filter(|node| node.covers(offset)) {
+            if best.is_none_or(|best| node.length < best.length) {
+                *best = Some(node);
+            }
+            visit(&node.children, offset, best);
+        }
+    }
+    let mut best = None;
+    visit(tree, offset, &mut best);
+    best
+}
+
+/// The whole frame, 16 bytes a line. The bytes of the selected field are highlighted, and
+/// clicking a byte selects the field it belongs to.
+fn render_bytes(
+    data: &[u8],
+    tree: &[ProtocolNode],
+    selected: Option<&ProtocolNode>,
+    on_select: &Callback<ProtocolNode>,
+) -> Html {
+    let highlighted = |offset: usize| selected.is_some_and(|node| node.covers(offset));
+    let byte = |offset: usize, text: String| {
+        let class = if highlighted(offset) {
+            "cursor-pointer bg-blue-200 dark:bg-blue-800"
+        } else {
+            "cursor-pointer hover:bg-gray-200 dark:hover:bg-gray-600"
+        };
+        let onclick = field_at(tree, offset).cloned().map(|node| {
+            let on_select = on_select.clone();
+            Callback::from(move |_: MouseEvent| on_select.emit(node.clone()))
+        });
+        html! { <span {class} {onclick}>{text}</span> }
+    };
+    data.chunks(16)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let start = line * 16;
+            html! {
+                <div class="flex items-center text-sm font-mono whitespace-pre">
+                    <span class="w-12 text-blue-700">{format!("{:04x}", start)}</span>
+                    <span class="w-[400px] text-gray-900 dark:text-gray-200">
+                        { for bytes.iter().enumerate().map(|(i, value)| {
+                            let gap = if i == 7 { "  " } else { " " };
+                            html! { <>{byte(start + i, format!("{:02x}", value))}{gap}</> }
+                        }) }
+                    </span>
+                    <span class="text-green-700">
+                        { for bytes.iter().enumerate().map(|(i, value)| {
+                            let shown = if value.is_ascii_graphic() || *value == b' ' { *value as char } else { '.' };
+                            byte(start + i, shown.to_string())
+                        }) }
+                    </span>
+                </div>
+            }
+        })
+        .collect()
+}
+
+/// The payload dump of exports without frame bytes, which can't be tied to the tree.
+fn render_payload_dump(payload_data: &str) -> Html {
+    payload_data
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                return html! { <div></div> };
+            }
+
+            let offset = &line[0..4];
+            let hex = &line[4..55].trim_end();
+            let ascii = &line[55..].trim_end();
+
+            html! {
+                <div class="flex items-center text-sm font-mono">
+                    <span class="w-12 text-blue-700">{offset}</span>
+                    <span class="w-[400px] text-gray-900">{hex}</span>
+                    <span class="text-green-700">{ascii}</span>
+                </div>
+            }
+        })
+        .collect()
+}
+
+/// The selected packet's bytes, in step with the protocol tree.
+#[function_component(HexView)]
+pub fn hex_view(props: &HexViewProps) -> Html {
+    let content = match &props.packet {
+        Some(packet) => {
+            let data = packet.frame.bytes();
+            match &packet.detailed_info {
+                Some(detailed_info) if !data.is_empty() => render_bytes(
+                    &data,
+                    &detailed_info.tree,
+                    props.selected.as_ref(),
+                    &props.on_select,
+                ),
+                Some(detailed_info) => render_payload_dump(&detailed_info.payload_data),
+                None => html! {},
+            }
+        }
+        None => html! {},
+    };
+    html! {
+        <div class="w-full md:col-span-6 p-2 border-4 dark:border-gray-700 bg-white dark:bg-gray-800 overflow-auto max-h-[450px]">
+            {content}
+        </div>
+    }
+}
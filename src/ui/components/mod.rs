@@ -1,15 +1,62 @@
+use serde::Deserialize;
 use theme::{use_theme_context, ThemeState};
 use yew::prelude::*;
 
+pub mod capture_options;
+pub mod hex_view;
+pub mod packet_details;
+pub mod session_bar;
 pub mod sidenavbar;
 pub mod theme;
 
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct InterfaceAddress {
+    pub ip: String,
+    pub prefix: u8,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct InterfaceFlags {
+    pub up: bool,
+    pub running: bool,
+    pub promiscuous: bool,
+    pub loopback: bool,
+    pub point_to_point: bool,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct InterfaceRates {
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub mac: Option<String>,
+    pub addresses: Vec<InterfaceAddress>,
+    pub mtu: Option<u32>,
+    pub flags: InterfaceFlags,
+    pub link_type_name: String,
+    pub counters: Option<InterfaceCounters>,
+    /// Traffic since the previous poll.
+    pub rates: Option<InterfaceRates>,
+}
+
 #[derive(Clone, PartialEq)]
 pub struct AppContext {
     pub theme: UseReducerHandle<ThemeState>,
     pub theme_cycle: Vec<&'static str>,
 
-    pub interfaces: UseStateHandle<Vec<String>>,
+    pub interfaces: UseStateHandle<Vec<InterfaceInfo>>,
     pub interface: UseStateHandle<String>,
 }
 
@@ -23,7 +70,7 @@ pub fn AppProvider(props: &AuthProviderProps) -> Html {
     let theme: UseReducerHandle<ThemeState> = use_theme_context();
     let theme_cycle: Vec<&str> = vec!["light", "dark"];
 
-    let interfaces = use_state(|| vec!["any".to_string()]);
+    let interfaces = use_state(Vec::new);
     let interface = use_state(|| String::from("any"));
 
     html! {
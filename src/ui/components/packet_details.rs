@@ -0,0 +1,121 @@
+use crate::ui::app::{FormatedPacket, PacketRow, ProtocolNode, TimeFormat};
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct PacketDetailsProps {
+    /// The selected line of the table.
+    pub row: Option<PacketRow>,
+    /// The whole of that packet, once it's fetched.
+    pub packet: Option<FormatedPacket>,
+    /// The field picked in the tree or the hex view.
+    pub selected: Option<ProtocolNode>,
+    pub on_select: Callback<ProtocolNode>,
+    /// Resending needs a session whose interface the frame goes out on.
+    pub can_resend: bool,
+    /// Set once the shown packet was sent again.
+    pub resent: bool,
+    pub on_resend: Callback<MouseEvent>,
+}
+
+impl ProtocolNode {
+    fn contains(&self, other: &ProtocolNode) -> bool {
+        self == other || self.children.iter().any(|child| child.contains(other))
+    }
+}
+
+/// One line of the protocol tree; nodes with fields under them expand. Clicking a line
+/// selects it, and the layers around the selected field are kept open.
+fn render_node(
+    node: &ProtocolNode,
+    selected: Option<&ProtocolNode>,
+    on_select: &Callback<ProtocolNode>,
+) -> Html {
+    let title = format!("{} ({} bytes at {})", node.name, node.length, node.offset);
+    let class = if selected == Some(node) {
+        "py-0.5 cursor-pointer bg-blue-200 dark:bg-blue-800 text-gray-800 dark:text-gray-200"
+    } else {
+        "py-0.5 cursor-pointer hover:bg-gray-100 dark:hover:bg-gray-700 text-gray-800 dark:text-gray-200"
+    };
+    let onclick = {
+        let on_select = on_select.clone();
+        let node = node.clone();
+        Callback::from(move |_: MouseEvent| on_select.emit(node.clone()))
+    };
+    if node.children.is_empty() {
+        return html! {
+            <div class={format!("pl-4 {}", class)} {title} {onclick}>{&node.display}</div>
+        };
+    }
+    let open = selected.is_some_and(|selected| node.contains(selected));
+    html! {
+        <details class="pl-1" {open}>
+            <summary {class} {title} {onclick}>{&node.display}</summary>
+            <div class="pl-3">
+                { for node.children.iter().map(|child| render_node(child, selected, on_select)) }
+            </div>
+        </details>
+    }
+}
+
+fn row(label: &str, value: String) -> Html {
+    html! {
+        <tr class="border-b border-gray-200 dark:border-gray-700">
+            <td class="py-2 px-2 sm:px-4 font-medium text-gray-800 dark:text-gray-200">{label}</td>
+            <td class="py-2 px-2 sm:px-4 text-gray-800 dark:text-gray-200">{value}</td>
+        </tr>
+    }
+}
+
+/// The selected packet's summary and its protocol tree.
+#[function_component(PacketDetails)]
+pub fn packet_details(props: &PacketDetailsProps) -> Html {
+    let content = match (&props.row, &props.packet) {
+        (Some(pkg), Some(packet)) => match &packet.detailed_info {
+            Some(detailed_info) => html! {
+                <>
+                <table class="w-full text-left border-collapse dark:bg-gray-800  text-white text-sm sm:text-base">
+                    <tbody>
+                        {row("Frame number", pkg.number.to_string())}
+                        {row("Interface name", detailed_info.interface.clone())}
+                        {row("Arrival time", TimeFormat::Utc.format(pkg))}
+                        {row("Local time", TimeFormat::Local.format(pkg))}
+                        {row("Since first packet", format!("{:.9} s", pkg.relative))}
+                        {row("Since previous packet", format!("{:.9} s", pkg.delta))}
+                        {row("Source Mac Address", detailed_info.src_mac.clone())}
+                        {row("Destination Mac Address", detailed_info.dst_mac.clone())}
+                        {row("Frame protocol name", detailed_info.frame_type.clone())}
+                        { if pkg.vlans.is_empty() {
+                            html! {}
+                        } else {
+                            row("VLAN", pkg.vlans.iter().map(|tag| format!("{} (priority {}{})", tag.id, tag.pcp, if tag.dei { ", drop eligible" } else { "" })).collect::<Vec<_>>().join(" / "))
+                        }}
+                        {row("Payload length", format!("{} bytes, ({}) bits", detailed_info.payload_length, detailed_info.payload_length * 8))}
+                        {row("Packet length", format!("{} bytes, ({}) bits", detailed_info.packet_length, detailed_info.packet_length * 8))}
+                    </tbody>
+                </table>
+                <div class="mt-3 text-sm font-mono">
+                    { for detailed_info.tree.iter().map(|node| render_node(node, props.selected.as_ref(), &props.on_select)) }
+                </div>
+                <button onclick={props.on_resend.clone()} disabled={!props.can_resend} type="button" class="mt-3 text-sm font-medium text-gray-900 bg-white border border-gray-300 rounded-lg px-3 py-2 hover:bg-gray-100 disabled:opacity-50 dark:bg-gray-800 dark:text-gray-400 dark:border-gray-600 dark:hover:bg-gray-700" title="Send this frame again on the interface it was captured on">
+                    {if props.resent { "Sent again" } else { "Resend" }}
+                </button>
+                </>
+            },
+            None => html! {
+                <a class="text-sm sm:text-base font-medium text-gray-400 text-center block py-4">{"No additional info"}</a>
+            },
+        },
+        (Some(_), None) => html! {},
+        (None, _) => html! {
+            <a class="text-sm sm:text-base font-medium text-gray-400 text-center block py-4">{"Click on table element to show more info about"}</a>
+        },
+    };
+    html! {
+        <div class="w-full md:col-span-6 p-2 border-4 dark:border-gray-700 bg-white dark:bg-gray-800 overflow-auto max-h-[450px]">
+            <h3 class="text-gray-700 text-lg sm:text-xl font-normal dark:text-gray-300 text-center mb-4">
+                {"Detailed info"}
+            </h3>
+            {content}
+        </div>
+    }
+}
@@ -0,0 +1,64 @@
+use crate::ui::app::SessionInfo;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct SessionBarProps {
+    pub sessions: Vec<SessionInfo>,
+    /// The session shown in the table.
+    pub session_id: Option<u32>,
+    /// Called with the picked session, `None` for none.
+    pub on_select: Callback<Option<u32>>,
+    pub on_close: Callback<MouseEvent>,
+}
+
+/// The option text: ID, interface, capture filter and whether it's still running.
+fn session_label(session: &SessionInfo) -> String {
+    format!(
+        "#{} {}{}{}",
+        session.id,
+        session.interface,
+        session
+            .filter
+            .as_ref()
+            .map(|filter| format!(" ({})", filter))
+            .unwrap_or_default(),
+        match (session.running, &session.stop_reason) {
+            (true, _) => " - running".to_string(),
+            (false, Some(reason)) => format!(" - stopped: {}", reason.replace('_', " ")),
+            (false, None) => String::new(),
+        }
+    )
+}
+
+/// Picks which of the captures, running or not, the table shows, and closes it.
+#[function_component(SessionBar)]
+pub fn session_bar(props: &SessionBarProps) -> Html {
+    let choosed_session = use_node_ref();
+    let select_session = {
+        let choosed_session = choosed_session.clone();
+        let on_select = props.on_select.clone();
+        Callback::from(move |_| {
+            if let Some(select) = choosed_session.cast::<HtmlSelectElement>() {
+                on_select.emit(select.value().parse::<u32>().ok());
+            }
+        })
+    };
+
+    html! {
+        <form class="mx-auto px-2">
+          <label for="sessions" class="text-sm font-medium text-gray-900 dark:text-white">{"session"}</label>
+          <div class="flex items-center space-x-1">
+          <select ref={choosed_session} onchange={select_session} id="sessions" class="bg-gray-50 border border-gray-300 text-gray-900 text-sm rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-full dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white dark:focus:ring-blue-500 dark:focus:border-blue-500">
+            <option value="" selected={props.session_id.is_none()}>{"none"}</option>
+            { for props.sessions.iter().map(|session| html! {
+                <option value={session.id.to_string()} selected={props.session_id == Some(session.id)}>
+                    {session_label(session)}
+                </option>
+            })}
+          </select>
+          <button type="button" onclick={props.on_close.clone()} disabled={props.session_id.is_none()} class="p-1 text-gray-500 rounded cursor-pointer hover:text-gray-900 hover:bg-gray-100 disabled:opacity-50 dark:text-gray-400 dark:hover:bg-gray-700 dark:hover:text-white">{"Close"}</button>
+          </div>
+        </form>
+    }
+}
@@ -1,9 +1,9 @@
 use super::theme::{Dark, Light, ThemeAction};
-use super::AppContext;
+use super::{AppContext, InterfaceInfo};
 use wasm_bindgen::prelude::*;
 use web_sys::HtmlSelectElement;
 use yew::prelude::*;
-use yew_hooks::{use_async_with_options, UseAsyncOptions};
+use yew_hooks::{use_async_with_options, use_interval, UseAsyncOptions};
 
 #[wasm_bindgen]
 extern "C" {
@@ -11,24 +11,92 @@ extern "C" {
     async fn invoke(cmd: &str, args: JsValue) -> JsValue;
 }
 
+/// How often the interface list, and with it the traffic rates, is refreshed.
+const POLL_INTERVAL_MS: u32 = 2000;
+
+/// The option text: name, main address, state and current traffic.
+fn interface_label(interface: &InterfaceInfo) -> String {
+    let mut label = interface.name.clone();
+    if let Some(address) = interface
+        .addresses
+        .iter()
+        .find(|address| !address.ip.contains(':'))
+        .or(interface.addresses.first())
+    {
+        label.push_str(&format!(" · {}/{}", address.ip, address.prefix));
+    }
+    if !interface.flags.up {
+        label.push_str(" · down");
+    } else if let Some(rates) = &interface.rates {
+        let rate = rates.rx_bytes_per_sec + rates.tx_bytes_per_sec;
+        label.push_str(&format!(" · {:.1} KB/s", rate / 1024.0));
+    }
+    label
+}
+
+/// Everything else we know about the interface, shown on hover.
+fn interface_details(interface: &InterfaceInfo) -> String {
+    let flags = &interface.flags;
+    let mut details = vec![format!("Link: {}", interface.link_type_name)];
+    if let Some(mac) = &interface.mac {
+        details.push(format!("MAC: {}", mac));
+    }
+    if let Some(mtu) = interface.mtu {
+        details.push(format!("MTU: {}", mtu));
+    }
+    for address in &interface.addresses {
+        details.push(format!("{}/{}", address.ip, address.prefix));
+    }
+    let state: Vec<&str> = [
+        (flags.up, "up"),
+        (flags.running, "running"),
+        (flags.promiscuous, "promiscuous"),
+        (flags.loopback, "loopback"),
+        (flags.point_to_point, "point-to-point"),
+    ]
+    .into_iter()
+    .filter_map(|(set, name)| set.then_some(name))
+    .collect();
+    details.push(format!("Flags: {}", state.join(", ")));
+    if let Some(counters) = &interface.counters {
+        details.push(format!(
+            "RX: {} packets, {} bytes",
+            counters.rx_packets, counters.rx_bytes
+        ));
+        details.push(format!(
+            "TX: {} packets, {} bytes",
+            counters.tx_packets, counters.tx_bytes
+        ));
+    }
+    if let Some(rates) = &interface.rates {
+        details.push(format!(
+            "Now: {:.1} KB/s in, {:.1} KB/s out",
+            rates.rx_bytes_per_sec / 1024.0,
+            rates.tx_bytes_per_sec / 1024.0
+        ));
+    }
+    details.join("\n")
+}
+
 #[function_component(SideNavBar)]
 pub fn sidenavbar() -> Html {
     let app_context: AppContext = use_context::<AppContext>().expect("No AppContext found!");
 
-    use_async_with_options(
+    let get_interfaces = use_async_with_options(
         {
             let app_context = app_context.clone();
             async move {
-                if let Ok(int) = serde_wasm_bindgen::from_value::<Vec<String>>(
+                if let Ok(interfaces) = serde_wasm_bindgen::from_value::<Vec<InterfaceInfo>>(
                     invoke("get_interfaces", JsValue::NULL).await,
                 ) {
-                    app_context.interfaces.set(int);
+                    app_context.interfaces.set(interfaces);
                 }
                 Ok::<(), ()>(())
             }
         },
         UseAsyncOptions::enable_auto(),
     );
+    use_interval(move || get_interfaces.run(), POLL_INTERVAL_MS);
 
     let cycle_theme = {
         let app_context = app_context.clone();
@@ -103,9 +171,9 @@ pub fn sidenavbar() -> Html {
     <form class="mx-auto px-2">
       <label for="interfaces" class="text-sm font-medium text-gray-900 dark:text-white">{"Interface"}</label>
       <select onchange={on_change_interface} ref={choosed_interface.clone()} id="interfaces" class="bg-gray-50 border border-gray-300 text-gray-900 text-sm rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-full dark:bg-gray-700 dark:border-gray-600 dark:placeholder-gray-400 dark:text-white dark:focus:ring-blue-500 dark:focus:border-blue-500">
-        <option selected=true>{"any"}</option>
-     { for (*(app_context.interfaces)).iter().map(|int_name| html!{
-        <option >{int_name}</option>
+        <option value="any" selected={*app_context.interface == "any"}>{"any"}</option>
+     { for (*(app_context.interfaces)).iter().map(|interface| html!{
+        <option value={interface.name.clone()} selected={*app_context.interface == interface.name} title={interface_details(interface)}>{interface_label(interface)}</option>
      })}
       </select>
     </form>